@@ -0,0 +1,113 @@
+/*
+ * Compares the JIT with the interpreter: `cargo bench --features jit`
+ *
+ * Both workloads are compiled with intcode::compiler, so they spend their time in function
+ * calls, relative addressing and the division helper, like the puzzle inputs.
+ */
+
+use std::time::{Duration, Instant};
+
+use advent_of_code_2019::intcode::IntCode;
+use advent_of_code_2019::intcode::Status;
+use advent_of_code_2019::intcode::compiler;
+use advent_of_code_2019::intcode::jit::Jit;
+
+// Counts the primes below its input by trial division; a single long run
+const PRIMES: &str = "
+fn main() {
+    var limit = input();
+    var count = 0;
+    var n = 2;
+    while (n < limit) {
+        var d = 2;
+        var prime = 1;
+        while (prime && d * d <= n) {
+            if (n % d == 0) { prime = 0; }
+            d = d + 1;
+        }
+        count = count + prime;
+        n = n + 1;
+    }
+    output(count);
+}";
+
+// Counts the steps for its input to reach 1 in the Collatz sequence; many short runs, like day 19's probes
+const COLLATZ: &str = "
+fn main() {
+    var n = input();
+    var steps = 0;
+    while (n != 1) {
+        if (n % 2 == 0) { n = n / 2; } else { n = 3 * n + 1; }
+        steps = steps + 1;
+    }
+    output(steps);
+}";
+
+trait Machine {
+    fn reset(&mut self);
+    fn push_input(&mut self, value: i64);
+    fn execute(&mut self) -> Status;
+}
+
+impl Machine for IntCode {
+    fn reset(&mut self) {
+        IntCode::reset(self);
+    }
+
+    fn push_input(&mut self, value: i64) {
+        IntCode::push_input(self, value);
+    }
+
+    fn execute(&mut self) -> Status {
+        IntCode::execute(self)
+    }
+}
+
+impl Machine for Jit {
+    fn reset(&mut self) {
+        Jit::reset(self);
+    }
+
+    fn push_input(&mut self, value: i64) {
+        Jit::push_input(self, value);
+    }
+
+    fn execute(&mut self) -> Status {
+        Jit::execute(self)
+    }
+}
+
+/*
+ * Runs the program from the start for every input, returning the outputs and how long it took.
+ */
+fn run<M: Machine>(machine: &mut M, inputs: &[i64]) -> (Vec<i64>, Duration) {
+    let start = Instant::now();
+    let outputs = inputs.iter().map(|input| {
+        machine.reset();
+        machine.push_input(*input);
+        match machine.execute() {
+            Status::Output(n) => n,
+            _ => panic!("Was expecting an output"),
+        }
+    }).collect();
+    (outputs, start.elapsed())
+}
+
+fn bench(name: &str, source: &str, inputs: &[i64]) {
+    let program = compiler::compile(source).unwrap();
+    let size = program.len() + 1000;
+    let mut machine = IntCode::new(program);
+    machine.resize(size);
+    let mut jit = Jit::new(machine.clone());
+
+    let (expected, interpreted) = run(&mut machine, inputs);
+    let (outputs, compiled) = run(&mut jit, inputs);
+    assert_eq!(outputs, expected);
+    println!("{:<8} interpreter {:>10.1?}   jit {:>10.1?}   {:.1}x faster",
+             name, interpreted, compiled, interpreted.as_secs_f64() / compiled.as_secs_f64());
+}
+
+fn main() {
+    bench("primes", PRIMES, &[20_000]);
+    bench("collatz", COLLATZ, &(1..3_000).collect::<Vec<_>>());
+}
@@ -0,0 +1,346 @@
+/*
+ * The day 25 text adventure as data.
+ *
+ * The droid's output is parsed into messages: room descriptions, inventory listings, the results
+ * of taking and dropping items, and the pressure-sensitive floor's verdict. Commands are sent
+ * through `Adventure`, which anything that plays the game (the Intcode droid, or a fake ship
+ * in tests) implements, so that scripts deal with `Message`s instead of matching on text.
+ */
+
+use std::fmt;
+
+use crate::intcode;
+
+pub mod shell;
+
+// Instructions the game may take to answer a command before it's considered stuck
+const STEP_LIMIT: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Room {
+    pub name: String,
+    pub description: String,
+    pub doors: Vec<Direction>,
+    pub items: Vec<String>,
+}
+
+// What the pressure-sensitive floor thinks of the droid's weight
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weight {
+    TooLight,
+    TooHeavy,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Room(Room),
+    Inventory(Vec<String>),
+    Took(String),
+    Dropped(String),
+    // Taking an item that isn't in the room
+    NoSuchItem,
+    // Dropping an item the droid isn't carrying
+    NotCarrying,
+    // Moving through a door that isn't there
+    NoDoor,
+    // Moving while the giant electromagnet is stuck to the droid
+    CantMove,
+    Unrecognized,
+    // Thrown back from the pressure-sensitive floor
+    Ejected(Weight),
+    // Let through by the pressure-sensitive floor, with the password for the airlock
+    Password(String),
+    // Anything else, like the ways the droid can die
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Go(Direction),
+    Take(String),
+    Drop(String),
+    Inventory,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reply {
+    // The game is waiting for the next command
+    Prompt(String),
+    Halted(String),
+    // The game didn't ask for a command within the step limit
+    Stuck(String),
+}
+
+/*
+ * A text adventure that can be copied to try out a command without consequences.
+ */
+pub trait Adventure: Clone {
+    // Sends a line of input, or nothing to start the game, and returns the game's answer
+    fn send(&mut self, line: &str) -> Reply;
+
+    fn command(&mut self, command: &Command) -> Reply {
+        self.send(&command.to_string())
+    }
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::North => "north",
+            Direction::South => "south",
+            Direction::East => "east",
+            Direction::West => "west",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL.iter().cloned().find(|direction| direction.name() == name)
+    }
+
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Go(direction) => write!(f, "{}", direction),
+            Command::Take(item) => write!(f, "take {}", item),
+            Command::Drop(item) => write!(f, "drop {}", item),
+            Command::Inventory => write!(f, "inv"),
+        }
+    }
+}
+
+impl Reply {
+    pub fn text(&self) -> &str {
+        match self {
+            Reply::Prompt(text) | Reply::Halted(text) | Reply::Stuck(text) => text,
+        }
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        parse(self.text())
+    }
+}
+
+impl Adventure for intcode::IntCode {
+    fn send(&mut self, line: &str) -> Reply {
+        if !line.is_empty() {
+            self.push_ascii(line);
+            self.push_ascii("\n");
+        }
+        let mut text = String::new();
+        let mut budget = STEP_LIMIT;
+        loop {
+            match self.execute_limited(&mut budget) {
+                Some(intcode::Status::Output(c)) => text.push(c as u8 as char),
+                Some(intcode::Status::WaitingInput) => return Reply::Prompt(text),
+                Some(intcode::Status::Halt) => return Reply::Halted(text),
+                None => return Reply::Stuck(text),
+            }
+        }
+    }
+}
+
+// Where the lines starting with "- " go
+enum List {
+    Doors,
+    Items,
+    Inventory,
+}
+
+fn password(line: &str) -> Option<String> {
+    let start = line.find("typing ")? + "typing ".len();
+    let digits: String = line[start..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() { None } else { Some(digits) }
+}
+
+/*
+ * Parses everything the game printed in answer to a command.
+ */
+pub fn parse(text: &str) -> Vec<Message> {
+    let mut messages = Vec::new();
+    let mut list = None;
+    // Whether the lines that follow are a room's description
+    let mut describing = false;
+    for line in text.lines().map(|line| line.trim_end()) {
+        if line.is_empty() {
+            list = None;
+            describing = false;
+            continue;
+        }
+        if let (Some(entry), Some(target)) = (line.strip_prefix("- "), &list) {
+            match (target, messages.last_mut()) {
+                (List::Doors, Some(Message::Room(room))) => {
+                    if let Some(direction) = Direction::from_name(entry) {
+                        room.doors.push(direction);
+                    }
+                }
+                (List::Items, Some(Message::Room(room))) => room.items.push(entry.to_string()),
+                (List::Inventory, Some(Message::Inventory(items))) => items.push(entry.to_string()),
+                _ => (),
+            }
+            continue;
+        }
+        if describing {
+            if let Some(Message::Room(room)) = messages.last_mut() {
+                if !room.description.is_empty() {
+                    room.description.push('\n');
+                }
+                room.description.push_str(line);
+                continue;
+            }
+        }
+        list = None;
+
+        let message = if line.starts_with("== ") && line.ends_with(" ==") {
+            describing = true;
+            let name = line[3..line.len() - 3].to_string();
+            Message::Room(Room { name, description: String::new(), doors: Vec::new(), items: Vec::new() })
+        } else if line == "Doors here lead:" || line == "Items here:" {
+            list = Some(if line == "Doors here lead:" { List::Doors } else { List::Items });
+            continue;
+        } else if line == "Items in your inventory:" {
+            list = Some(List::Inventory);
+            Message::Inventory(Vec::new())
+        } else if line == "You aren't carrying any items." {
+            Message::Inventory(Vec::new())
+        } else if let Some(item) = line.strip_prefix("You take the ").and_then(|rest| rest.strip_suffix('.')) {
+            Message::Took(item.to_string())
+        } else if let Some(item) = line.strip_prefix("You drop the ").and_then(|rest| rest.strip_suffix('.')) {
+            Message::Dropped(item.to_string())
+        } else if line == "You don't see that item here." {
+            Message::NoSuchItem
+        } else if line == "You don't have that item." {
+            Message::NotCarrying
+        } else if line == "You can't go that way." {
+            Message::NoDoor
+        } else if line.ends_with("You can't move!!") {
+            Message::CantMove
+        } else if line == "Unrecognized command." {
+            Message::Unrecognized
+        } else if line.contains("Droids on this ship are heavier") {
+            Message::Ejected(Weight::TooLight)
+        } else if line.contains("Droids on this ship are lighter") {
+            Message::Ejected(Weight::TooHeavy)
+        } else if let Some(password) = password(line) {
+            Message::Password(password)
+        } else if line == "Command?" {
+            continue;
+        } else {
+            Message::Text(line.to_string())
+        };
+        messages.push(message);
+    }
+    messages
+}
+
+/*
+ * The rooms the droid went through, in order; the last one is where it is now.
+ */
+pub fn rooms(messages: &[Message]) -> Vec<&Room> {
+    messages.iter().filter_map(|message| match message {
+        Message::Room(room) => Some(room),
+        _ => None,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(name: &str, description: &str, doors: &[Direction], items: &[&str]) -> Message {
+        Message::Room(Room {
+            name: name.to_string(),
+            description: description.to_string(),
+            doors: doors.to_vec(),
+            items: items.iter().map(|item| item.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn transcript() {
+        let start = "\n\n\n== Hull Breach ==\nYou got in through a hole in the floor here. To keep your ship from also \
+            freezing, the hole has been sealed.\n\nDoors here lead:\n- north\n- south\n- west\n\nCommand?\n";
+        assert_eq!(parse(start), [room("Hull Breach", "You got in through a hole in the floor here. To keep your ship \
+            from also freezing, the hole has been sealed.", &[Direction::North, Direction::South, Direction::West], &[])]);
+
+        let north = "\n\n\n== Kitchen ==\nEverything's freeze-dried.\n\nDoors here lead:\n- south\n\nItems here:\n\
+            - mug\n- giant electromagnet\n\nCommand?\n";
+        assert_eq!(parse(north), [room("Kitchen", "Everything's freeze-dried.", &[Direction::South], &["mug", "giant electromagnet"])]);
+
+        assert_eq!(parse("\nYou take the mug.\n\nCommand?\n"), [Message::Took("mug".to_string())]);
+        assert_eq!(parse("\nYou drop the mug.\n\nCommand?\n"), [Message::Dropped("mug".to_string())]);
+        assert_eq!(parse("\nYou don't see that item here.\n\nCommand?\n"), [Message::NoSuchItem]);
+        assert_eq!(parse("\nYou don't have that item.\n\nCommand?\n"), [Message::NotCarrying]);
+        assert_eq!(parse("\nItems in your inventory:\n- mug\n- food ration\n\nCommand?\n"),
+                   [Message::Inventory(vec!["mug".to_string(), "food ration".to_string()])]);
+        assert_eq!(parse("\nYou aren't carrying any items.\n\nCommand?\n"), [Message::Inventory(Vec::new())]);
+        assert_eq!(parse("\nYou can't go that way.\n\nCommand?\n"), [Message::NoDoor]);
+        assert_eq!(parse("\nThe giant electromagnet is stuck to you.  You can't move!!\n\nCommand?\n"), [Message::CantMove]);
+        assert_eq!(parse("\nUnrecognized command.\n\nCommand?\n"), [Message::Unrecognized]);
+        assert_eq!(parse("\nYou take the molten lava.\n\nThe molten lava is way too hot! You melt!\n"),
+                   [Message::Took("molten lava".to_string()), Message::Text("The molten lava is way too hot! You melt!".to_string())]);
+    }
+
+    #[test]
+    fn checkpoint() {
+        let ejected = "\n\n\n== Pressure-Sensitive Floor ==\nAnalyzing...\n\nDoors here lead:\n- south\n\n\
+            A loud, robotic voice says \"Alert! Droids on this ship are heavier than the detected value!\" and you are \
+            ejected back to the checkpoint.\n\n\n\n== Security Checkpoint ==\nIn the next room, a pressure-sensitive \
+            floor will verify your identity.\n\nDoors here lead:\n- north\n- east\n\nCommand?\n";
+        let messages = parse(ejected);
+        assert_eq!(messages[1], Message::Ejected(Weight::TooLight));
+        let names: Vec<&str> = rooms(&messages).iter().map(|room| room.name.as_str()).collect();
+        assert_eq!(names, ["Pressure-Sensitive Floor", "Security Checkpoint"]);
+
+        let passed = "\n\n\n== Pressure-Sensitive Floor ==\nAnalyzing...\n\nDoors here lead:\n- south\n\n\
+            A loud, robotic voice says \"Analysis complete! You may proceed.\" and you enter the cockpit.\n\
+            Santa notices your small droid, looks puzzled for a moment, realizes what has happened, and radios your ship directly.\n\
+            \"Oh, hello! You should be able to get in by typing 2424308736 on the keypad at the main airlock.\"\n";
+        assert_eq!(parse(passed).last(), Some(&Message::Password("2424308736".to_string())));
+    }
+
+    #[test]
+    fn replies() {
+        // Echoes a character, then loops forever on `w` and halts on anything else
+        let program = intcode::parse_program("3,20,4,20,1008,20,119,21,1005,21,12,99,1105,1,12").unwrap();
+        let mut droid = intcode::IntCode::new(program);
+        droid.resize(30);
+        assert_eq!(droid.send(""), Reply::Prompt(String::new()));
+        assert_eq!(droid.clone().send("x"), Reply::Halted("x".to_string()));
+        assert_eq!(droid.command(&Command::Go(Direction::West)), Reply::Stuck("w".to_string()));
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(Command::Go(Direction::West).to_string(), "west");
+        assert_eq!(Command::Take("space heater".to_string()).to_string(), "take space heater");
+        assert_eq!(Command::Drop("mug".to_string()).to_string(), "drop mug");
+        assert_eq!(Command::Inventory.to_string(), "inv");
+        assert_eq!(Direction::from_name("east").map(Direction::opposite), Some(Direction::West));
+    }
+}
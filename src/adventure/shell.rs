@@ -0,0 +1,292 @@
+/*
+ * Interactive shell for playing a text adventure by hand.
+ *
+ * Lines are passed on to the game, except for the shell's own commands:
+ *
+ *     n, s, e, w, i      north, south, east, west and inv
+ *     history            list the commands sent to the game so far
+ *     !!, !<n>           send the last command again, or the n-th one from the history
+ *     save <slot>        snapshot the game
+ *     load <slot>        go back to a snapshot, even after the droid died
+ *     saves              list the save slots
+ *     record <name>      start recording the commands sent to the game as a macro
+ *     end                stop recording
+ *     run <name>         send a macro's commands
+ *     macros             list the macros
+ *     help               show this list
+ *     quit               stop playing
+ */
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use crate::adventure::{Adventure, Reply};
+
+const HELP: &str = "\
+n, s, e, w, i      north, south, east, west and inv
+history            list the commands sent to the game so far
+!!, !<n>           send the last command again, or the n-th one from the history
+save <slot>        snapshot the game
+load <slot>        go back to a snapshot, even after the droid died
+saves              list the save slots
+record <name>      start recording the commands sent to the game as a macro
+end                stop recording
+run <name>         send a macro's commands
+macros             list the macros
+help               show this list
+quit               stop playing
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Playing,
+    // The game halted or stopped responding; only loading a save gets out of this
+    Over,
+    Quit,
+}
+
+struct Save<A> {
+    game: A,
+    state: State,
+    // What the game said last, shown again on loading
+    text: String,
+}
+
+pub struct Shell<A: Adventure> {
+    game: A,
+    state: State,
+    text: String,
+    history: Vec<String>,
+    saves: BTreeMap<String, Save<A>>,
+    macros: BTreeMap<String, Vec<String>>,
+    recording: Option<(String, Vec<String>)>,
+}
+
+/*
+ * Cleans up a line typed by the player: no line endings (including "\r\n" from Windows),
+ * no extra spaces, and lowercase like the game's commands.
+ */
+pub fn normalize(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn expand(command: &str) -> &str {
+    match command {
+        "n" => "north",
+        "s" => "south",
+        "e" => "east",
+        "w" => "west",
+        "i" | "inventory" => "inv",
+        _ => command,
+    }
+}
+
+impl<A: Adventure> Shell<A> {
+    pub fn new(game: A) -> Shell<A> {
+        Shell {
+            game,
+            state: State::Playing,
+            text: String::new(),
+            history: Vec::new(),
+            saves: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            recording: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Quit
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn macros(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.macros
+    }
+
+    /*
+     * Starts the game and returns its introduction.
+     */
+    pub fn start(&mut self) -> String {
+        self.send("")
+    }
+
+    fn send(&mut self, command: &str) -> String {
+        if self.state != State::Playing {
+            return "The game is over; load a save or quit.\n".to_string();
+        }
+        let text = match self.game.send(command) {
+            Reply::Prompt(text) => text,
+            Reply::Halted(text) => {
+                self.state = State::Over;
+                text + "\nThe game is over; load a save or quit.\n"
+            }
+            Reply::Stuck(text) => {
+                self.state = State::Over;
+                text + "\nThe game stopped responding; load a save or quit.\n"
+            }
+        };
+        if !command.is_empty() {
+            self.history.push(command.to_string());
+            if let Some((_, commands)) = &mut self.recording {
+                commands.push(command.to_string());
+            }
+        }
+        self.text = text.clone();
+        text
+    }
+
+    fn recall(&self, which: &str) -> Result<String, String> {
+        let index = if which == "!" {
+            self.history.len().checked_sub(1)
+        } else {
+            which.parse::<usize>().ok().and_then(|n| n.checked_sub(1))
+        };
+        index.and_then(|i| self.history.get(i).cloned()).ok_or(format!("No command !{} in the history.\n", which))
+    }
+
+    /*
+     * Handles a line typed by the player and returns what to show them.
+     */
+    pub fn input(&mut self, line: &str) -> String {
+        let line = normalize(line);
+        let (word, argument) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].to_string()),
+            None => (line.as_str(), String::new()),
+        };
+        let result = match (word, argument.is_empty()) {
+            ("", _) => Ok(String::new()),
+            ("help", true) => Ok(HELP.to_string()),
+            ("quit", true) => {
+                self.state = State::Quit;
+                Ok(String::new())
+            }
+            ("history", true) => Ok(self.history.iter().enumerate().map(|(i, command)| format!("{:>4}  {}\n", i + 1, command)).collect()),
+            (_, true) if word.starts_with('!') && word.len() > 1 => self.recall(&word[1..]).map(|command| self.send(&command)),
+            ("save", false) => {
+                let save = Save { game: self.game.clone(), state: self.state, text: self.text.clone() };
+                self.saves.insert(argument.clone(), save);
+                Ok(format!("Saved to slot `{}`.\n", argument))
+            }
+            ("load", false) => match self.saves.get(&argument) {
+                Some(save) => {
+                    self.game = save.game.clone();
+                    self.state = save.state;
+                    self.text = save.text.clone();
+                    Ok(format!("Loaded slot `{}`.\n{}", argument, self.text))
+                }
+                None => Err(format!("No save in slot `{}`.\n", argument)),
+            },
+            ("saves", true) => Ok(self.saves.keys().map(|slot| format!("{}\n", slot)).collect()),
+            ("record", false) => {
+                self.recording = Some((argument.clone(), Vec::new()));
+                Ok(format!("Recording macro `{}`; `end` stops.\n", argument))
+            }
+            ("end", true) => match self.recording.take() {
+                Some((name, commands)) => {
+                    let message = format!("Recorded macro `{}` with {} commands.\n", name, commands.len());
+                    self.macros.insert(name, commands);
+                    Ok(message)
+                }
+                None => Err("Not recording a macro.\n".to_string()),
+            },
+            ("run", false) => match self.macros.get(&argument).cloned() {
+                Some(commands) => Ok(commands.iter().map(|command| self.send(command)).collect()),
+                None => Err(format!("No macro named `{}`.\n", argument)),
+            },
+            ("macros", true) => Ok(self.macros.iter().map(|(name, commands)| format!("{}: {}\n", name, commands.join(", "))).collect()),
+            _ => Ok(self.send(&format!("{}{}{}", expand(word), if argument.is_empty() { "" } else { " " }, argument))),
+        };
+        result.unwrap_or_else(|error| error)
+    }
+
+    /*
+     * Plays with lines from `input` until the player quits or the input runs out.
+     */
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        write!(output, "{}", self.start())?;
+        output.flush()?;
+        for line in input.lines() {
+            write!(output, "{}", self.input(&line?))?;
+            output.flush()?;
+            if self.is_done() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Remembers everything it's told, and dies on `jump`
+    #[derive(Clone)]
+    struct Parrot {
+        heard: Vec<String>,
+    }
+
+    impl Adventure for Parrot {
+        fn send(&mut self, line: &str) -> Reply {
+            if line.is_empty() {
+                return Reply::Prompt("Hello!\n".to_string());
+            }
+            self.heard.push(line.to_string());
+            let text = format!("{}\n", self.heard.join(", "));
+            if line == "jump" { Reply::Halted(text) } else { Reply::Prompt(text) }
+        }
+    }
+
+    fn shell() -> Shell<Parrot> {
+        Shell::new(Parrot { heard: Vec::new() })
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(normalize("  Take   the MUG\r\n"), "take the mug");
+        let mut shell = shell();
+        assert_eq!(shell.start(), "Hello!\n");
+        assert_eq!(shell.input("n\r\n"), "north\n");
+        assert_eq!(shell.input("i"), "north, inv\n");
+        assert_eq!(shell.input("take mug"), "north, inv, take mug\n");
+        assert_eq!(shell.input("!1"), "north, inv, take mug, north\n");
+        assert_eq!(shell.input("!!"), "north, inv, take mug, north, north\n");
+        assert_eq!(shell.input("!9"), "No command !9 in the history.\n");
+        assert_eq!(shell.history().len(), 5);
+        assert_eq!(shell.input("history").lines().nth(2), Some("   3  take mug"));
+    }
+
+    #[test]
+    fn saves() {
+        let mut shell = shell();
+        shell.input("w");
+        assert_eq!(shell.input("save start"), "Saved to slot `start`.\n");
+        assert!(shell.input("jump").ends_with("The game is over; load a save or quit.\n"));
+        assert_eq!(shell.input("e"), "The game is over; load a save or quit.\n");
+        assert_eq!(shell.input("load start"), "Loaded slot `start`.\nwest\n");
+        assert_eq!(shell.input("e"), "west, east\n");
+        assert_eq!(shell.input("load nowhere"), "No save in slot `nowhere`.\n");
+        assert_eq!(shell.input("saves"), "start\n");
+    }
+
+    #[test]
+    fn macros() {
+        let mut shell = shell();
+        shell.input("record loop");
+        shell.input("n");
+        shell.input("e");
+        assert_eq!(shell.input("end"), "Recorded macro `loop` with 2 commands.\n");
+        assert_eq!(shell.input("run loop"), "north, east, north\nnorth, east, north, east\n");
+        assert_eq!(shell.macros()["loop"], ["north", "east"]);
+        assert_eq!(shell.input("macros"), "loop: north, east\n");
+
+        let mut output = Vec::new();
+        shell.run(&b"s\r\nquit\nn\n"[..], &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Hello!\nnorth, east, north, east, south\n");
+    }
+}
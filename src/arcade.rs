@@ -0,0 +1,330 @@
+/*
+ * Headless arcade cabinet for the day 13 breakout game.
+ *
+ * The game draws by outputting (x, y, tile) triples, except that (-1, 0, n) sets the score,
+ * and asks for the joystick position once per frame. The arcade keeps the screen as a grid
+ * of tiles, and the joystick is moved by a `Controller` between frames.
+ */
+
+use std::fmt;
+
+use crate::intcode;
+use crate::intcode::IntCode;
+
+pub mod terminal;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Joystick {
+    Left,
+    Neutral,
+    Right,
+}
+
+/*
+ * Decides where to tilt the joystick, given the screen after a frame.
+ */
+pub trait Controller {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick;
+}
+
+/*
+ * Keeps the paddle under the ball, which is enough to break every block.
+ */
+pub struct FollowBall;
+
+#[derive(Clone)]
+pub struct Arcade {
+    machine: IntCode,
+    screen: Vec<Vec<Tile>>,
+    score: i64,
+    ball: Option<(usize, usize)>,
+    paddle: Option<(usize, usize)>,
+}
+
+impl Tile {
+    pub fn from_id(id: i64) -> Option<Tile> {
+        match id {
+            0 => Some(Tile::Empty),
+            1 => Some(Tile::Wall),
+            2 => Some(Tile::Block),
+            3 => Some(Tile::Paddle),
+            4 => Some(Tile::Ball),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Tile::Empty => ' ',
+            Tile::Wall => '#',
+            Tile::Block => '%',
+            Tile::Paddle => '=',
+            Tile::Ball => 'o',
+        }
+    }
+}
+
+impl Joystick {
+    pub fn value(self) -> i64 {
+        match self {
+            Joystick::Left => -1,
+            Joystick::Neutral => 0,
+            Joystick::Right => 1,
+        }
+    }
+}
+
+impl Controller for FollowBall {
+    fn joystick(&mut self, arcade: &Arcade) -> Joystick {
+        match (arcade.ball(), arcade.paddle()) {
+            (Some((ball, _)), Some((paddle, _))) if ball < paddle => Joystick::Left,
+            (Some((ball, _)), Some((paddle, _))) if ball > paddle => Joystick::Right,
+            _ => Joystick::Neutral,
+        }
+    }
+}
+
+impl Arcade {
+    pub fn new(machine: IntCode) -> Arcade {
+        Arcade { machine, screen: Vec::new(), score: 0, ball: None, paddle: None }
+    }
+
+    /*
+     * Sets the game to free play, as part 2 asks; without quarters it only draws the screen.
+     */
+    pub fn insert_quarters(&mut self) {
+        self.machine.poke(0, 2).unwrap();
+    }
+
+    pub fn score(&self) -> i64 {
+        self.score
+    }
+
+    pub fn ball(&self) -> Option<(usize, usize)> {
+        self.ball
+    }
+
+    pub fn paddle(&self) -> Option<(usize, usize)> {
+        self.paddle
+    }
+
+    pub fn width(&self) -> usize {
+        self.screen.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.screen.len()
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Tile {
+        self.screen.get(y).and_then(|row| row.get(x)).cloned().unwrap_or(Tile::Empty)
+    }
+
+    pub fn count(&self, tile: Tile) -> usize {
+        self.screen.iter().flatten().filter(|t| **t == tile).count()
+    }
+
+    fn draw(&mut self, x: i64, y: i64, id: i64) {
+        if (x, y) == (-1, 0) {
+            self.score = id;
+            return;
+        }
+        if x < 0 || y < 0 {
+            panic!("Tile drawn off screen at ({}, {})", x, y);
+        }
+        let tile = Tile::from_id(id).unwrap_or_else(|| panic!("Invalid tile id {}", id));
+        let (x, y) = (x as usize, y as usize);
+        if self.screen.len() <= y {
+            self.screen.resize(y + 1, Vec::new());
+        }
+        let row = &mut self.screen[y];
+        if row.len() <= x {
+            row.resize(x + 1, Tile::Empty);
+        }
+        row[x] = tile;
+
+        // Drawing over the ball or paddle means they have moved, so only forget them then
+        match tile {
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::Paddle => self.paddle = Some((x, y)),
+            _ if self.ball == Some((x, y)) => self.ball = None,
+            _ if self.paddle == Some((x, y)) => self.paddle = None,
+            _ => (),
+        }
+    }
+
+    fn next_output(&mut self, expecting: &str) -> i64 {
+        match self.machine.execute() {
+            intcode::Status::Output(n) => n,
+            _ => panic!("Was expecting {}", expecting),
+        }
+    }
+
+    /*
+     * Runs the game until it finishes a frame and asks for the joystick, or halts.
+     * Returns whether the game is still going.
+     */
+    pub fn update(&mut self) -> bool {
+        loop {
+            match self.machine.execute() {
+                intcode::Status::Output(x) => {
+                    let y = self.next_output("y-coordinate");
+                    let id = self.next_output("tile type or score");
+                    self.draw(x, y, id);
+                }
+                intcode::Status::WaitingInput => return true,
+                intcode::Status::Halt => return false,
+            }
+        }
+    }
+
+    pub fn tilt(&mut self, joystick: Joystick) {
+        self.machine.push_input(joystick.value());
+    }
+
+    /*
+     * Plays until the game halts, and returns the final score.
+     */
+    pub fn play<C: Controller>(&mut self, controller: &mut C) -> i64 {
+        while self.update() {
+            let joystick = controller.joystick(self);
+            self.tilt(joystick);
+        }
+        self.score
+    }
+}
+
+impl fmt::Display for Arcade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Score: {}", self.score)?;
+        for row in &self.screen {
+            let line: String = row.iter().map(|tile| tile.symbol()).collect();
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::compiler;
+
+    // A small breakout game speaking the same protocol; the ball is lost if the paddle isn't under it
+    const GAME: &str = "
+        var blocks[10];
+        fn draw(x, y, tile) { output(x); output(y); output(tile); }
+        fn hit(x, y) {
+            if (y < 1 || y > 2) { return 0; }
+            var i = (y - 1) * 5 + x - 1;
+            var hit = blocks[i];
+            blocks[i] = 0;
+            return hit;
+        }
+        fn main() {
+            var x = 0;
+            while (x < 7) { draw(x, 0, 1); x = x + 1; }
+            var y = 1;
+            while (y < 6) { draw(0, y, 1); draw(6, y, 1); y = y + 1; }
+            var i = 0;
+            while (i < 10) {
+                x = i % 5 + 1;
+                y = i / 5 + 1;
+                if ((x + y) % 2 == 0) { blocks[i] = 1; draw(x, y, 2); }
+                i = i + 1;
+            }
+            var px = 3;
+            var bx = 1;
+            var by = 3;
+            var dx = 1;
+            var dy = 1;
+            var score = 0;
+            var left = 5;
+            draw(px, 5, 3);
+            draw(bx, by, 4);
+            draw(-1, 0, score);
+            while (left > 0) {
+                var j = input();
+                if (px + j > 0 && px + j < 6) { draw(px, 5, 0); px = px + j; draw(px, 5, 3); }
+                var nx = bx + dx;
+                if (nx < 1 || nx > 5) { dx = -dx; nx = bx + dx; }
+                var ny = by + dy;
+                if (ny < 1 || hit(nx, ny)) {
+                    if (ny >= 1) {
+                        left = left - 1;
+                        score = score + 10;
+                        draw(nx, ny, 0);
+                        draw(-1, 0, score);
+                    }
+                    dy = -dy;
+                    ny = by + dy;
+                }
+                if (ny == 5) {
+                    if (bx != px) { halt(); }
+                    dy = -dy;
+                    ny = by + dy;
+                }
+                draw(bx, by, 0);
+                bx = nx;
+                by = ny;
+                draw(bx, by, 4);
+            }
+        }";
+
+    fn game() -> IntCode {
+        let mut machine = IntCode::new(compiler::compile(GAME).unwrap());
+        machine.resize(machine.memory_size() + 500);
+        machine
+    }
+
+    #[test]
+    fn screen() {
+        let mut arcade = Arcade::new(game());
+        assert!(arcade.update());
+        assert_eq!(arcade.to_string(), "\
+Score: 0
+#######
+#% % %#
+# % % #
+#o    #
+#     #
+#  =  #
+");
+        assert_eq!((arcade.width(), arcade.height()), (7, 6));
+        assert_eq!(arcade.count(Tile::Block), 5);
+        assert_eq!(arcade.ball(), Some((1, 3)));
+        assert_eq!(arcade.paddle(), Some((3, 5)));
+
+        arcade.tilt(Joystick::Left);
+        assert!(arcade.update());
+        assert_eq!(arcade.ball(), Some((2, 4)));
+        assert_eq!(arcade.paddle(), Some((2, 5)));
+        assert_eq!(arcade.tile(3, 5), Tile::Empty);
+    }
+
+    #[test]
+    fn play() {
+        let mut arcade = Arcade::new(game());
+        assert_eq!(arcade.play(&mut FollowBall), 50);
+        assert_eq!(arcade.count(Tile::Block), 0);
+
+        struct Idle;
+        impl Controller for Idle {
+            fn joystick(&mut self, _: &Arcade) -> Joystick {
+                Joystick::Neutral
+            }
+        }
+        let mut arcade = Arcade::new(game());
+        assert!(arcade.play(&mut Idle) < 50);
+        assert!(arcade.count(Tile::Block) > 0);
+    }
+}
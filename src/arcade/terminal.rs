@@ -0,0 +1,214 @@
+/*
+ * Terminal front end for the arcade, drawing the screen with ANSI escape codes.
+ *
+ * Keys are read without waiting, so the input has to be a terminal in non-canonical mode
+ * (`stty -icanon -echo min 0 time 0`) for the game to keep running between key presses.
+ * A key only tilts the joystick for the next frame, since terminals don't report key releases.
+ */
+
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::arcade::{Arcade, Controller, Joystick, Tile};
+
+const CLEAR: &str = "\x1b[2J";
+const HOME: &str = "\x1b[H";
+const CLEAR_LINE: &str = "\x1b[K";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Joystick(Joystick),
+    Quit,
+}
+
+pub struct Terminal<R: Read, W: Write> {
+    input: R,
+    output: W,
+    frame_time: Option<Duration>,
+    last_frame: Option<Instant>,
+    // Plays in place of the keyboard, leaving only quitting to the player
+    autopilot: Option<Box<dyn Controller>>,
+}
+
+/*
+ * Draws each tile two characters wide, so that the screen comes out roughly square.
+ */
+fn tile(tile: Tile) -> String {
+    match tile {
+        Tile::Empty => "  ".to_string(),
+        Tile::Wall => format!("\x1b[47m  {}", RESET),
+        Tile::Block => format!("\x1b[44m[]{}", RESET),
+        Tile::Paddle => format!("\x1b[1;37m=={}", RESET),
+        Tile::Ball => format!("\x1b[1;33m(){}", RESET),
+    }
+}
+
+/*
+ * Draws the score and the screen over the previous frame.
+ */
+pub fn render(arcade: &Arcade) -> String {
+    let mut frame = format!("{}Score: {}{}\n", HOME, arcade.score(), CLEAR_LINE);
+    for y in 0..arcade.height() {
+        for x in 0..arcade.width() {
+            frame.push_str(&tile(arcade.tile(x, y)));
+        }
+        frame.push_str(CLEAR_LINE);
+        frame.push('\n');
+    }
+    frame
+}
+
+/*
+ * Finds the last key pressed in some terminal input: a/d or the arrow keys to move, s or space to
+ * stay, q to quit.
+ */
+pub fn parse_keys(bytes: &[u8]) -> Option<Key> {
+    let mut key = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let arrow = if bytes[i..].starts_with(b"\x1b[") { bytes.get(i + 2).cloned() } else { None };
+        let pressed = match (arrow, bytes[i]) {
+            (Some(b'D'), _) => Some(Key::Joystick(Joystick::Left)),
+            (Some(b'C'), _) => Some(Key::Joystick(Joystick::Right)),
+            (Some(_), _) => None,
+            (None, b'a') | (None, b'h') => Some(Key::Joystick(Joystick::Left)),
+            (None, b'd') | (None, b'l') => Some(Key::Joystick(Joystick::Right)),
+            (None, b's') | (None, b' ') => Some(Key::Joystick(Joystick::Neutral)),
+            (None, b'q') => Some(Key::Quit),
+            _ => None,
+        };
+        // Quitting wins over anything pressed after it
+        if key != Some(Key::Quit) {
+            key = pressed.or(key);
+        }
+        i += if arrow.is_some() { 3 } else { 1 };
+    }
+    key
+}
+
+impl<R: Read, W: Write> Terminal<R, W> {
+    /*
+     * Makes a terminal drawing to `output` and reading keys from `input`, showing at most
+     * `fps` frames a second, or as many as it can for 0.
+     */
+    pub fn new(input: R, output: W, fps: u32) -> Terminal<R, W> {
+        let frame_time = if fps == 0 { None } else { Some(Duration::from_secs(1) / fps) };
+        Terminal { input, output, frame_time, last_frame: None, autopilot: None }
+    }
+
+    pub fn with_autopilot<C: Controller + 'static>(mut self, controller: C) -> Terminal<R, W> {
+        self.autopilot = Some(Box::new(controller));
+        self
+    }
+
+    fn draw(&mut self, arcade: &Arcade) -> io::Result<()> {
+        if self.last_frame.is_none() {
+            self.output.write_all(CLEAR.as_bytes())?;
+        }
+        self.output.write_all(render(arcade).as_bytes())?;
+        self.output.flush()
+    }
+
+    /*
+     * Shows a frame and reads the joystick for the next one, or None if the player quit.
+     */
+    pub fn frame(&mut self, arcade: &Arcade) -> io::Result<Option<Joystick>> {
+        self.draw(arcade)?;
+        if let (Some(frame_time), Some(last_frame)) = (self.frame_time, self.last_frame) {
+            let elapsed = last_frame.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+
+        let mut buffer = [0; 64];
+        let mut keys = Vec::new();
+        loop {
+            let n = self.input.read(&mut buffer)?;
+            keys.extend_from_slice(&buffer[..n]);
+            if n < buffer.len() {
+                break;
+            }
+        }
+        let joystick = match parse_keys(&keys) {
+            Some(Key::Quit) => return Ok(None),
+            Some(Key::Joystick(joystick)) => joystick,
+            None => Joystick::Neutral,
+        };
+        Ok(Some(match &mut self.autopilot {
+            Some(autopilot) => autopilot.joystick(arcade),
+            None => joystick,
+        }))
+    }
+
+    /*
+     * Plays until the game halts or the player quits, and returns the score.
+     */
+    pub fn play(&mut self, arcade: &mut Arcade) -> io::Result<i64> {
+        while arcade.update() {
+            match self.frame(arcade)? {
+                Some(joystick) => arcade.tilt(joystick),
+                None => return Ok(arcade.score()),
+            }
+        }
+        self.draw(arcade)?;
+        Ok(arcade.score())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arcade::FollowBall;
+    use crate::intcode::IntCode;
+
+    fn arcade() -> Arcade {
+        let mut arcade = Arcade::new(IntCode::new(vec![99]));
+        arcade.draw(0, 0, 1);
+        arcade.draw(1, 1, 4);
+        arcade.draw(2, 1, 3);
+        arcade.draw(-1, 0, 7);
+        arcade
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(parse_keys(b""), None);
+        assert_eq!(parse_keys(b"x"), None);
+        assert_eq!(parse_keys(b"a"), Some(Key::Joystick(Joystick::Left)));
+        assert_eq!(parse_keys(b"ad"), Some(Key::Joystick(Joystick::Right)));
+        assert_eq!(parse_keys(b"\x1b[C\x1b[D"), Some(Key::Joystick(Joystick::Left)));
+        assert_eq!(parse_keys(b"\x1b[Ax"), None);
+        assert_eq!(parse_keys(b"aqd"), Some(Key::Quit));
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(render(&arcade()), format!(
+            "\x1b[HScore: 7\x1b[K\n\x1b[47m  \x1b[0m    \x1b[K\n  \x1b[1;33m()\x1b[0m\x1b[1;37m==\x1b[0m\x1b[K\n"));
+
+        let mut output = Vec::new();
+        let mut terminal = Terminal::new(&b"d"[..], &mut output, 0);
+        assert_eq!(terminal.frame(&arcade()).unwrap(), Some(Joystick::Right));
+        assert_eq!(terminal.frame(&arcade()).unwrap(), Some(Joystick::Neutral));
+        let frame = render(&arcade());
+        assert_eq!(String::from_utf8(output).unwrap(), format!("{}{}{}", CLEAR, frame, frame));
+
+        let mut terminal = Terminal::new(&b"q"[..], io::sink(), 0).with_autopilot(FollowBall);
+        assert_eq!(terminal.frame(&arcade()).unwrap(), None);
+        assert_eq!(terminal.frame(&arcade()).unwrap(), Some(Joystick::Left));
+
+        // Frames after the first are held back to the frame rate
+        let mut terminal = Terminal::new(io::empty(), io::sink(), 50);
+        let start = Instant::now();
+        for _ in 0..3 {
+            terminal.frame(&arcade()).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
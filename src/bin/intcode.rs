@@ -0,0 +1,467 @@
+/*
+ * Command-line runner for Intcode programs.
+ *
+ * Loads a program from a file and runs it, feeding it inputs from the command line,
+ * from files and finally from stdin whenever the program asks for more.
+ */
+
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::process::Stdio;
+
+use advent_of_code_2019::adventure::shell::Shell;
+use advent_of_code_2019::arcade::{Arcade, FollowBall};
+use advent_of_code_2019::arcade::terminal::Terminal;
+use advent_of_code_2019::day11;
+use advent_of_code_2019::intcode;
+use advent_of_code_2019::intcode::coverage::Coverage;
+use advent_of_code_2019::intcode::decompile;
+use advent_of_code_2019::intcode::disasm;
+use advent_of_code_2019::intcode::patch::Patch;
+
+const USAGE: &str = "\
+Usage: intcode <command> [options] <program>
+
+Commands:
+    run       Run the program
+    disasm    Print a disassembly of the program
+    decompile Print the program as C-like pseudo-code
+    arcade    Play the day 13 game in the terminal
+    adventure Play the day 25 game with save slots, history and macros (type `help` once it starts)
+    paint     Run the day 11 painting robot and save the hull as an image
+
+Options for run:
+    -i, --input <values>      Queue comma-separated values (or text with --ascii) as input
+    -f, --input-file <path>   Queue the contents of a file as input
+    -a, --ascii               Read input as text and print outputs below 128 as characters
+    -t, --trace               Print every instruction to stderr before executing it
+    -c, --coverage            Print a disassembly with hit counts to stderr once the program stops
+    -p, --patch <path>        Apply a patch file to the program before running it
+    -l, --max-steps <n>       Stop after executing n instructions
+    -m, --memory <cells>      Size of the machine's memory (default 16384)
+
+Options for arcade:
+    -p, --patch <path>        Apply a patch file to the game before playing it
+    -m, --memory <cells>      Size of the machine's memory (default 16384)
+    --fps <n>                 Show at most n frames a second, or as many as possible for 0 (default 30)
+    --auto                    Let the game play itself; q still quits
+
+Options for adventure:
+    -p, --patch <path>        Apply a patch file to the game before playing it
+    -m, --memory <cells>      Size of the machine's memory (default 16384)
+
+Options for paint:
+    -p, --patch <path>        Apply a patch file to the robot before running it
+    -m, --memory <cells>      Size of the machine's memory (default 16384)
+    -o, --output <path>       Where to save the hull, as .pbm, .pgm or .png (default hull.png)
+    --white                   Start the robot on a white panel, as part 2 does
+    --scale <n>               Draw every panel as n by n pixels (default 1)
+    --frames <dir>            Also save a PNG of every step of the painting into dir
+
+The arcade moves the joystick with a/d or the arrow keys, and quits with q.
+Once all queued input is used up, further input is read from stdin a line at a time.";
+
+const DEFAULT_MEMORY: usize = 16_384;
+const DEFAULT_FPS: u32 = 30;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Run,
+    Disasm,
+    Decompile,
+    Arcade,
+    Adventure,
+    Paint,
+}
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Values(String),
+    File(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    program: String,
+    inputs: Vec<Input>,
+    ascii: bool,
+    trace: bool,
+    coverage: bool,
+    patches: Vec<String>,
+    max_steps: Option<usize>,
+    memory: usize,
+    fps: u32,
+    auto: bool,
+    output: String,
+    white: bool,
+    scale: usize,
+    frames: Option<String>,
+}
+
+fn command_name(command: &Command) -> &'static str {
+    match command {
+        Command::Run => "run",
+        Command::Disasm => "disasm",
+        Command::Decompile => "decompile",
+        Command::Arcade => "arcade",
+        Command::Adventure => "adventure",
+        Command::Paint => "paint",
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let command = match args.next().as_deref() {
+        Some("run") => Command::Run,
+        Some("disasm") => Command::Disasm,
+        Some("decompile") => Command::Decompile,
+        Some("arcade") => Command::Arcade,
+        Some("adventure") => Command::Adventure,
+        Some("paint") => Command::Paint,
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut options = Options {
+        command,
+        program: String::new(),
+        inputs: Vec::new(),
+        ascii: false,
+        trace: false,
+        coverage: false,
+        patches: Vec::new(),
+        max_steps: None,
+        memory: DEFAULT_MEMORY,
+        fps: DEFAULT_FPS,
+        auto: false,
+        output: "hull.png".to_string(),
+        white: false,
+        scale: 1,
+        frames: None,
+    };
+    let mut program = None;
+
+    while let Some(arg) = args.next() {
+        let allowed = match options.command {
+            Command::Run => ["-i", "--input", "-f", "--input-file", "-a", "--ascii", "-t", "--trace", "-c", "--coverage",
+                "-p", "--patch", "-l", "--max-steps", "-m", "--memory"].contains(&arg.as_str()),
+            Command::Disasm | Command::Decompile => false,
+            Command::Arcade => ["-p", "--patch", "-m", "--memory", "--fps", "--auto"].contains(&arg.as_str()),
+            Command::Adventure => ["-p", "--patch", "-m", "--memory"].contains(&arg.as_str()),
+            Command::Paint => ["-p", "--patch", "-m", "--memory", "-o", "--output", "--white", "--scale", "--frames"]
+                .contains(&arg.as_str()),
+        };
+        if arg.starts_with('-') && !allowed {
+            return Err(format!("{} doesn't take option `{}`", command_name(&options.command), arg));
+        }
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for {}", name));
+        match arg.as_str() {
+            "-i" | "--input" => options.inputs.push(Input::Values(value(&arg)?)),
+            "-f" | "--input-file" => options.inputs.push(Input::File(value(&arg)?)),
+            "-a" | "--ascii" => options.ascii = true,
+            "-t" | "--trace" => options.trace = true,
+            "-c" | "--coverage" => options.coverage = true,
+            "-p" | "--patch" => options.patches.push(value(&arg)?),
+            "-l" | "--max-steps" => {
+                let n = value(&arg)?;
+                options.max_steps = Some(n.parse().map_err(|_| format!("invalid step count `{}`", n))?);
+            }
+            "-m" | "--memory" => {
+                let n = value(&arg)?;
+                options.memory = n.parse().map_err(|_| format!("invalid memory size `{}`", n))?;
+            }
+            "--fps" => {
+                let n = value(&arg)?;
+                options.fps = n.parse().map_err(|_| format!("invalid frame rate `{}`", n))?;
+            }
+            "--auto" => options.auto = true,
+            "-o" | "--output" => options.output = value(&arg)?,
+            "--white" => options.white = true,
+            "--scale" => {
+                let n = value(&arg)?;
+                options.scale = n.parse().ok().filter(|n| *n > 0).ok_or(format!("invalid scale `{}`", n))?;
+            }
+            "--frames" => options.frames = Some(value(&arg)?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ if program.is_none() => program = Some(arg),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    options.program = program.ok_or("missing program")?;
+    Ok(options)
+}
+
+/*
+ * Converts text into input values; either as ASCII codes or as a list of numbers.
+ */
+fn encode_input(text: &str, ascii: bool) -> Result<Vec<i64>, String> {
+    if ascii {
+        // Normalise line endings, since the ASCII programs only understand '\n'
+        Ok(text.replace("\r\n", "\n").bytes().map(|b| b as i64).collect())
+    } else {
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse().map_err(|_| format!("invalid input value `{}`", s)))
+            .collect()
+    }
+}
+
+fn run(options: &Options, machine: &mut intcode::IntCode, coverage: &mut Coverage) -> Result<(), String> {
+    for input in &options.inputs {
+        let text = match input {
+            Input::Values(values) if options.ascii => format!("{}\n", values),
+            Input::Values(values) => values.clone(),
+            Input::File(path) => fs::read_to_string(path)
+                .map_err(|e| format!("could not read {}: {}", path, e))?,
+        };
+        machine.extend_input(encode_input(&text, options.ascii)?);
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut budget = options.max_steps;
+    let mut resumed = false;
+
+    loop {
+        if budget == Some(0) {
+            stdout.flush().map_err(|e| e.to_string())?;
+            return Err(format!("step limit of {} reached", options.max_steps.unwrap()));
+        }
+        // Don't trace an input instruction again after reading from stdin
+        if options.trace && !resumed {
+            eprintln!("{}", disasm::trace(machine));
+        }
+        resumed = false;
+
+        let status = if options.coverage { coverage.step(machine) } else { machine.step() };
+        match status {
+            None => (),
+            Some(intcode::Status::Output(n)) => {
+                if options.ascii && (0..128).contains(&n) {
+                    write!(stdout, "{}", n as u8 as char)
+                } else {
+                    writeln!(stdout, "{}", n)
+                }.map_err(|e| e.to_string())?;
+            }
+            Some(intcode::Status::WaitingInput) => {
+                stdout.flush().map_err(|e| e.to_string())?;
+                let mut line = String::new();
+                match stdin.lock().read_line(&mut line) {
+                    Ok(0) => return Err("program is waiting for input, but there is none left".to_string()),
+                    Ok(_) => machine.extend_input(encode_input(&line, options.ascii)?),
+                    Err(e) => return Err(format!("error reading from stdin: {}", e)),
+                }
+                resumed = true;
+                continue;
+            }
+            Some(intcode::Status::Halt) => return stdout.flush().map_err(|e| e.to_string()),
+        }
+        budget = budget.map(|n| n - 1);
+    }
+}
+
+/*
+ * Loads the program into a machine with the requested memory size and patches.
+ */
+fn build_machine(options: &Options, mut program: Vec<i64>) -> intcode::IntCode {
+    if program.len() < options.memory {
+        program.resize(options.memory, 0);
+    }
+    let mut machine = intcode::IntCode::new(program);
+    for path in &options.patches {
+        if let Err(e) = Patch::load(path).and_then(|patch| patch.apply(&mut machine)) {
+            eprintln!("intcode: could not apply patch {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    machine
+}
+
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = process::Command::new("stty").args(args).stdin(Stdio::inherit()).output()
+        .map_err(|e| format!("could not run stty: {}", e))?;
+    if !output.status.success() {
+        return Err("stdin is not a terminal".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/*
+ * Switches the terminal to reading keys as they're pressed, until dropped.
+ * Restoring it on drop means a panicking game doesn't leave the shell without echo.
+ */
+struct KeyMode {
+    saved: String,
+}
+
+impl KeyMode {
+    fn enter() -> Result<KeyMode, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "min", "0", "time", "0"])?;
+        Ok(KeyMode { saved })
+    }
+}
+
+impl Drop for KeyMode {
+    fn drop(&mut self) {
+        if let Err(e) = stty(&[&self.saved]) {
+            eprintln!("intcode: could not restore the terminal: {}", e);
+        }
+    }
+}
+
+/*
+ * Plays the game with the terminal reading keys as they're pressed.
+ */
+fn play(options: &Options, arcade: &mut Arcade) -> Result<i64, String> {
+    let _keys = KeyMode::enter()?;
+    let mut terminal = Terminal::new(io::stdin(), io::stdout(), options.fps);
+    if options.auto {
+        terminal = terminal.with_autopilot(FollowBall);
+    }
+    terminal.play(arcade).map_err(|e| e.to_string())
+}
+
+/*
+ * Saves the hull, and every step of painting it if asked to.
+ */
+fn paint(options: &Options, robot: &intcode::IntCode) -> Result<(), String> {
+    let start = if options.white { day11::Color::White } else { day11::Color::Black };
+    day11::picture(robot, start).scaled(options.scale).save(&options.output)
+        .map_err(|e| format!("could not save {}: {}", options.output, e))?;
+    if let Some(dir) = &options.frames {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir, e))?;
+        let mut step = 0;
+        day11::animate(robot, start, |frame| {
+            step += 1;
+            frame.scaled(options.scale).save(Path::new(dir).join(format!("{:05}.png", step - 1)))
+        }).map_err(|e| format!("could not save frames into {}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("intcode: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    let program = fs::read_to_string(&options.program)
+        .map_err(|e| format!("could not read {}: {}", options.program, e))
+        .and_then(|s| intcode::parse_program(&s).map_err(|e| format!("invalid program: {}", e)));
+    let program = match program {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("intcode: {}", e);
+            process::exit(1);
+        }
+    };
+
+    match options.command {
+        Command::Disasm => {
+            for line in disasm::disassemble(&program) {
+                println!("{}", line);
+            }
+        }
+        Command::Decompile => print!("{}", decompile::decompile(&program)),
+        Command::Run => {
+            let image = program.clone();
+            let mut machine = build_machine(&options, program);
+            let mut coverage = Coverage::new();
+            let result = run(&options, &mut machine, &mut coverage);
+            if options.coverage {
+                eprint!("{}", coverage.render(&image));
+            }
+            if let Err(e) = result {
+                eprintln!("intcode: {}", e);
+                process::exit(1);
+            }
+        }
+        Command::Arcade => {
+            let mut arcade = Arcade::new(build_machine(&options, program));
+            arcade.insert_quarters();
+            match play(&options, &mut arcade) {
+                Ok(score) => println!("Final score: {}", score),
+                Err(e) => {
+                    eprintln!("intcode: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+        Command::Adventure => {
+            let mut shell = Shell::new(build_machine(&options, program));
+            let stdin = io::stdin();
+            if let Err(e) = shell.run(stdin.lock(), io::stdout()) {
+                eprintln!("intcode: {}", e);
+                process::exit(1);
+            }
+        }
+        Command::Paint => {
+            if let Err(e) = paint(&options, &build_machine(&options, program)) {
+                eprintln!("intcode: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> impl Iterator<Item = String> + '_ {
+        s.split_whitespace().map(String::from)
+    }
+
+    #[test]
+    fn options() {
+        let options = parse_args(args("run -a -i north -f cmds.txt --max-steps 1000 -c -p fix.patch day25.txt")).unwrap();
+        assert_eq!(options.command, Command::Run);
+        assert_eq!(options.program, "day25.txt");
+        assert_eq!(options.inputs, [Input::Values("north".to_string()), Input::File("cmds.txt".to_string())]);
+        assert!(options.ascii);
+        assert!(!options.trace);
+        assert!(options.coverage);
+        assert_eq!(options.patches, ["fix.patch"]);
+        assert_eq!(options.max_steps, Some(1000));
+        assert_eq!(options.memory, DEFAULT_MEMORY);
+
+        assert!(parse_args(args("disasm day09.txt")).is_ok());
+        assert!(parse_args(args("disasm -t day09.txt")).is_err());
+        assert_eq!(parse_args(args("decompile day21.txt")).unwrap().command, Command::Decompile);
+        assert!(parse_args(args("decompile -p fix.patch day21.txt")).is_err());
+
+        let options = parse_args(args("arcade --fps 60 --auto -p free.patch day13.txt")).unwrap();
+        assert_eq!(options.command, Command::Arcade);
+        assert_eq!((options.fps, options.auto), (60, true));
+        assert!(parse_args(args("arcade -a day13.txt")).is_err());
+        assert!(parse_args(args("run --auto day13.txt")).is_err());
+        assert_eq!(parse_args(args("adventure -m 20000 day25.txt")).unwrap().memory, 20_000);
+        assert!(parse_args(args("adventure --fps 5 day25.txt")).is_err());
+        let options = parse_args(args("paint --white -o hull.pbm --scale 4 --frames steps day11.txt")).unwrap();
+        assert_eq!(options.command, Command::Paint);
+        assert_eq!((options.white, options.output.as_str(), options.scale), (true, "hull.pbm", 4));
+        assert_eq!(options.frames, Some("steps".to_string()));
+        assert!(parse_args(args("paint --scale 0 day11.txt")).is_err());
+        assert!(parse_args(args("paint -a day11.txt")).is_err());
+        assert!(parse_args(args("run --white day11.txt")).is_err());
+        assert!(parse_args(args("run -l lots day09.txt")).is_err());
+        assert!(parse_args(args("run")).is_err());
+    }
+
+    #[test]
+    fn input() {
+        assert_eq!(encode_input("1, -2\n3\n", false), Ok(vec![1, -2, 3]));
+        assert_eq!(encode_input("n\r\n", true), Ok(vec![110, 10]));
+        assert!(encode_input("north", false).is_err());
+    }
+}
@@ -6,29 +6,44 @@
  * Remarks:
  * The output turns out to be 250702 + 243000 * noun + verb,
  * but analyzing that would've taken much longer than just brute forcing it. :3
+ * Later on the symbolic executor recovered that formula automatically,
+ * so part 2 now solves it directly and only brute forces as a fallback.
  * 
  * The question hinted at adding more functionality to our IntCode machine,
  * so I ended up abstracting more things than strictly required.
  */
 
+use crate::intcode;
+use crate::intcode::symbolic;
+
 #[derive(Clone)]
-struct IntCode {
+pub(crate) struct IntCode {
     ip: usize,
     mem: Vec<i32>
 }
 
 #[aoc_generator(day2)]
 fn parse(input: &str) -> IntCode {
-    IntCode {
+    let i = IntCode {
         ip: 0,
         mem: input
             .split(',')
             .map(|n| { n.parse().unwrap() })
             .collect()
-    }
-} 
+    };
+    intcode::fingerprint::check_input(2, &i.mem.iter().map(|n| *n as i64).collect::<Vec<_>>());
+    i
+}
 
 impl IntCode {
+    pub(crate) fn new(mem: Vec<i32>) -> IntCode {
+        IntCode { ip: 0, mem }
+    }
+
+    pub(crate) fn memory(&self) -> &[i32] {
+        &self.mem
+    }
+
     fn get_args(&self) -> Vec<i32> {
         let opcode = self.mem[self.ip];
         let num_params;
@@ -43,39 +58,71 @@ impl IntCode {
         self.mem[self.ip + 1..self.ip + 1 + num_params].to_vec()
     }
 
-    pub fn execute(&mut self) {
-        loop {
-            let args = self.get_args();
-            let opcode = self.mem[self.ip];
-
-            match opcode {
-                1 => { self.mem[args[2] as usize] = self.mem[args[0] as usize] + self.mem[args[1] as usize]; },
-                2 => { self.mem[args[2] as usize] = self.mem[args[0] as usize] * self.mem[args[1] as usize]; },
-                99 => break,
-                _ => panic!("Invalid opcode {}", opcode),
-            }
+    /*
+     * Executes a single instruction. Returns false once the program has halted.
+     */
+    pub(crate) fn step(&mut self) -> bool {
+        let args = self.get_args();
+        let opcode = self.mem[self.ip];
 
-            self.ip += args.len() + 1;
+        match opcode {
+            1 => { self.mem[args[2] as usize] = self.mem[args[0] as usize] + self.mem[args[1] as usize]; },
+            2 => { self.mem[args[2] as usize] = self.mem[args[0] as usize] * self.mem[args[1] as usize]; },
+            99 => return false,
+            _ => panic!("Invalid opcode {}", opcode),
         }
+
+        self.ip += args.len() + 1;
+        true
+    }
+
+    pub fn execute(&mut self) {
+        while self.step() {}
     }
 }
 
 #[aoc(day2, part1)]
 fn solve_part1(input: &IntCode) -> i32 {
     let mut program = input.clone();
-    program.mem[0] = 12;
-    program.mem[1] = 2;
+    program.mem[1] = 12;
+    program.mem[2] = 2;
     program.execute();
     program.mem[0]
 }
 
+/*
+ * Finds the noun and verb from the closed form of mem[0], if it is affine in both.
+ */
+fn solve_symbolically(input: &IntCode, target: i32) -> Option<i32> {
+    let program: Vec<i64> = input.mem.iter().map(|n| *n as i64).collect();
+    let mut machine = symbolic::SymbolicMachine::new(&program);
+    machine.symbolize(1, "noun");
+    machine.symbolize(2, "verb");
+    machine.set_range("noun", 0, 99);
+    machine.set_range("verb", 0, 99);
+
+    let paths = machine.explore(10_000, 2);
+    let output = match paths.as_slice() {
+        [path] if path.end == symbolic::End::Halt => path.read(0),
+        _ => return None,
+    };
+    let output = output.as_affine()?;
+    (0..100).find_map(|noun| {
+        let verb = output.substitute("noun", noun)?.solve("verb", target as i64)?;
+        if (0..100).contains(&verb) { Some(100 * noun + verb) } else { None }
+    }).map(|answer| answer as i32)
+}
+
 #[aoc(day2, part2)]
 fn solve_part2(input: &IntCode) -> i32 {
+    if let Some(answer) = solve_symbolically(input, 19_690_720) {
+        return answer;
+    }
     for noun in 0..100 {
         for verb in 0..100 {
             let mut program = input.clone();
-            program.mem[0] = noun;
-            program.mem[1] = verb;
+            program.mem[1] = noun;
+            program.mem[2] = verb;
             program.execute();
             if program.mem[0] == 19_690_720 {
                 return 100 * noun + verb
@@ -117,5 +164,9 @@ mod tests {
 
     #[test]
     fn part2() {
+        // mem[0] = 250702 + 243000 * noun + verb, like the real input
+        let program = parse("1,0,0,3,2,1,17,3,1,3,2,0,1,0,18,0,99,243000,250702");
+        assert_eq!(solve_symbolically(&program, 19_690_720), Some(8018));
+        assert_eq!(solve_part2(&program), 8018);
     }
 }
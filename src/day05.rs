@@ -8,24 +8,35 @@
  *  Dealing with the immediate/position mode shenanigan was tricky though.
  */
 
+use crate::intcode;
+
 #[derive(Clone)]
-struct IntCode {
+pub(crate) struct IntCode {
     ip: usize,
     mem: Vec<i64>
 }
 
 #[aoc_generator(day5)]
 fn parse(input: &str) -> IntCode {
-    IntCode {
+    let i = IntCode {
         ip: 0,
         mem: input
             .split(',')
             .map(|n| { n.parse().unwrap() })
             .collect()
-    }
-} 
+    };
+    intcode::fingerprint::check_input(5, &i.mem);
+    i
+}
 
 impl IntCode {
+    pub(crate) fn new(mem: Vec<i64>) -> IntCode {
+        IntCode { ip: 0, mem }
+    }
+
+    pub(crate) fn memory(&self) -> &[i64] {
+        &self.mem
+    }
 
     /*
      * Returns a Vector containing indicies for the arguments of the current opcode.
@@ -60,27 +71,34 @@ impl IntCode {
         args
     }
 
+    /*
+     * Executes a single instruction, reading `input` if it asks for one.
+     * Returns false once the program has halted.
+     */
+    pub(crate) fn step(&mut self, input: i64, outputs: &mut Vec<i64>) -> bool {
+        let args = self.get_args();
+        let instruction = self.mem[self.ip];
+        let opcode = instruction % 100;
+
+        self.ip += args.len() + 1;
+        match opcode {
+            1 => self.mem[args[2]] = self.mem[args[0]] + self.mem[args[1]],
+            2 => self.mem[args[2]] = self.mem[args[0]] * self.mem[args[1]],
+            3 => self.mem[args[0]] = input,
+            4 => outputs.push(self.mem[args[0]]),
+            5 => if self.mem[args[0]] != 0 { self.ip = self.mem[args[1]] as usize },
+            6 => if self.mem[args[0]] == 0 { self.ip = self.mem[args[1]] as usize },
+            7 => self.mem[args[2]] = (self.mem[args[0]] < self.mem[args[1]]) as i64,
+            8 => self.mem[args[2]] = (self.mem[args[0]] == self.mem[args[1]]) as i64,
+            99 => return false,
+            _ => panic!("Invalid opcode {}", opcode),
+        }
+        true
+    }
+
     pub fn execute(&mut self, input: i64) -> Vec<i64> {
         let mut outputs: Vec<i64> = Vec::new();
-        loop {
-            let args = self.get_args();
-            let instruction = self.mem[self.ip];
-            let opcode = instruction % 100;
-
-            self.ip += args.len() + 1;
-            match opcode {
-                1 => self.mem[args[2]] = self.mem[args[0]] + self.mem[args[1]],
-                2 => self.mem[args[2]] = self.mem[args[0]] * self.mem[args[1]],
-                3 => self.mem[args[0]] = input,
-                4 => outputs.push(self.mem[args[0]]),
-                5 => if self.mem[args[0]] != 0 { self.ip = self.mem[args[1]] as usize },
-                6 => if self.mem[args[0]] == 0 { self.ip = self.mem[args[1]] as usize },
-                7 => self.mem[args[2]] = (self.mem[args[0]] < self.mem[args[1]]) as i64,
-                8 => self.mem[args[2]] = (self.mem[args[0]] == self.mem[args[1]]) as i64,
-                99 => break,
-                _ => panic!("Invalid opcode {}", opcode),
-            }
-        }
+        while self.step(input, &mut outputs) {}
         outputs
     }
 }
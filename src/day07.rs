@@ -16,21 +16,14 @@
  */
 
 use permutohedron;
-use std::collections::VecDeque;
 
 use crate::intcode;
 
 #[aoc_generator(day7)]
 fn parse(input: &str) -> intcode::IntCode {
-    intcode::IntCode {
-        ip: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-        relative_base: 0,
-    }
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(7, &program);
+    intcode::IntCode::new(program)
 }
 
 #[aoc(day7, part1)]
@@ -52,11 +45,11 @@ fn solve_part1(input: &intcode::IntCode) -> i64 {
         ];
 
         for (i, program) in programs.iter_mut().enumerate() {
-            program.input_queue.push_back(phase_set[i]);
+            program.push_input(phase_set[i]);
         }
 
         for program in &mut programs {
-            program.input_queue.push_back(signal);
+            program.push_input(signal);
             if let intcode::Status::Output(s) = program.execute() { signal = s; }
             else { panic!("Expected to receive output, but did not"); }
         }
@@ -87,7 +80,7 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
         ];
 
         for i in 0..5 {
-            programs[i].input_queue.push_back(phase_set[i]);
+            programs[i].push_input(phase_set[i]);
         }
 
         /*
@@ -96,7 +89,7 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
          */
         'main: loop {
             for (i, program) in programs.iter_mut().enumerate() {
-                program.input_queue.push_back(signal);
+                program.push_input(signal);
                 match program.execute() {
                     intcode::Status::Output(s) => { signal = s; }
                     intcode::Status::WaitingInput => panic!("Deadlock reached"),
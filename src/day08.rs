@@ -7,57 +7,54 @@
  *  Eh.
  */
 
+use crate::ocr;
+use crate::sif::SifImage;
+
 const WIDTH: usize = 25;
 const HEIGHT: usize = 6;
 
 #[aoc_generator(day8)]
-fn parse(input: &str) -> Vec<String>  {
-    input.as_bytes()
-        .chunks(WIDTH * HEIGHT)
-        .map(|s| { String::from_utf8(s.to_vec()).unwrap() })
-        .collect()
+fn parse(input: &str) -> SifImage {
+    SifImage::parse(input, WIDTH, HEIGHT).unwrap_or_else(|e| panic!("{}", e))
 }
 
 #[aoc(day8, part1)]
-fn solve_part1(layers: &[String]) -> i32 {
-    let mut min = (std::usize::MAX, 0, 0);
-    for layer in layers {
-        let mut count = (0, 0, 0);
-        for c in layer.chars() {
-            match c {
-                '0' => count.0 += 1,
-                '1' => count.1 += 1,
-                '2' => count.2 += 1,
-                _ => panic!("layer contains invalid character"),
-            };
-        }
-        if count.0 < min.0 { min = count; }
-    }
-    (min.1 * min.2) as i32
+fn solve_part1(image: &SifImage) -> usize {
+    let stats = image.stats();
+    let fewest_zeros = stats.iter().min_by_key(|layer| layer.count(0)).unwrap();
+    fewest_zeros.count(1) * fewest_zeros.count(2)
 }
 
 #[aoc(day8, part2)]
-fn solve_part2(layers: &[String]) -> &'static str {
-    let mut image: [i32; WIDTH * HEIGHT] = [2; WIDTH * HEIGHT];
+fn solve_part2(image: &SifImage) -> String {
+    let grid: Vec<Vec<bool>> = image.composite().chunks(image.width())
+        .map(|row| row.iter().map(|&pixel| match image.palette().color(pixel) {
+            // Letters are light on a dark background
+            Some(color) => color.luma() >= 128,
+            None => panic!("image has a transparent pixel"),
+        }).collect())
+        .collect();
+    ocr::read(&grid).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    for layer in layers {
-        for (i, c) in layer.chars().enumerate() {
-            match c {
-                '0' => if image[i] == 2 { image[i] = 0; },
-                '1' => if image[i] == 2 { image[i] = 1; },
-                '2' => (),
-                _ => panic!("layer contains invalid character"),
-            };
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for (i, n) in image.iter().enumerate() {
-        match n {
-            0 => print!(" "),
-            1 => print!("*"),
-            _ => panic!("image contains invalid character"),
-        }
-        if (i + 1) % WIDTH == 0 {println!()}
+    #[test]
+    fn message() {
+        let letters = [
+            "#..#.####.#....#.....##..",
+            "#..#.#....#....#....#..#.",
+            "####.###..#....#....#..#.",
+            "#..#.#....#....#....#..#.",
+            "#..#.#....#....#....#..#.",
+            "#..#.####.####.####..##..",
+        ];
+        // Everything transparent on top, then the message, then a white layer it hides
+        let message: String = letters.concat().chars().map(|c| if c == '#' { '1' } else { '0' }).collect();
+        let input = format!("{}{}{}", "2".repeat(WIDTH * HEIGHT), message, "1".repeat(WIDTH * HEIGHT));
+        assert_eq!(solve_part1(&parse(&input)), 0);
+        assert_eq!(solve_part2(&parse(&input)), "HELLO");
     }
-    "Check output"
 }
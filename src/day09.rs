@@ -8,22 +8,14 @@
  *  At least it made me refactor some stuff.
  */
 
-use std::collections::VecDeque;
-
 use crate::intcode;
 
 #[aoc_generator(day9)]
 fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(9, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
     i
 }
 
@@ -36,7 +28,7 @@ fn solve_part1(input: &intcode::IntCode) -> String {
     let mut program = input.clone();
 
     let mut outputs: Vec<i64> = Vec::new();
-    program.input_queue.push_back(1);
+    program.push_input(1);
     loop {
         match program.execute() {
             intcode::Status::Output(n) => outputs.push(n),
@@ -51,7 +43,7 @@ fn solve_part1(input: &intcode::IntCode) -> String {
 fn solve_part2(input: &intcode::IntCode) -> i64 {
     let mut program = input.clone();
 
-    program.input_queue.push_back(2);
+    program.push_input(2);
     match program.execute() {
         intcode::Status::Output(n) => n,
         intcode::Status::Halt => panic!("Premature halt"),
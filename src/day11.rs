@@ -12,136 +12,157 @@
  *  The problem itself was pretty straightforward!
  *  I think my solution is good-ish except for the ugly `receiving_color` flag
  *  which could be gone if there was an output queue.
+ *
+ *  It's gone now that the robots from days 11, 15 and 17 share a framework
+ *  which hands the robot all of a response at once.
  */
 
 extern crate num;
 
 use num::complex::Complex;
-use std::collections::VecDeque;
-use std::collections::HashMap;
+use crate::image;
+use crate::image::Image;
 use crate::intcode;
+use crate::ocr;
+use crate::robot::{Direction, Driver, Position, Robot, World};
+use std::io;
 
 #[aoc_generator(day11)]
 fn parse(input: &str) -> intcode::IntCode  {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(11, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
     i
 }
 
-#[derive (PartialEq, Eq)]
-enum Color { 
+#[derive (Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color { 
     Black,
     White,
 }
 
-#[aoc(day11, part1)]
-fn solve_part1(input: &intcode::IntCode) -> i64 {
-    let mut grid: HashMap<Complex<i64>, Color> = HashMap::new();
-
-    let mut robot = input.clone();
-    let mut robot_position = Complex::new(0, 0);
-    let mut robot_direction = Complex::new(0, 1);
-
-    grid.insert(robot_position.clone(), Color::Black);
-    let mut receiving_color = true;
-    let turn = Complex::new(0, 1);
-    loop {
-        match robot.execute() {
-            intcode::Status::Output(n) => {
-                if receiving_color {
-                    let color = if n == 0 { Color::Black } else { Color::White };
-                    grid.insert(robot_position.clone(), color);
-                    receiving_color = false;
-                } else {
-                    if n == 0 { robot_direction *= turn; } // left turn
-                    else { robot_direction /= turn; } // right turn
-                    robot_position += robot_direction;
-                    receiving_color = true;
-                }
-            }
-            intcode::Status::WaitingInput => {
-                let current_color = match grid.get(&robot_position) {
-                    None => &Color::Black,
-                    Some(c) => c,
-                };
-                let input = if current_color == &Color::Black { 0 } else { 1 };
-                robot.input_queue.push_back(input);
-            }
-            intcode::Status::Halt => break,
+/*
+ * Reads the color under its camera, then is told what to paint and which way to turn.
+ */
+#[derive (Clone)]
+struct Painter {
+    position: Position,
+    direction: Direction,
+    // Every panel painted, in order
+    history: Vec<(Position, Color)>,
+}
+
+impl Robot for Painter {
+    type Cell = Color;
+    type Command = Color;
+
+    fn encode(&self, color: &Color) -> Vec<i64> {
+        vec![if *color == Color::Black { 0 } else { 1 }]
+    }
+
+    fn update(&mut self, _: Option<&Color>, outputs: &[i64], world: &mut World<Color>) {
+        if let [color, turn, ..] = *outputs {
+            let color = if color == 0 { Color::Black } else { Color::White };
+            world.set(self.position, color);
+            self.history.push((self.position, color));
+            self.direction = if turn == 0 { self.direction.turn_left() } else { self.direction.turn_right() };
+            self.position += self.direction.offset();
         }
     }
 
-    grid.len() as i64
+    fn next(&mut self, world: &World<Color>) -> Option<Color> {
+        Some(world.get(self.position).cloned().unwrap_or(Color::Black))
+    }
 }
 
-#[aoc(day11, part2)]
-fn solve_part2(input: &intcode::IntCode) -> &'static str {
-    let mut grid: HashMap<Complex<i64>, Color> = HashMap::new();
-
-    let mut robot = input.clone();
-    let mut robot_position = Complex::new(0, 0);
-    let mut robot_direction = Complex::new(0, 1);
-
-    grid.insert(robot_position.clone(), Color::White);
-    let mut receiving_color = true;
-    let turn = Complex::new(0, 1);
-    loop {
-        match robot.execute() {
-            intcode::Status::Output(n) => {
-                if receiving_color {
-                    let color = if n == 0 { Color::Black } else { Color::White };
-                    grid.insert(robot_position.clone(), color);
-                    receiving_color = false;
-                } else {
-                    if n == 0 { robot_direction *= turn; } // left turn
-                    else { robot_direction /= turn; } // right turn
-                    robot_position += robot_direction;
-                    receiving_color = true;
-                }
-            }
-            intcode::Status::WaitingInput => {
-                let current_color = match grid.get(&robot_position) {
-                    None => &Color::Black,
-                    Some(c) => c,
-                };
-                let input = if current_color == &Color::Black { 0 } else { 1 };
-                robot.input_queue.push_back(input);
-            }
-            intcode::Status::Halt => break,
-        }
-    }
+fn paint(input: &intcode::IntCode, start: Color) -> Driver<Painter> {
+    let painter = Painter { position: Complex::new(0, 0), direction: Direction::North, history: Vec::new() };
+    let mut robot = Driver::new(input.clone(), painter);
+    robot.world_mut().set(Complex::new(0, 0), start);
+    robot.run();
+    robot
+}
+
+fn shade(color: Option<&Color>) -> u8 {
+    if color == Some(&Color::White) { image::WHITE } else { image::BLACK }
+}
 
-    let min_x = grid.keys().min_by_key(|a| a.re).unwrap().re;
-    let max_x = grid.keys().max_by_key(|a| a.re).unwrap().re;
-    let min_y = grid.keys().min_by_key(|a| a.im).unwrap().im;
-    let max_y = grid.keys().max_by_key(|a| a.im).unwrap().im;
+/*
+ * Draws the hull one pixel per panel, on a canvas just big enough for what was painted.
+ */
+pub fn picture(input: &intcode::IntCode, start: Color) -> Image {
+    let hull = paint(input, start).world().clone();
+    let (min, max) = hull.bounds().unwrap();
+    Image::from_fn((max.re - min.re + 1) as usize, (max.im - min.im + 1) as usize, |x, y| {
+        shade(hull.get(min + Complex::new(x as i64, y as i64)))
+    })
+}
 
-    let width = max_x - min_x + 1;
-    let height = max_y - min_y + 1;
-    let mut points = vec![vec![false; 100]; 100]; // Magic number that's probably good enough
+/*
+ * Replays the painting, passing a picture of the hull to `frame` before the first step and
+ * after every step, with the robot in grey. Every frame is the same size, big enough
+ * for everywhere the robot went.
+ */
+pub fn animate<F: FnMut(&Image) -> io::Result<()>>(input: &intcode::IntCode, start: Color, mut frame: F) -> io::Result<()> {
+    let robot = paint(input, start);
+    let history = &robot.robot().history;
+    let mut canvas = robot.world().clone();
+    canvas.set(robot.robot().position, Color::Black);
+    let (min, max) = canvas.bounds().unwrap();
 
-    for (point, color) in &grid {
-        if color == &Color::White {
-            let x = point.re - min_x;
-            let y = max_y - point.im;
-            points[y as usize][x as usize] = true;
+    let mut hull = World::new();
+    hull.set(Complex::new(0, 0), start);
+    for step in 0..=history.len() {
+        if step > 0 {
+            let (position, color) = history[step - 1];
+            hull.set(position, color);
         }
+        let robot = history.get(step).map_or(robot.robot().position, |(position, _)| *position);
+        let image = Image::from_fn((max.re - min.re + 1) as usize, (max.im - min.im + 1) as usize, |x, y| {
+            let position = min + Complex::new(x as i64, y as i64);
+            if position == robot { 128 } else { shade(hull.get(position)) }
+        });
+        frame(&image)?;
     }
-    for line in points.iter().take(height as usize) {
-        for is_white in line.iter().take(width as usize) {
-            if *is_white { print!("⬜") }
-            else { print!("⬛") }
+    Ok(())
+}
+
+#[aoc(day11, part1)]
+fn solve_part1(input: &intcode::IntCode) -> i64 {
+    paint(input, Color::Black).world().len() as i64
+}
+
+/*
+ * Reads the registration identifier off the hull.
+ */
+fn read(hull: &World<Color>) -> String {
+    ocr::read(&hull.grid(|color| color == Some(&Color::White))).unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[aoc(day11, part2)]
+fn solve_part2(input: &intcode::IntCode) -> String {
+    read(paint(input, Color::White).world())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration() {
+        let mut hull = World::new();
+        let letters = ["#..#.###.", "#..#.#..#", "####.###.", "#..#.#..#", "#..#.#..#", "#..#.###."];
+        for (y, row) in letters.iter().enumerate() {
+            for (x, panel) in row.chars().enumerate() {
+                let color = if panel == '#' { Color::White } else { Color::Black };
+                hull.set(Complex::new(x as i64 + 1, y as i64), color);
+            }
         }
-        println!();
+        // Black panels around the letters don't get in the way
+        hull.set(Complex::new(0, 0), Color::Black);
+        hull.set(Complex::new(12, 7), Color::Black);
+        assert_eq!(read(&hull), "HB");
     }
+}
 
-    "Output above"
-} 
@@ -10,15 +10,14 @@
 extern crate num;
 
 use crate::intcode;
+use crate::robot::{Direction, Driver, Position, Robot, World};
 use std::collections::VecDeque;
-use std::collections::HashMap;
 use std::collections::HashSet;
 use num::complex::Complex;
 
 #[derive (Clone)]
 struct Drone {
-    code: intcode::IntCode,
-    pos: Complex<i64>,
+    pos: Position,
 }
 
 #[derive (PartialEq, Eq, Debug, Clone)]
@@ -28,61 +27,60 @@ enum Tile {
     Oxygen,
 }
 
-type Maze = HashMap<Complex<i64>, Tile>;
+type Maze = World<Tile>;
+
+impl Robot for Drone {
+    type Cell = Tile;
+    type Command = Direction;
+
+    fn encode(&self, direction: &Direction) -> Vec<i64> {
+        vec![match direction {
+            Direction::North => 1,
+            Direction::South => 2,
+            Direction::West => 3,
+            Direction::East => 4,
+        }]
+    }
+
+    fn update(&mut self, direction: Option<&Direction>, outputs: &[i64], map: &mut Maze) {
+        let new_pos = self.pos + direction.expect("Drone said something unprompted").offset();
+        let new_tile = match outputs {
+            [0] => Tile::Wall,
+            [1] => Tile::Space,
+            [2] => Tile::Oxygen,
+            _ => panic!("Unknown response from drone"),
+        };
+        // If not a wall, update drone position
+        if new_tile != Tile::Wall { self.pos = new_pos; }
+        map.set(new_pos, new_tile);
+    }
+}
 
 #[aoc_generator(day15)]
 fn parse(input: &str) -> Maze {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
-    let mut drone = Drone {
-        code: i.clone(),
-        pos: Complex::new(0, 0),
-    };
-    let mut map: Maze = HashMap::new();
-    map.insert(drone.pos, Tile::Space);
-    scan(&mut drone, &map)
-
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(15, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
+    let mut drone = Driver::new(i, Drone { pos: Complex::new(0, 0) });
+    drone.world_mut().set(Complex::new(0, 0), Tile::Space);
+    scan(&drone)
 }
 
-fn scan(drone: &mut Drone, map: &Maze) -> Maze {
-    let mut map = map.clone();
-    for input in 1..5 {
-        let mut drone = drone.clone();
-        let direction = match input {
-            1 => Complex::new(0, 1),
-            2 => Complex::new(0, -1),
-            3 => Complex::new(-1, 0),
-            4 => Complex::new(1, 0),
-            _ => panic!(),
-        };
-        let new_pos = drone.pos + direction;
+fn scan(drone: &Driver<Drone>) -> Maze {
+    let mut map = drone.world().clone();
+    for &direction in Direction::ALL.iter() {
+        let new_pos = drone.robot().pos + direction.offset();
 
         // Check if tile is explored
-        if let None = map.get(&new_pos) {
-            // Move drone and check result
-            drone.code.input_queue.push_back(input);
-            if let intcode::Status::Output(n) = drone.code.execute() {
-                let new_tile = match n {
-                    0 => Tile::Wall,
-                    1 => Tile::Space,
-                    2 => Tile::Oxygen,
-                    _ => panic!("Unknown response from drone"),
-                };
-                // If not a wall, update drone position
-                if new_tile != Tile::Wall { drone.pos = new_pos; }
-                map.insert(new_pos, new_tile);
-            } else { panic!("Expecting response from drone") }
+        if !map.contains(new_pos) {
+            // Move a copy of the drone, which knows the whole map so far
+            let mut drone = drone.clone();
+            *drone.world_mut() = map;
+            drone.send(direction);
 
             // From our new position, recursively scan the entire maze.
-            map.extend(scan(&mut drone, &map));
+            map = scan(&drone);
         }
     }
     map
@@ -91,21 +89,20 @@ fn scan(drone: &mut Drone, map: &Maze) -> Maze {
 #[aoc(day15, part1)]
 fn solve_part1(map: &Maze) -> i64 {
     // For keeping track of tiles that were visited
-    let mut visited: HashSet<Complex<i64>> = HashSet::new();
+    let mut visited: HashSet<Position> = HashSet::new();
     // Queue of (pos, distance) for each tile to visit
-    let mut to_visit: VecDeque<(Complex<i64>, i64)> = VecDeque::new();
+    let mut to_visit: VecDeque<(Position, i64)> = VecDeque::new();
     to_visit.push_back((Complex::new(0, 0), 0));
 
-    let directions = [Complex::new(0, 1), Complex::new(0, -1), Complex::new(-1, 0), Complex::new(1, 0)];
     while !to_visit.is_empty() {
         let current = to_visit.pop_front().unwrap();
         visited.insert(current.0);
-        if map.get(&current.0).unwrap() == &Tile::Oxygen { return current.1; }
+        if map.get(current.0).unwrap() == &Tile::Oxygen { return current.1; }
 
-        for direction in directions.iter() {
-            let new = current.0 + direction;
+        for direction in Direction::ALL.iter() {
+            let new = current.0 + direction.offset();
             if visited.contains(&new) { continue; }
-            if let Some(tile) = map.get(&new) {
+            if let Some(tile) = map.get(new) {
                 if tile != &Tile::Wall {
                     to_visit.push_back((new, current.1 + 1));
                 }
@@ -118,28 +115,27 @@ fn solve_part1(map: &Maze) -> i64 {
 #[aoc(day15, part2)]
 fn solve_part2(map: &Maze) -> i64 {
     // For keeping track of tiles that were visited
-    let mut visited: HashSet<Complex<i64>> = HashSet::new();
+    let mut visited: HashSet<Position> = HashSet::new();
     // Queue of (pos, distance) for each tile to visit
-    let mut to_visit: VecDeque<(Complex<i64>, i64)> = VecDeque::new();
+    let mut to_visit: VecDeque<(Position, i64)> = VecDeque::new();
 
-    for (pos, tile) in map {
+    for (pos, tile) in map.iter() {
         if tile == &Tile::Oxygen {
-            to_visit.push_back((pos.clone(), 0));
+            to_visit.push_back((pos, 0));
             break;
         }
     }
 
-    let directions = [Complex::new(0, 1), Complex::new(0, -1), Complex::new(-1, 0), Complex::new(1, 0)];
     let mut t = 0;
     while !to_visit.is_empty() {
         let current = to_visit.pop_front().unwrap();
         visited.insert(current.0);
         if current.1 > t { t = current.1; }
 
-        for direction in directions.iter() {
-            let new = current.0 + direction;
+        for direction in Direction::ALL.iter() {
+            let new = current.0 + direction.offset();
             if visited.contains(&new) { continue; }
-            if let Some(tile) = map.get(&new) {
+            if let Some(tile) = map.get(new) {
                 if tile != &Tile::Wall {
                     to_visit.push_back((new, current.1 + 1));
                 }
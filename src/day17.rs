@@ -13,80 +13,85 @@
 extern crate num;
 
 use crate::intcode;
-use std::collections::VecDeque;
-use std::collections::HashSet;
+use crate::robot::{Direction, Driver, Position, Robot, World};
 use num::complex::Complex;
 
 #[aoc_generator(day17)]
 fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(17, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
     i
 }
 
-fn is_intersection(visited: &HashSet<Complex<i32>>, current: &Complex<i32>) -> bool {
-    visited.contains(&(current + Complex::new(1, 0))) &&
-    visited.contains(&(current + Complex::new(-1, 0))) &&
-    visited.contains(&(current + Complex::new(0, 1))) &&
-    visited.contains(&(current + Complex::new(0, -1)))
+/*
+ * Shows what its camera sees as ASCII, and once woken up takes its movement routine as ASCII too,
+ * answering with the amount of dust it collected.
+ */
+#[derive (Clone, Default)]
+struct Vacuum {
+    position: Position,
+    direction: Option<Direction>,
+    dust: Option<i64>,
 }
 
-fn scan(robot: &mut intcode::IntCode) -> (HashSet<Complex<i32>>, Complex<i32>, Complex<i32>) {
-    let mut map: HashSet<Complex<i32>> = HashSet::new();
-    let mut current_position = Complex::new(0, 0);
+impl Robot for Vacuum {
+    // The scaffold, drawn as the camera shows it
+    type Cell = char;
+    type Command = String;
 
-    let mut robot_position = Complex::new(0, 0);
-    let mut robot_direction = Complex::new(0, 0);
+    fn encode(&self, routine: &String) -> Vec<i64> {
+        routine.bytes().map(|b| b as i64).collect()
+    }
 
-    loop {
-        match robot.execute() {
-            intcode::Status::Output(chr) => {
-                match chr {
-                    35 => {
-                        map.insert(current_position);
-                        current_position.re += 1;
-                    },
-                    94 | 118 | 60 | 62 => {
-                        map.insert(current_position);
-                        robot_position = current_position;
-                        match chr {
-                            94 => robot_direction.im = -1,
-                            118 => robot_direction.im = 1,
-                            60 => robot_direction.re = -1,
-                            62 => robot_direction.re = 1,
-                            _ => panic!(),
-                        };
-                        current_position.re += 1;
-                    },
-                    46 => current_position.re += 1,
-                    10 => {
-                        current_position.re = 0;
-                        current_position.im += 1;
-                    },
-                    _ => (),
-                }
-            },
-            intcode::Status::WaitingInput => break,
-            intcode::Status::Halt => break,
+    fn update(&mut self, routine: Option<&String>, outputs: &[i64], map: &mut World<char>) {
+        if routine.is_some() {
+            // Only the dust isn't ASCII
+            self.dust = outputs.iter().cloned().find(|n| *n > 255);
+            return;
+        }
+        let mut current_position = Complex::new(0, 0);
+        for &chr in outputs {
+            let chr = chr as u8 as char;
+            let direction = match chr {
+                '^' => Some(Direction::North),
+                'v' => Some(Direction::South),
+                '<' => Some(Direction::West),
+                '>' => Some(Direction::East),
+                _ => None,
+            };
+            if direction.is_some() {
+                self.position = current_position;
+                self.direction = direction;
+            }
+            match chr {
+                '#' | '^' | 'v' | '<' | '>' => {
+                    map.set(current_position, chr);
+                    current_position.re += 1;
+                },
+                '.' => current_position.re += 1,
+                '\n' => {
+                    current_position.re = 0;
+                    current_position.im += 1;
+                },
+                _ => (),
+            }
         }
     }
-    (map, robot_position, robot_direction)
+}
+
+fn is_intersection(map: &World<char>, current: Position) -> bool {
+    Direction::ALL.iter().all(|direction| map.contains(current + direction.offset()))
 }
 
 #[aoc(day17, part1)]
-fn solve_part1(input: &intcode::IntCode) -> i32 {
-    let (visited, _, _) = scan(&mut input.clone());
-    visited.iter()
-        .filter(|coord| is_intersection(&visited, coord))
-        .fold(0, |sum, a| sum + a.re * a.im)
+fn solve_part1(input: &intcode::IntCode) -> i64 {
+    let robot = Driver::new(input.clone(), Vacuum::default());
+    let map = robot.world();
+    map.iter()
+        .filter(|(coord, _)| is_intersection(map, *coord))
+        .fold(0, |sum, (a, _)| sum + a.re * a.im)
 }
 
 // This is crap
@@ -141,28 +146,31 @@ fn compress(movements: &str, a_length: usize, b_length: usize, c_length: usize)
 
 #[aoc(day17, part2)]
 fn solve_part2(input: &intcode::IntCode) -> i64 {
-    let mut robot = input.clone();
-    robot.mem[0] = 2;
-    let (map, mut robot_position, mut robot_direction) = scan(&mut robot);
+    let mut machine = input.clone();
+    machine.patch(0, 2).unwrap();
+    let mut robot = Driver::new(machine, Vacuum::default());
+    let map = robot.world();
+    let mut robot_position = robot.robot().position;
+    let mut robot_direction = robot.robot().direction.expect("Robot not in sight").offset();
 
     let mut movements = String::new();
     let right_turn = Complex::new(0, 1);
     let left_turn = Complex::new(0, -1);
 
     loop {
-        if map.contains(&(robot_position + robot_direction)) {
+        if map.contains(robot_position + robot_direction) {
             let mut distance = 1;
-            while map.contains(&(robot_position + (distance + 1) * robot_direction)) {
+            while map.contains(robot_position + (distance + 1) * robot_direction) {
                 distance += 1;
             }
             robot_position += distance * robot_direction;
             movements += &distance.to_string();
             movements += ",";
         } else {
-            if map.contains(&(robot_position + robot_direction * left_turn)) {
+            if map.contains(robot_position + robot_direction * left_turn) {
                 robot_direction *= left_turn;
                 movements += "L,";
-            } else if map.contains(&(robot_position + robot_direction * right_turn)) {
+            } else if map.contains(robot_position + robot_direction * right_turn) {
                 robot_direction *= right_turn;
                 movements += "R,";
             } else { // Dead end
@@ -172,25 +180,18 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
     }
     // println!("{:?}", movements);
 
+    let mut routine = None;
     for a in 1..21 {
         for b in 1..21 {
             for c in 1..21 {
-                if let Some(s) = compress(&movements, a, b, c) {
-                    // println!("{:?}", s);
-                    robot.input_queue.extend(s.bytes().map(|x| x as i64));
+                if routine.is_none() {
+                    routine = compress(&movements, a, b, c);
                 }
             }
         }
     }
-    robot.input_queue.extend("n\n".bytes().map(|x| x as i64));
-    loop {
-        match robot.execute() {
-            intcode::Status::Output(dust) => {
-                // print!("{}", dust as u8 as char);
-                if dust > 256 { return dust; } // not ASCII
-            }
-            intcode::Status::WaitingInput => panic!("Unexpected to wait for input"),
-            intcode::Status::Halt => panic!("Halting due to error"),
-        }
-    }
+    let routine = routine.expect("Could not compress movements");
+    // println!("{:?}", routine);
+    robot.send(routine + "n\n");
+    robot.robot().dust.expect("Halting due to error")
 }
@@ -15,45 +15,39 @@
 
 extern crate num;
 
+use std::thread;
+
 use crate::intcode;
-use std::collections::VecDeque;
+use crate::intcode::pool::Pool;
 
 #[aoc_generator(day19)]
-fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
-    i
+fn parse(input: &str) -> Pool {
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(19, &program);
+    let mut pool = Pool::new(&program, 5_000);
+    // The drone doesn't remember anything between queries
+    pool.set_pure(true);
+    pool
 }
 
 #[aoc(day19, part1)]
-fn solve_part1(input: &intcode::IntCode) -> i64 {
-    let mut count = 0;
-    for y in 0..50 {
-        for x in 0..50 {
-            let mut drone = input.clone();
-            drone.input_queue.push_back(x);
-            drone.input_queue.push_back(y);
-            match drone.execute() {
-                intcode::Status::Output(a) => {
-                    if a == 1 { count += 1; }
-                },
-                _ => (),
-            }
-        }
-    }
-    count
+fn solve_part1(input: &Pool) -> i64 {
+    // Every query is independent, so ask about the whole area at once
+    let queries: Vec<Vec<i64>> = (0..50).flat_map(|y| (0..50).map(move |x| vec![x, y])).collect();
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    input.query_batch(&queries, threads)
+        .into_iter()
+        .filter(|result| result.as_deref() == Ok(&[1][..]))
+        .count() as i64
 }
 
 #[aoc(day19, part2)]
-fn solve_part2(input: &intcode::IntCode) -> i64 {
+fn solve_part2(input: &Pool) -> i64 {
+    let mut drone = input.clone();
+    let mut attracted = |x: i64, y: i64| {
+        drone.query(&[x, y]).expect("The drone should answer every query") == [1]
+    };
+
     // ....####...
     // ......####.
     // ^^^^
@@ -61,32 +55,17 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
     let mut x = 0;
 
     // the first few rows don't have any attracted tiles in my input for some reason?
-    for y in 10..std::i64::MAX {
-        let edge;
-
+    for y in 10..i64::MAX {
         // Get the lower left corner of the box
-        loop {
-            let mut drone = input.clone();
-            drone.input_queue.extend(vec![x, y]);
-            match drone.execute() {
-                intcode::Status::Output(n) => {
-                    if n == 1 { edge = x; break; }
-                },
-                _ => (),
-            }
+        while !attracted(x, y) {
             x += 1;
         }
 
         // Check upper-right corner; if both corners are in the beam
         // the entire beam is guaranteed to be in it
-        let mut drone = input.clone();
-        drone.input_queue.extend(vec![edge + 99, y - 99]);
-        match drone.execute() {
-            intcode::Status::Output(n) => {
-                // Make sure to return based on upper-left corner
-                if n == 1 { return edge * 10000 + (y - 99); }
-            },
-            _ => (),
+        if attracted(x + 99, y - 99) {
+            // Make sure to return based on upper-left corner
+            return x * 10000 + (y - 99);
         }
     }
     panic!("Could not find suitable position (This is a bug)")
@@ -12,25 +12,18 @@
 extern crate num;
 
 use crate::intcode;
-use std::collections::VecDeque;
 
 #[aoc_generator(day21)]
 fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(21, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
     i
 }
 
 fn input_string(droid: &mut intcode::IntCode, s: &str) {
-    droid.input_queue.extend(s.bytes().map(|x| x as i64));
+    droid.push_ascii(s);
 }
 
 fn not(droid: &mut intcode::IntCode, x: char, y: char) {
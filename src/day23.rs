@@ -12,20 +12,13 @@ extern crate num;
 
 use crate::intcode;
 use std::collections::HashSet;
-use std::collections::VecDeque;
 
 #[aoc_generator(day23)]
 fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(5_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(23, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(5_000);
     i
 }
 
@@ -35,14 +28,14 @@ fn solve_part1(input: &intcode::IntCode) -> i64 {
     computers.reserve(50);
     for i in 0..50 {
         computers.push(input.clone());
-        computers[i].input_queue.push_back(i as i64);
+        computers[i].push_input(i as i64);
     }
     loop {
         let mut packets: Vec<(usize, i64, i64)> = Vec::new();
         for computer in computers.iter_mut() {
             match computer.execute() {
                 intcode::Status::Halt => panic!("A computer halted unexpectedly!"),
-                intcode::Status::WaitingInput => computer.input_queue.push_back(-1),
+                intcode::Status::WaitingInput => computer.push_input(-1),
                 intcode::Status::Output(addr) => {
                     let x;
                     let y;
@@ -58,8 +51,8 @@ fn solve_part1(input: &intcode::IntCode) -> i64 {
         }
         for (addr, x, y) in packets {
             if addr == 255 { return y; }
-            computers[addr].input_queue.push_back(x);
-            computers[addr].input_queue.push_back(y);
+            computers[addr].push_input(x);
+            computers[addr].push_input(y);
         }
     }
 }
@@ -70,7 +63,7 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
     computers.reserve(50);
     for i in 0..50 {
         computers.push(input.clone());
-        computers[i].input_queue.push_back(i as i64);
+        computers[i].push_input(i as i64);
     }
     let mut sent_y: HashSet<i64> = HashSet::new();
     let mut nat_x = 0;
@@ -80,7 +73,7 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
         for computer in computers.iter_mut() {
             match computer.execute() {
                 intcode::Status::Halt => panic!("A computer halted unexpectedly!"),
-                intcode::Status::WaitingInput => computer.input_queue.push_back(-1),
+                intcode::Status::WaitingInput => computer.push_input(-1),
                 intcode::Status::Output(addr) => {
                     let x;
                     let y;
@@ -95,8 +88,8 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
             }
         }
         if packets.is_empty() {
-            computers[0].input_queue.push_back(nat_x);
-            computers[0].input_queue.push_back(nat_y);
+            computers[0].push_input(nat_x);
+            computers[0].push_input(nat_y);
             if !sent_y.insert(nat_y) {
                 return nat_y;
             }
@@ -106,8 +99,8 @@ fn solve_part2(input: &intcode::IntCode) -> i64 {
                     nat_x = x;
                     nat_y = y;
                 } else {
-                    computers[addr].input_queue.push_back(x);
-                    computers[addr].input_queue.push_back(y);
+                    computers[addr].push_input(x);
+                    computers[addr].push_input(y);
                 }
             }
         }
@@ -9,114 +9,303 @@
  *  try all different combinations of picked up items.
  *  I'm not a cool person.
  *
- *  A new command `solve` was hacked in, which if
- *      1) all the items are collected,
- *      2) droid is located at Security Checkpoint, just before Pressure-Sensitive Floor, 
- *      3) the `bruteforce` function is modified appropriately,
- *  then will enter commands into the droid that try each combination of the items
- *  and attempt to pass the pressure sensor.
+ *  Turns out I am now. The droid explores the whole ship, trying every item
+ *  on a copy of itself first since some of them kill it or hang the game,
+ *  then goes to the Security Checkpoint and tries combinations of the items
+ *  until the pressure sensor lets it through, skipping the ones that
+ *  the sensor's "heavier" and "lighter" already rule out.
+ *  `play` is still around for playing by hand.
  */
 
 extern crate num;
 
+use crate::adventure;
+use crate::adventure::{Adventure, Command, Direction, Message, Reply, Room, Weight};
+use crate::adventure::shell::Shell;
 use crate::intcode;
-use std::collections::VecDeque;
 use std::collections::HashSet;
 use std::io;
 
 #[aoc_generator(day25)]
 fn parse(input: &str) -> intcode::IntCode {
-    let mut i = intcode::IntCode {
-        ip: 0,
-        relative_base: 0,
-        mem: input
-            .split(',')
-            .map(|n| { n.parse().unwrap() })
-            .collect(),
-        input_queue: VecDeque::new(),
-    };
-    i.mem.resize(15_000, 0);
+    let program = intcode::parse_program(input).unwrap();
+    intcode::fingerprint::check_input(25, &program);
+    let mut i = intcode::IntCode::new(program);
+    i.resize(15_000);
     i
 }
 
-// Copied from https://gist.github.com/synecdoche/9ade913c891dda6fcf1cdac823e7d524
-fn powerset<T: Clone>(slice: &[T]) -> Vec<Vec<T>> {
-    let mut v: Vec<Vec<T>> = Vec::new();
-
-    for mask in 0..(1 << slice.len()) {
-        let mut ss: Vec<T> = vec![];
-        let mut bitset = mask;
-        while bitset > 0 {
-            // isolate the rightmost bit to select one item
-            let rightmost: u64 = bitset & !(bitset - 1);
-            // turn the isolated bit into an array index
-            let idx = rightmost.trailing_zeros();
-            let item = (*slice.get(idx as usize).unwrap()).clone();
-            ss.push(item);
-            // zero the trailing bit
-            bitset &= bitset - 1;
+struct Solver<A: Adventure> {
+    droid: A,
+    inventory: Vec<String>,
+    visited: HashSet<String>,
+    // Directions from the start to the Security Checkpoint, and the door to the pressure-sensitive floor
+    checkpoint: Option<(Vec<Direction>, Direction)>,
+    // Times the droid stepped on the pressure-sensitive floor
+    attempts: usize,
+}
+
+impl<A: Adventure> Solver<A> {
+    fn new(droid: A) -> Solver<A> {
+        Solver { droid, inventory: Vec::new(), visited: HashSet::new(), checkpoint: None, attempts: 0 }
+    }
+
+    fn command(&mut self, command: Command) -> Vec<Message> {
+        match self.droid.command(&command) {
+            Reply::Prompt(text) => adventure::parse(&text),
+            reply => panic!("Droid didn't survive `{}`: {:?}", command, reply),
         }
-        v.push(ss);
     }
-    v
-}
 
-/*
- * Returns a sequence of commands that try out every single combination of the items.
- * Modify items and directions based on input.
- */
-fn bruteforce() -> String {
-    // hardcoded list of all items
-    let items = ["prime number", "candy cane", "asterisk", "food ration", "boulder", "mutex", "mug"];
-    // hardcoded direction to pressure sensor from security checkpoint
-    let direction = "north\n";
-
-	let mut commands = String::new();
-    let mut inv = HashSet::new();
-    for set in powerset(&items) {
-        // Drop any items that aren't included
-        let mut new_inv = inv.clone();
-        for carrying in &inv {
-            if !set.contains(carrying) {
-                commands.push_str(&format!("drop {}\n", carrying));
-                new_inv.remove(carrying);
+    /*
+     * Checks that the droid can take an item and still move, on a copy of it.
+     */
+    fn is_safe(&self, item: &str, door: Direction) -> bool {
+        let mut droid = self.droid.clone();
+        match droid.command(&Command::Take(item.to_string())) {
+            Reply::Prompt(_) => (),
+            _ => return false,
+        }
+        match droid.command(&Command::Go(door)) {
+            Reply::Prompt(text) => !adventure::rooms(&adventure::parse(&text)).is_empty(),
+            _ => false,
+        }
+    }
+
+    /*
+     * Visits every room reachable from this one, picking up whatever is safe to take,
+     * and comes back.
+     */
+    fn explore(&mut self, room: Room, path: &mut Vec<Direction>) {
+        self.visited.insert(room.name.clone());
+        for item in &room.items {
+            if self.is_safe(item, room.doors[0]) {
+                self.command(Command::Take(item.clone()));
+                self.inventory.push(item.clone());
+            }
+        }
+        for &door in &room.doors {
+            let messages = self.command(Command::Go(door));
+            if messages.iter().any(|message| matches!(message, Message::Ejected(_))) {
+                self.checkpoint = Some((path.clone(), door));
+                continue;
+            }
+            let next = adventure::rooms(&messages).pop().cloned().expect("Was expecting a room");
+            if !self.visited.contains(&next.name) {
+                path.push(door);
+                self.explore(next, path);
+                path.pop();
             }
+            self.command(Command::Go(door.opposite()));
         }
-        inv = new_inv;
+    }
 
-        // Pick up items that we don't have
-        for item in set {
-            if !inv.contains(item) {
-                commands.push_str(&format!("take {}\n", item));
-                inv.insert(item);
+    /*
+     * Sets what the droid carries to the items in `subset`, a bitmask over `items`.
+     */
+    fn carry(&mut self, items: &[String], subset: u32) {
+        for (i, item) in items.iter().enumerate() {
+            let wanted = subset & (1 << i) != 0;
+            if wanted != self.inventory.contains(item) {
+                if wanted {
+                    self.command(Command::Take(item.clone()));
+                    self.inventory.push(item.clone());
+                } else {
+                    self.command(Command::Drop(item.clone()));
+                    self.inventory.retain(|held| held != item);
+                }
             }
         }
-        commands.push_str(direction);
     }
-	commands
+
+    /*
+     * Tries combinations of the items on the pressure-sensitive floor until one gets through.
+     *
+     * Combinations are tried in Gray code order, so that the next one is usually a single take or
+     * drop away. Items only add weight, so once a combination is too heavy every combination
+     * containing it is too, and once one is too light so is every combination inside it.
+     */
+    fn pass_checkpoint(&mut self, floor: Direction) -> String {
+        let items = self.inventory.clone();
+        assert!(items.len() < 32, "Too many items to try combinations of");
+        let mut too_heavy: Vec<u32> = Vec::new();
+        let mut too_light: Vec<u32> = Vec::new();
+        for i in 0..1u32 << items.len() {
+            let subset = i ^ (i >> 1);
+            if too_heavy.iter().any(|heavy| subset & heavy == *heavy)
+                || too_light.iter().any(|light| subset & light == subset) {
+                continue;
+            }
+            self.carry(&items, subset);
+            self.attempts += 1;
+            let reply = self.droid.command(&Command::Go(floor));
+            let messages = reply.messages();
+            match reply {
+                Reply::Prompt(_) => match messages.iter().find_map(|message| match message {
+                    Message::Ejected(weight) => Some(*weight),
+                    _ => None,
+                }) {
+                    Some(Weight::TooHeavy) => too_heavy.push(subset),
+                    Some(Weight::TooLight) => too_light.push(subset),
+                    None => panic!("Was expecting to be weighed"),
+                },
+                Reply::Halted(_) => {
+                    return messages.into_iter().find_map(|message| match message {
+                        Message::Password(password) => Some(password),
+                        _ => None,
+                    }).expect("Was expecting a password");
+                }
+                Reply::Stuck(_) => panic!("Game got stuck on the pressure-sensitive floor"),
+            }
+        }
+        panic!("No combination of items passed the pressure-sensitive floor");
+    }
+
+    fn solve(&mut self) -> String {
+        let start = match self.droid.send("") {
+            Reply::Prompt(text) => adventure::parse(&text),
+            reply => panic!("Game didn't start: {:?}", reply),
+        };
+        let start = adventure::rooms(&start).pop().cloned().expect("Was expecting a room");
+        self.explore(start, &mut Vec::new());
+        let (path, floor) = self.checkpoint.clone().expect("Didn't find the Security Checkpoint");
+        for door in path {
+            self.command(Command::Go(door));
+        }
+        self.pass_checkpoint(floor)
+    }
+}
+
+/*
+ * Plays the game by hand, with commands from stdin.
+ */
+pub fn play(droid: &intcode::IntCode) {
+    let stdin = io::stdin();
+    if let Err(e) = Shell::new(droid.clone()).run(stdin.lock(), io::stdout()) {
+        eprintln!("Error playing: {}", e);
+    }
 }
 
 #[aoc(day25, part1)]
-fn solve_part1(input: &intcode::IntCode) -> i32 {
-    let mut droid = input.clone();
-
-    loop {
-        match droid.execute() {
-            intcode::Status::Halt => break,
-            intcode::Status::WaitingInput => {
-				// TODO: what if carriage returns??
-                let mut input = String::new();
-                if let Err(e) = io::stdin().read_line(&mut input) {
-                    eprintln!("Error reading from stdin: {}", e);
+fn solve_part1(input: &intcode::IntCode) -> String {
+    Solver::new(input.clone()).solve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A small ship talking like the real game, with the kinds of items that are dangerous to pick up
+    #[derive(Clone)]
+    struct Ship {
+        location: usize,
+        items: Vec<Vec<&'static str>>,
+        inventory: Vec<&'static str>,
+    }
+
+    const ROOMS: [(&str, &[(&str, usize)]); 7] = [
+        ("Hull Breach", &[("north", 1), ("east", 2), ("west", 4)]),
+        ("Kitchen", &[("south", 0)]),
+        ("Hallway", &[("east", 3), ("west", 0)]),
+        ("Security Checkpoint", &[("north", 6), ("west", 2)]),
+        ("Storage", &[("north", 5), ("east", 0)]),
+        ("Observatory", &[("south", 4)]),
+        ("Pressure-Sensitive Floor", &[("south", 3)]),
+    ];
+    const WEIGHTS: [(&str, i64); 5] = [("mug", 1), ("boulder", 2), ("food ration", 4), ("asterisk", 8), ("candy cane", 16)];
+    const TARGET: i64 = 25;
+
+    impl Ship {
+        fn new() -> Ship {
+            let items = vec![
+                vec![], vec!["mug", "molten lava"], vec!["boulder", "infinite loop"], vec![],
+                vec!["food ration", "giant electromagnet", "asterisk"], vec!["candy cane"], vec![],
+            ];
+            Ship { location: 0, items, inventory: Vec::new() }
+        }
+
+        fn describe(&self, room: usize) -> String {
+            let (name, doors) = ROOMS[room];
+            let mut text = format!("\n\n\n== {} ==\nA room.\n\nDoors here lead:\n", name);
+            for (door, _) in doors {
+                text.push_str(&format!("- {}\n", door));
+            }
+            if !self.items[room].is_empty() {
+                text.push_str("\nItems here:\n");
+                for item in &self.items[room] {
+                    text.push_str(&format!("- {}\n", item));
                 }
-                if input == "solve\n" {
-                    droid.input_queue.extend(bruteforce().bytes().map(|x| x as i64));
-                } else {
-                    droid.input_queue.extend(input.bytes().map(|x| x as i64));
+            }
+            text
+        }
+
+        fn walk(&mut self, to: usize) -> Reply {
+            if to != 6 {
+                self.location = to;
+                return Reply::Prompt(format!("{}\nCommand?\n", self.describe(to)));
+            }
+            let weight: i64 = self.inventory.iter()
+                .map(|item| WEIGHTS.iter().find(|(name, _)| name == item).unwrap().1)
+                .sum();
+            let floor = self.describe(6);
+            if weight == TARGET {
+                return Reply::Halted(format!("{}\nA loud, robotic voice says \"Analysis complete! You may proceed.\" \
+                    and you enter the cockpit.\n\"Oh, hello! You should be able to get in by typing 2424308736 on the keypad \
+                    at the main airlock.\"\n", floor));
+            }
+            let comparison = if weight > TARGET { "lighter" } else { "heavier" };
+            Reply::Prompt(format!("{}\nA loud, robotic voice says \"Alert! Droids on this ship are {} than the detected value!\" \
+                and you are ejected back to the checkpoint.\n{}\nCommand?\n", floor, comparison, self.describe(3)))
+        }
+    }
+
+    impl Adventure for Ship {
+        fn send(&mut self, command: &str) -> Reply {
+            if command.is_empty() {
+                return Reply::Prompt(format!("{}\nCommand?\n", self.describe(self.location)));
+            }
+            if let Some((_, to)) = ROOMS[self.location].1.iter().find(|(door, _)| *door == command) {
+                if self.inventory.contains(&"giant electromagnet") {
+                    return Reply::Prompt("\nThe giant electromagnet is stuck to you.  You can't move!!\n\nCommand?\n".to_string());
                 }
+                return self.walk(*to);
+            }
+            if let Some(item) = command.strip_prefix("take ") {
+                let here = &mut self.items[self.location];
+                let item = match here.iter().position(|i| *i == item) {
+                    Some(i) => here.remove(i),
+                    None => return Reply::Prompt("\nYou don't see that item here.\n\nCommand?\n".to_string()),
+                };
+                self.inventory.push(item);
+                return match item {
+                    "infinite loop" => Reply::Stuck(String::new()),
+                    "molten lava" => Reply::Halted("\nYou take the molten lava.\n\nThe molten lava is way too hot! You melt!\n".to_string()),
+                    _ => Reply::Prompt(format!("\nYou take the {}.\n\nCommand?\n", item)),
+                };
             }
-            intcode::Status::Output(c) => print!("{}", c as u8 as char),
+            if let Some(item) = command.strip_prefix("drop ") {
+                return match self.inventory.iter().position(|i| *i == item) {
+                    Some(i) => {
+                        let item = self.inventory.remove(i);
+                        self.items[self.location].push(item);
+                        Reply::Prompt(format!("\nYou drop the {}.\n\nCommand?\n", item))
+                    }
+                    None => Reply::Prompt("\nYou don't have that item.\n\nCommand?\n".to_string()),
+                };
+            }
+            Reply::Prompt("\nUnrecognized command.\n\nCommand?\n".to_string())
         }
     }
-	0
+
+    #[test]
+    fn solve() {
+        let mut solver = Solver::new(Ship::new());
+        assert_eq!(solver.solve(), "2424308736");
+        let mut inventory = solver.inventory.clone();
+        inventory.sort();
+        assert_eq!(inventory, ["asterisk", "candy cane", "mug"]);
+        assert_eq!(solver.checkpoint, Some((vec![Direction::East, Direction::East], Direction::North)));
+        // Out of the 32 combinations of the five safe items
+        assert_eq!(solver.attempts, 10);
+    }
 }
@@ -0,0 +1,604 @@
+/*
+ * Greyscale and colour images, for saving the pictures some puzzles draw.
+ *
+ * Saves to plain PBM, PGM and PPM, which are easy to read and diff, and to PNG for everything else.
+ * The PNG encoder doesn't compress, so it needs nothing but the standard library.
+ * Loads any of those back, including binary PBM/PGM and compressed PNGs from other programs,
+ * with colour turned into grey.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub mod inflate;
+
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 255;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+    Io { path: String, message: String },
+    Invalid(String),
+    Unsupported(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io { path, message } => write!(f, "could not read {}: {}", path, message),
+            ImageError::Invalid(message) => write!(f, "invalid image: {}", message),
+            ImageError::Unsupported(message) => write!(f, "unsupported image: {}", message),
+        }
+    }
+}
+
+impl Error for ImageError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    // Rows from top to bottom, 0 is black and 255 white
+    pixels: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColorImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Image {
+        Image { width, height, pixels: vec![BLACK; width * height] }
+    }
+
+    pub fn from_fn<F: Fn(usize, usize) -> u8>(width: usize, height: usize, shade: F) -> Image {
+        let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| shade(x, y)).collect();
+        Image { width, height, pixels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, shade: u8) {
+        self.pixels[y * self.width + x] = shade;
+    }
+
+    /*
+     * Blows every pixel up into a `factor` by `factor` square.
+     */
+    pub fn scaled(&self, factor: usize) -> Image {
+        Image::from_fn(self.width * factor, self.height * factor, |x, y| self.get(x / factor, y / factor))
+    }
+
+    /*
+     * Plain (ASCII) PBM, where anything darker than mid-grey counts as black.
+     */
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut file = format!("P1\n{} {}\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            let bits: Vec<&str> = row.iter().map(|&shade| if shade < 128 { "1" } else { "0" }).collect();
+            file.push_str(&bits.join(" "));
+            file.push('\n');
+        }
+        file.into_bytes()
+    }
+
+    /*
+     * Plain (ASCII) PGM.
+     */
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut file = format!("P2\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            let shades: Vec<String> = row.iter().map(|shade| shade.to_string()).collect();
+            file.push_str(&shades.join(" "));
+            file.push('\n');
+        }
+        file.into_bytes()
+    }
+
+    /*
+     * 8-bit greyscale PNG.
+     */
+    pub fn to_png(&self) -> Vec<u8> {
+        png(self.width, self.height, 0, 1, &self.pixels)
+    }
+
+    /*
+     * Saves in the format the extension asks for: .pbm, .pgm or .png.
+     */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = match extension(path).as_deref() {
+            Some("pbm") => self.to_pbm(),
+            Some("pgm") => self.to_pgm(),
+            Some("png") => self.to_png(),
+            _ => return Err(unknown_format(path)),
+        };
+        fs::write(path, data)
+    }
+
+    /*
+     * Reads a PBM, PGM or PNG file, going by its contents rather than its name.
+     */
+    pub fn decode(data: &[u8]) -> Result<Image, ImageError> {
+        match data {
+            [0x89, b'P', b'N', b'G', ..] => from_png(data),
+            [b'P', b'1', ..] | [b'P', b'2', ..] | [b'P', b'4', ..] | [b'P', b'5', ..] => from_netpbm(data),
+            _ => Err(ImageError::Unsupported("not a PBM, PGM or PNG file".to_string())),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Image, ImageError> {
+        let path = path.as_ref();
+        let data = fs::read(path)
+            .map_err(|e| ImageError::Io { path: path.display().to_string(), message: e.to_string() })?;
+        Image::decode(&data)
+    }
+}
+
+fn invalid<T>(message: &str) -> Result<T, ImageError> {
+    Err(ImageError::Invalid(message.to_string()))
+}
+
+fn luma(r: u32, g: u32, b: u32) -> u8 {
+    ((299 * r + 587 * g + 114 * b + 500) / 1000) as u8
+}
+
+/*
+ * Reads the header fields of a Netpbm file, skipping whitespace and comments.
+ */
+fn netpbm_field(data: &[u8], position: &mut usize) -> Result<usize, ImageError> {
+    loop {
+        match data.get(*position) {
+            Some(b'#') => {
+                while data.get(*position).is_some_and(|&c| c != b'\n') {
+                    *position += 1;
+                }
+            }
+            Some(c) if c.is_ascii_whitespace() => *position += 1,
+            Some(_) => break,
+            None => return invalid("file ends in the header"),
+        }
+    }
+    let start = *position;
+    while data.get(*position).is_some_and(|c| c.is_ascii_digit()) {
+        *position += 1;
+    }
+    std::str::from_utf8(&data[start..*position]).unwrap().parse().or_else(|_| invalid("expected a number in the header"))
+}
+
+fn from_netpbm(data: &[u8]) -> Result<Image, ImageError> {
+    let kind = data[1];
+    let mut position = 2;
+    let width = netpbm_field(data, &mut position)?;
+    let height = netpbm_field(data, &mut position)?;
+    let max = if kind == b'1' || kind == b'4' { 1 } else { netpbm_field(data, &mut position)? };
+    if max == 0 || max > 65535 {
+        return invalid("maximum value out of range");
+    }
+    // A single whitespace character separates the header from binary data
+    position += 1;
+
+    let values: Vec<usize> = match kind {
+        b'1' => data.get(position..).unwrap_or(&[]).iter()
+            .filter(|c| **c == b'0' || **c == b'1').map(|c| (c - b'0') as usize).collect(),
+        b'4' => {
+            let row = width.div_ceil(8);
+            data.get(position..).unwrap_or(&[]).chunks_exact(row.max(1)).take(height)
+                .flat_map(|bytes| (0..width).map(move |x| (bytes[x / 8] >> (7 - x % 8)) as usize & 1))
+                .collect()
+        }
+        b'5' if max > 255 => data.get(position..).unwrap_or(&[]).chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize).collect(),
+        b'5' => data.get(position..).unwrap_or(&[]).iter().map(|&b| b as usize).collect(),
+        _ => {
+            let mut values = Vec::new();
+            while values.len() < width * height {
+                values.push(netpbm_field(data, &mut position)?);
+            }
+            values
+        }
+    };
+    if values.len() < width * height {
+        return invalid("file ends before the last pixel");
+    }
+    let pixels = values[..width * height].iter().map(|&value| match kind {
+        // Bitmaps have 1 for black
+        b'1' | b'4' => if value == 1 { BLACK } else { WHITE },
+        _ => (value.min(max) * 255 / max) as u8,
+    }).collect();
+    Ok(Image { width, height, pixels })
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+fn from_png(data: &[u8]) -> Result<Image, ImageError> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return invalid("bad PNG signature");
+    }
+    let mut position = 8;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = Vec::new();
+    loop {
+        let length = match data.get(position..position + 4) {
+            Some(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+            None => return invalid("PNG ends without an IEND chunk"),
+        };
+        let chunk = match data.get(position + 4..position + 8 + length) {
+            Some(chunk) => chunk,
+            None => return invalid("PNG chunk runs past the end of the file"),
+        };
+        let crc = data.get(position + 8 + length..position + 12 + length);
+        if crc != Some(&crc32(chunk).to_be_bytes()[..]) {
+            return invalid("PNG chunk checksum doesn't match");
+        }
+        position += 12 + length;
+        let (kind, body) = chunk.split_at(4);
+        match kind {
+            b"IHDR" if body.len() == 13 => header = Some(body),
+            b"PLTE" => palette = body,
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => (),
+        }
+    }
+    let header = match header {
+        Some(header) => header,
+        None => return invalid("PNG has no IHDR chunk"),
+    };
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (depth, color) = (header[8] as usize, header[9]);
+    if header[12] != 0 {
+        return Err(ImageError::Unsupported("interlaced PNG".to_string()));
+    }
+    let channels = match (color, depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) | (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (4, 8) | (4, 16) => 2,
+        (2, 8) | (2, 16) => 3,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(ImageError::Unsupported(format!("PNG colour type {} with bit depth {}", color, depth))),
+    };
+
+    let raw = inflate::decompress(&compressed).map_err(ImageError::Invalid)?;
+    let stride = (width * channels * depth).div_ceil(8);
+    // Filters work on whole pixels, or bytes for bit depths below 8
+    let step = (channels * depth / 8).max(1);
+    if raw.len() < (stride + 1) * height {
+        return invalid("PNG image data is too short");
+    }
+    let mut rows: Vec<Vec<u8>> = Vec::with_capacity(height);
+    for y in 0..height {
+        let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        let mut row = line[1..].to_vec();
+        for i in 0..stride {
+            let a = if i >= step { row[i - step] } else { 0 };
+            let b = if y > 0 { rows[y - 1][i] } else { 0 };
+            let c = if y > 0 && i >= step { rows[y - 1][i - step] } else { 0 };
+            let predicted = match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return invalid("unknown PNG filter type"),
+            };
+            row[i] = row[i].wrapping_add(predicted);
+        }
+        rows.push(row);
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in &rows {
+        for x in 0..width {
+            // Samples of 16 bits are cut down to their high byte
+            let sample = |channel: usize| -> u32 {
+                match depth {
+                    8 => row[x * channels + channel] as u32,
+                    16 => row[(x * channels + channel) * 2] as u32,
+                    _ => {
+                        let bit = x * depth;
+                        (row[bit / 8] >> (8 - depth - bit % 8)) as u32 & ((1 << depth) - 1)
+                    }
+                }
+            };
+            pixels.push(match color {
+                0 | 4 if depth < 8 => (sample(0) * 255 / ((1 << depth) - 1)) as u8,
+                0 | 4 => sample(0) as u8,
+                3 => match palette.get(sample(0) as usize * 3..sample(0) as usize * 3 + 3) {
+                    Some(rgb) => luma(rgb[0] as u32, rgb[1] as u32, rgb[2] as u32),
+                    None => return invalid("PNG palette index out of range"),
+                },
+                _ => luma(sample(0), sample(1), sample(2)),
+            });
+        }
+    }
+    Ok(Image { width, height, pixels })
+}
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    /*
+     * How bright the colour looks, as a shade of grey.
+     */
+    pub fn luma(self) -> u8 {
+        luma(self.0 as u32, self.1 as u32, self.2 as u32)
+    }
+}
+
+impl ColorImage {
+    pub fn from_fn<F: Fn(usize, usize) -> Rgb>(width: usize, height: usize, color: F) -> ColorImage {
+        let pixels = (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| color(x, y)).collect();
+        ColorImage { width, height, pixels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn to_grey(&self) -> Image {
+        Image::from_fn(self.width, self.height, |x, y| self.get(x, y).luma())
+    }
+
+    /*
+     * Plain (ASCII) PPM.
+     */
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut file = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            let colors: Vec<String> = row.iter().map(|Rgb(r, g, b)| format!("{} {} {}", r, g, b)).collect();
+            file.push_str(&colors.join("  "));
+            file.push('\n');
+        }
+        file.into_bytes()
+    }
+
+    /*
+     * 8-bit RGB PNG.
+     */
+    pub fn to_png(&self) -> Vec<u8> {
+        let samples: Vec<u8> = self.pixels.iter().flat_map(|&Rgb(r, g, b)| vec![r, g, b]).collect();
+        png(self.width, self.height, 2, 3, &samples)
+    }
+
+    /*
+     * Draws the image for a terminal with 24-bit colour, each pixel as two spaces on a coloured background.
+     */
+    pub fn to_ansi(&self) -> String {
+        let mut picture = String::new();
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            for Rgb(r, g, b) in row {
+                picture.push_str(&format!("\x1b[48;2;{};{};{}m  ", r, g, b));
+            }
+            picture.push_str("\x1b[0m\n");
+        }
+        picture
+    }
+
+    /*
+     * Saves in the format the extension asks for: .ppm or .png in colour, or .pbm or .pgm in grey.
+     */
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = match extension(path).as_deref() {
+            Some("ppm") => self.to_ppm(),
+            Some("png") => self.to_png(),
+            Some("pbm") | Some("pgm") => return self.to_grey().save(path),
+            _ => return Err(unknown_format(path)),
+        };
+        fs::write(path, data)
+    }
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+}
+
+fn unknown_format(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("don't know what image format {} should be", path.display()))
+}
+
+/*
+ * Writes an 8-bit PNG from rows of samples, in greyscale (colour type 0) or RGB (colour type 2).
+ */
+fn png(width: usize, height: usize, color_type: u8, channels: usize, samples: &[u8]) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth 8, and the only compression, filter and interlace methods there are
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    // Every row starts with its filter type, which is always "none" here
+    let stride = width * channels;
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    for y in 0..height {
+        raw.push(0);
+        raw.extend_from_slice(&samples[y * stride..(y + 1) * stride]);
+    }
+
+    let mut file = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut file, b"IHDR", &header);
+    chunk(&mut file, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut file, b"IEND", &[]);
+    file
+}
+
+fn chunk(file: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = file.len();
+    file.extend_from_slice(kind);
+    file.extend_from_slice(data);
+    let crc = crc32(&file[start..]);
+    file.extend_from_slice(&crc.to_be_bytes());
+}
+
+/*
+ * Wraps data in a zlib stream made of uncompressed deflate blocks.
+ */
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, no dictionary, and a check value that makes this a multiple of 31
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        stream.push(if blocks.peek().is_none() { 1 } else { 0 });
+        stream.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        stream.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image {
+        let mut image = Image::new(3, 2);
+        image.set(0, 0, WHITE);
+        image.set(2, 1, 100);
+        image
+    }
+
+    #[test]
+    fn netpbm() {
+        assert_eq!(String::from_utf8(image().to_pbm()).unwrap(), "P1\n3 2\n0 1 1\n1 1 1\n");
+        assert_eq!(String::from_utf8(image().to_pgm()).unwrap(), "P2\n3 2\n255\n255 0 0\n0 0 100\n");
+        assert_eq!(image().scaled(2).to_pbm(), b"P1\n6 4\n0 0 1 1 1 1\n0 0 1 1 1 1\n1 1 1 1 1 1\n1 1 1 1 1 1\n".to_vec());
+    }
+
+    #[test]
+    fn png() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let png = image().to_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[8..33], b"\0\0\0\x0dIHDR\0\0\0\x03\0\0\0\x02\x08\0\0\0\0\xb8\x1f\x39\xc6");
+        // The rows, each with a filter byte, in a single final stored block
+        let data = [0, 255, 0, 0, 0, 0, 0, 100];
+        assert_eq!(&png[33..41], b"\0\0\0\x13IDAT");
+        assert_eq!(&png[41..48], &[0x78, 0x01, 1, 8, 0, 0xf7, 0xff]);
+        assert_eq!(&png[48..56], &data);
+        assert_eq!(&png[56..60], &adler32(&data).to_be_bytes());
+        assert_eq!(&png[64..], b"\0\0\0\0IEND\xae\x42\x60\x82");
+    }
+
+    #[test]
+    fn decode() {
+        for data in [image().to_pbm(), image().to_pgm(), image().to_png()].iter() {
+            let decoded = Image::decode(data).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (3, 2));
+        }
+        assert_eq!(Image::decode(&image().to_pgm()), Ok(image()));
+        assert_eq!(Image::decode(&image().to_png()), Ok(image()));
+        assert_eq!(Image::decode(&image().to_pbm()).unwrap().get(2, 1), BLACK);
+
+        // Binary Netpbm, with a comment and a maximum value that isn't 255
+        assert_eq!(Image::decode(b"P5 # made by hand\n3 2\n15\n\x0f\x00\x00\x00\x00\x05"),
+            Ok(Image::from_fn(3, 2, |x, y| [[255, 0, 0], [0, 0, 85]][y][x])));
+        assert_eq!(Image::decode(b"P4\n3 2\n\x60\xe0"), Image::decode(&image().to_pbm()));
+        assert_eq!(Image::decode(b"P1\n3 2\n011111"), Image::decode(&image().to_pbm()));
+        assert!(Image::decode(b"P2\n3 2\n255\n1 2 3").is_err());
+        assert!(Image::decode(b"P4\n9 2\n\x60\xe0\x00").is_err());
+        assert!(Image::decode(b"P3\n1 1\n255\n0 0 0").is_err());
+        assert!(Image::decode(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn color() {
+        let image = ColorImage::from_fn(2, 1, |x, _| if x == 0 { Rgb(255, 0, 0) } else { Rgb::WHITE });
+        assert_eq!(String::from_utf8(image.to_ppm()).unwrap(), "P3\n2 1\n255\n255 0 0  255 255 255\n");
+        assert_eq!(image.to_ansi(), "\x1b[48;2;255;0;0m  \x1b[48;2;255;255;255m  \x1b[0m\n");
+        assert_eq!(image.to_grey(), Image::from_fn(2, 1, |x, _| [76, 255][x]));
+
+        let png = image.to_png();
+        assert_eq!(png[25], 2, "should be an RGB PNG");
+        assert_eq!(Image::decode(&png), Ok(image.to_grey()));
+    }
+
+    #[test]
+    fn compressed_png() {
+        // Written by zlib at level 9, with each row using the next filter type
+        assert_eq!(Image::decode(GREY), Ok(Image::from_fn(4, 5, |x, y| (x * 40 + y * 13) as u8)));
+        // Red, green, blue and white
+        assert_eq!(Image::decode(RGB), Ok(Image::from_fn(2, 2, |x, y| [[76, 150], [29, 255]][y][x])));
+
+        let mut corrupt = GREY.to_vec();
+        corrupt[50] ^= 1;
+        assert!(Image::decode(&corrupt).is_err());
+    }
+
+    const GREY: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x05, 0x08, 0x00, 0x00, 0x00, 0x00, 0x47, 0xc6, 0x12,
+        0x07, 0x00, 0x00, 0x00, 0x1d, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x60, 0xd0, 0x08, 0xa8,
+        0x60, 0xe4, 0xd5, 0xd0, 0xd0, 0x60, 0xe2, 0x05, 0x02, 0x66, 0x29, 0x69, 0x69, 0x69, 0x16, 0x10,
+        0x0b, 0x00, 0x23, 0xe9, 0x02, 0x53, 0xa3, 0xb1, 0x4a, 0x4e, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+        0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    const RGB: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x02, 0x00, 0x00, 0x00, 0xfd, 0xd4, 0x9a,
+        0x73, 0x00, 0x00, 0x00, 0x15, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xf8, 0xcf, 0xc0, 0xc0,
+        0xf0, 0x9f, 0x81, 0x11, 0x48, 0xfc, 0xff, 0xcf, 0x00, 0x00, 0x1e, 0xf6, 0x04, 0xfd, 0x37, 0x86,
+        0x66, 0x6a, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+}
@@ -0,0 +1,270 @@
+/*
+ * Decompressor for zlib streams (RFC 1950) and the deflate format inside them (RFC 1951),
+ * enough to read PNG files written by other programs.
+ *
+ * Huffman codes are decoded a bit at a time from their code length counts, which is slow
+ * but simple, and images here are small.
+ */
+
+const MAX_BITS: usize = 15;
+
+// Base values and extra bits for length codes 257..285 and distance codes 0..29
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u32; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u32; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// Order in which a dynamic block gives the lengths of the code length code
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    // Position in bits
+    position: usize,
+}
+
+/*
+ * Canonical Huffman code, as the number of codes of each length and the symbols in code order.
+ */
+struct Huffman {
+    counts: [usize; MAX_BITS + 1],
+    symbols: Vec<usize>,
+}
+
+impl<'a> Bits<'a> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = self.data.get(self.position / 8).ok_or("compressed data ends early")?;
+        let bit = (byte >> (self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit as u32)
+    }
+
+    // Extra bits and header fields come least significant bit first
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+impl Huffman {
+    /*
+     * Builds the code for symbols with the given code lengths, where 0 means unused.
+     * Incomplete codes are allowed, since deflate uses them for a single distance code.
+     */
+    fn new(lengths: &[usize]) -> Result<Huffman, String> {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length] += 1;
+        }
+        let mut left: i64 = 1;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i64;
+            if left < 0 {
+                return Err("over-subscribed Huffman code".to_string());
+            }
+        }
+        let mut symbols: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] != 0).collect();
+        // Stable, so symbols of the same length stay in order
+        symbols.sort_by_key(|&s| lengths[s]);
+        counts[0] = 0;
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<usize, String> {
+        // `code` is the code read so far, `first` the first code of this length,
+        // and `index` where the codes of this length start in `symbols`
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.bit()? as usize;
+            if code < first + count {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0; 288];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&[5; 30]).unwrap())
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.bits(5)? as usize + 257;
+    let distances = bits.bits(5)? as usize + 1;
+    let code_lengths = bits.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err("too many codes in dynamic block".to_string());
+    }
+
+    let mut lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.bits(3)? as usize;
+    }
+    let code_length_code = Huffman::new(&lengths)?;
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (length, repeat) = match code_length_code.decode(bits)? {
+            symbol @ 0..=15 => (symbol, 1),
+            16 => (*lengths.last().ok_or("repeated length with nothing to repeat")?, 3 + bits.bits(2)?),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(length);
+        }
+    }
+    if lengths.len() > literals + distances {
+        return Err("code lengths run past the end of the table".to_string());
+    }
+    if lengths[256] == 0 {
+        return Err("no end-of-block code".to_string());
+    }
+    Ok((Huffman::new(&lengths[..literals])?, Huffman::new(&lengths[literals..])?))
+}
+
+fn codes(bits: &mut Bits, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)?;
+        if symbol < 256 {
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let symbol = symbol - 257;
+        if symbol >= LENGTH_BASE.len() {
+            return Err("invalid length code".to_string());
+        }
+        let length = LENGTH_BASE[symbol] + bits.bits(LENGTH_EXTRA[symbol])? as usize;
+        let symbol = distances.decode(bits)?;
+        if symbol >= DISTANCE_BASE.len() {
+            return Err("invalid distance code".to_string());
+        }
+        let distance = DISTANCE_BASE[symbol] + bits.bits(DISTANCE_EXTRA[symbol])? as usize;
+        if distance > output.len() {
+            return Err("distance reaches back before the start of the data".to_string());
+        }
+        // The copy can overlap what it's producing, so it has to go a byte at a time
+        let start = output.len() - distance;
+        for i in 0..length {
+            output.push(output[start + i]);
+        }
+    }
+}
+
+/*
+ * Decompresses raw deflate data, returning it and the number of bytes it took up.
+ */
+pub fn inflate(data: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut bits = Bits { data, position: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let start = bits.position / 8;
+                let header = data.get(start..start + 4).ok_or("compressed data ends early")?;
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return Err("stored block length doesn't match its complement".to_string());
+                }
+                let block = data.get(start + 4..start + 4 + length as usize).ok_or("compressed data ends early")?;
+                output.extend_from_slice(block);
+                bits.position = (start + 4 + length as usize) * 8;
+            }
+            1 => {
+                let (literals, distances) = fixed_codes();
+                codes(&mut bits, &mut output, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut output, &literals, &distances)?;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+        if last {
+            bits.align();
+            return Ok((output, bits.position / 8));
+        }
+    }
+}
+
+/*
+ * Decompresses a zlib stream, checking its header and checksum.
+ */
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 {
+        return Err("zlib stream is too short".to_string());
+    }
+    let (method, flags) = (data[0], data[1]);
+    if method & 0x0f != 8 || !(method as u16 * 256 + flags as u16).is_multiple_of(31) {
+        return Err("not a deflate zlib stream".to_string());
+    }
+    if flags & 0x20 != 0 {
+        return Err("zlib stream needs a preset dictionary".to_string());
+    }
+    let (output, length) = inflate(&data[2..])?;
+    let checksum = data.get(2 + length..6 + length).ok_or("zlib stream has no checksum")?;
+    if super::adler32(&output).to_be_bytes() != checksum {
+        return Err("zlib checksum doesn't match".to_string());
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks() {
+        // A stored block, and a fixed block with a match that overlaps itself ("abc" then 9 more)
+        assert_eq!(inflate(&[1, 3, 0, 0xfc, 0xff, b'a', b'b', b'c']), Ok((b"abc".to_vec(), 8)));
+        assert_eq!(decompress(&[0x78, 0xda, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00, 0x1d, 0xe0, 0x04, 0x99]).as_deref(),
+            Ok(&b"abcabcabcabc"[..]));
+        assert!(decompress(&[0x78, 0xda, 0x4b, 0x4c, 0x4a, 0x4e, 0x84, 0x21, 0x00, 0x1d, 0xe0, 0x04, 0x98]).is_err());
+        assert!(inflate(&[1, 3, 0, 0xfc]).is_err());
+    }
+
+    #[test]
+    fn dynamic() {
+        // zlib.compress of "The quick brown fox jumps over the lazy dog. " repeated, at level 9
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(20) + "Pack my box with five dozen liquor jugs.";
+        assert_eq!(DYNAMIC[2] >> 1 & 3, 2, "should be a dynamic block");
+        assert_eq!(decompress(DYNAMIC), Ok(text.into_bytes()));
+    }
+
+    const DYNAMIC: &[u8] = &[
+        0x78, 0xda, 0xed, 0xcb, 0xc9, 0x01, 0x80, 0x20, 0x0c, 0x05, 0xd1, 0x56, 0x7e, 0x05, 0xd6, 0xe2,
+        0x81, 0x06, 0x50, 0x83, 0x44, 0x85, 0x08, 0x0a, 0x2e, 0xd5, 0x9b, 0x22, 0x3c, 0x72, 0x9e, 0x37,
+        0xc6, 0x13, 0x52, 0xe1, 0x71, 0xc5, 0x90, 0xe5, 0x8a, 0x70, 0x72, 0x63, 0x29, 0x61, 0x3f, 0x20,
+        0x95, 0x32, 0x4e, 0xcd, 0x9b, 0x7d, 0x1f, 0x4c, 0x32, 0x77, 0x30, 0x0d, 0x37, 0xdc, 0xf0, 0xdf,
+        0xb8, 0xb7, 0xea, 0xc2, 0x83, 0x41, 0xd1, 0xc5, 0xa7, 0x87, 0xe3, 0x4a, 0x9a, 0x5e, 0x8a, 0xd8,
+        0x38, 0x15, 0xc9, 0xfa, 0xce, 0x47, 0xf7, 0x01, 0xeb, 0x72, 0x51, 0xb3,
+    ];
+}
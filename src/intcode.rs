@@ -1,11 +1,33 @@
 use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::num::ParseIntError;
+use std::sync::Arc;
+
+pub mod cache;
+pub mod compiler;
+pub mod coverage;
+pub mod decompile;
+pub mod disasm;
+pub mod fingerprint;
+pub mod fuzz;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub mod jit;
+pub mod memdiff;
+pub mod optimize;
+pub mod patch;
+pub mod pool;
+pub mod symbolic;
 
 #[derive(Clone)]
 pub struct IntCode {
-    pub ip: usize,
-    pub relative_base: i64,
-    pub mem: Vec<i64>,
-    pub input_queue: VecDeque<i64>
+    ip: usize,
+    relative_base: i64,
+    mem: Vec<i64>,
+    input_queue: VecDeque<i64>,
+    // The loaded program and the patches applied on top of it, restored by reset()
+    program: Arc<Vec<i64>>,
+    patches: Vec<(usize, i64)>,
 }
 
 pub enum Status {
@@ -14,8 +36,145 @@ pub enum Status {
     WaitingInput,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub addr: usize,
+    pub size: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "address {} is out of bounds for a memory of {} cells", self.addr, self.size)
+    }
+}
+
+impl Error for OutOfBounds {}
+
+/*
+ * Parses a comma-separated Intcode program, ignoring surrounding whitespace.
+ */
+pub fn parse_program(input: &str) -> Result<Vec<i64>, ParseIntError> {
+    input
+        .trim()
+        .split(',')
+        .map(|n| n.trim().parse())
+        .collect()
+}
+
 impl IntCode {
 
+    pub fn new(program: Vec<i64>) -> IntCode {
+        IntCode {
+            ip: 0,
+            relative_base: 0,
+            mem: program.clone(),
+            input_queue: VecDeque::new(),
+            program: Arc::new(program),
+            patches: Vec::new(),
+        }
+    }
+
+    /*
+     * Replaces the program, dropping all patches. Memory keeps its size unless the program needs more.
+     */
+    pub fn load(&mut self, program: Vec<i64>) {
+        let size = self.mem.len().max(program.len());
+        self.program = Arc::new(program);
+        self.patches.clear();
+        self.mem.clear();
+        self.mem.resize(size, 0);
+        self.reset();
+    }
+
+    /*
+     * Puts the machine back into its initial state: the program with its patches applied,
+     * at the first instruction and without any pending input.
+     */
+    pub fn reset(&mut self) {
+        let size = self.mem.len();
+        self.mem.clear();
+        self.mem.extend_from_slice(&self.program);
+        self.mem.resize(size, 0);
+        for (addr, value) in &self.patches {
+            if *addr < size {
+                self.mem[*addr] = *value;
+            }
+        }
+        self.ip = 0;
+        self.relative_base = 0;
+        self.input_queue.clear();
+    }
+
+    /*
+     * Writes to memory like poke(), but also remembers the change so that reset() applies it again.
+     */
+    pub fn patch(&mut self, addr: usize, value: i64) -> Result<(), OutOfBounds> {
+        self.poke(addr, value)?;
+        self.patches.push((addr, value));
+        Ok(())
+    }
+
+    pub fn patches(&self) -> &[(usize, i64)] {
+        &self.patches
+    }
+
+    pub fn peek(&self, addr: usize) -> Result<i64, OutOfBounds> {
+        self.mem.get(addr).cloned().ok_or(OutOfBounds { addr, size: self.mem.len() })
+    }
+
+    pub fn poke(&mut self, addr: usize, value: i64) -> Result<(), OutOfBounds> {
+        let size = self.mem.len();
+        match self.mem.get_mut(addr) {
+            Some(cell) => {
+                *cell = value;
+                Ok(())
+            }
+            None => Err(OutOfBounds { addr, size }),
+        }
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.mem
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.mem.len()
+    }
+
+    /*
+     * Grows (or shrinks) memory to `size` cells; new cells are zero.
+     */
+    pub fn resize(&mut self, size: usize) {
+        self.mem.resize(size, 0);
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.input_queue.push_back(value);
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        self.input_queue.extend(values);
+    }
+
+    /*
+     * Queues the characters of a string as input for an ASCII program.
+     */
+    pub fn push_ascii(&mut self, s: &str) {
+        self.input_queue.extend(s.bytes().map(|b| b as i64));
+    }
+
+    pub fn pending_input(&self) -> usize {
+        self.input_queue.len()
+    }
+
     /*
      * Returns a Vector containinStatus indicies for the arStatusuments of the current opcode.
      */
@@ -51,35 +210,157 @@ impl IntCode {
         args
     }
 
+    /*
+     * Executes a single instruction, calling `on_write` with every address that gets written to.
+     * Returns the status if the machine stopped, or None if it can keep going.
+     */
+    fn step_with<F: FnMut(usize)>(&mut self, mut on_write: F) -> Option<Status> {
+        let args = self.get_args();
+        let instruction = self.mem[self.ip];
+        let opcode = instruction % 100;
+
+        let mut new_ip = self.ip + args.len() + 1;
+        match opcode {
+            1 => self.mem[args[2]] = self.mem[args[0]] + self.mem[args[1]],
+            2 => self.mem[args[2]] = self.mem[args[0]] * self.mem[args[1]],
+            3 => {
+                match self.input_queue.pop_front() {
+                    Some(input) => self.mem[args[0]] = input,
+                    None => return Some(Status::WaitingInput),
+                }
+            }
+            4 => {
+                self.ip = new_ip;
+                return Some(Status::Output(self.mem[args[0]]));
+            }
+            5 => if self.mem[args[0]] != 0 { new_ip = self.mem[args[1]] as usize },
+            6 => if self.mem[args[0]] == 0 { new_ip = self.mem[args[1]] as usize },
+            7 => self.mem[args[2]] = (self.mem[args[0]] < self.mem[args[1]]) as i64,
+            8 => self.mem[args[2]] = (self.mem[args[0]] == self.mem[args[1]]) as i64,
+            9 => self.relative_base += self.mem[args[0]],
+            99 => return Some(Status::Halt),
+            _ => panic!("Invalid opcode {}", opcode),
+        }
+        match opcode {
+            1 | 2 | 7 | 8 => on_write(args[2]),
+            3 => on_write(args[0]),
+            _ => (),
+        }
+        self.ip = new_ip;
+        None
+    }
+
+    /*
+     * Executes a single instruction.
+     * Returns the status if the machine stopped, or None if it can keep going.
+     */
+    pub fn step(&mut self) -> Option<Status> {
+        self.step_with(|_| ())
+    }
+
     pub fn execute(&mut self) -> Status {
         loop {
-            let args = self.get_args();
-            let instruction = self.mem[self.ip];
-            let opcode = instruction % 100;
-
-            let mut new_ip = self.ip + args.len() + 1;
-            match opcode {
-                1 => self.mem[args[2]] = self.mem[args[0]] + self.mem[args[1]],
-                2 => self.mem[args[2]] = self.mem[args[0]] * self.mem[args[1]],
-                3 => {
-                    match self.input_queue.pop_front() {
-                        Some(input) => self.mem[args[0]] = input,
-                        None => return Status::WaitingInput,
-                    }
-                }
-                4 => {
-                    self.ip = new_ip;
-                    return Status::Output(self.mem[args[0]]);
+            if let Some(status) = self.step() {
+                return status;
+            }
+        }
+    }
+
+    /*
+     * Same as execute(), but records every executed instruction in `coverage`.
+     */
+    pub fn execute_with_coverage(&mut self, coverage: &mut coverage::Coverage) -> Status {
+        loop {
+            if let Some(status) = coverage.step(self) {
+                return status;
+            }
+        }
+    }
+
+    /*
+     * Same as execute(), but gives up once `budget` instructions have been executed.
+     * The budget is decremented in place so that it can be shared between calls.
+     * Returns None if the budget ran out before the machine stopped.
+     */
+    pub fn execute_limited(&mut self, budget: &mut usize) -> Option<Status> {
+        loop {
+            if *budget == 0 {
+                return None;
+            }
+            match self.step() {
+                None => *budget -= 1,
+                Some(Status::Output(n)) => {
+                    *budget -= 1;
+                    return Some(Status::Output(n));
                 }
-                5 => if self.mem[args[0]] != 0 { new_ip = self.mem[args[1]] as usize },
-                6 => if self.mem[args[0]] == 0 { new_ip = self.mem[args[1]] as usize },
-                7 => self.mem[args[2]] = (self.mem[args[0]] < self.mem[args[1]]) as i64,
-                8 => self.mem[args[2]] = (self.mem[args[0]] == self.mem[args[1]]) as i64,
-                9 => self.relative_base += self.mem[args[0]],
-                99 => return Status::Halt,
-                _ => panic!("Invalid opcode {}", opcode),
+                Some(status) => return Some(status),
             }
-            self.ip = new_ip;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step() {
+        let mut machine = IntCode::new(parse_program("1101,2,3,5,99,0\n").unwrap());
+        assert!(machine.step().is_none());
+        assert_eq!(machine.ip, 4);
+        assert_eq!(machine.mem[5], 5);
+        assert!(matches!(machine.step(), Some(Status::Halt)));
+    }
+
+    #[test]
+    fn lifecycle() {
+        // Adds the input to the cell at 9 and outputs it
+        let mut machine = IntCode::new(parse_program("3,10,1,9,10,9,4,9,99,5,0").unwrap());
+        machine.resize(20);
+        assert_eq!(machine.patch(9, 7), Ok(()));
+        assert_eq!(machine.patch(20, 1), Err(OutOfBounds { addr: 20, size: 20 }));
+        machine.push_input(3);
+        assert!(matches!(machine.execute(), Status::Output(10)));
+        assert_eq!(machine.peek(9), Ok(10));
+        assert_eq!(machine.poke(19, -1), Ok(()));
+
+        machine.push_input(1);
+        machine.reset();
+        assert_eq!(machine.pending_input(), 0);
+        assert_eq!((machine.ip(), machine.memory_size()), (0, 20));
+        assert_eq!(machine.peek(9), Ok(7));
+        assert_eq!(machine.peek(19), Ok(0));
+        assert_eq!(machine.patches(), [(9, 7)]);
+        machine.push_ascii("A");
+        assert!(matches!(machine.execute(), Status::Output(72)));
+
+        machine.load(parse_program("104,1,99").unwrap());
+        assert!(machine.patches().is_empty());
+        assert_eq!(machine.memory()[..4], [104, 1, 99, 0]);
+        assert_eq!(machine.peek(25).unwrap_err().to_string(), "address 25 is out of bounds for a memory of 20 cells");
+    }
+
+    #[test]
+    fn execute_limited() {
+        // Counts down from 3, outputting every value, then halts
+        let program = parse_program("104,3,1001,1,-1,1,1005,1,0,99").unwrap();
+        {
+            let mut machine = IntCode::new(program.clone());
+            let mut budget = 100;
+            let mut outputs = Vec::new();
+            while let Some(Status::Output(n)) = machine.execute_limited(&mut budget) {
+                outputs.push(n);
+            }
+            assert_eq!(outputs, [3, 2, 1]);
+            assert_eq!(budget, 100 - 9);
+        }
+        {
+            let mut machine = IntCode::new(program);
+            let mut budget = 4;
+            assert!(matches!(machine.execute_limited(&mut budget), Some(Status::Output(3))));
+            assert!(matches!(machine.execute_limited(&mut budget), Some(Status::Output(2))));
+            assert!(machine.execute_limited(&mut budget).is_none());
+            assert_eq!(budget, 0);
         }
     }
 }
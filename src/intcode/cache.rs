@@ -0,0 +1,196 @@
+/*
+ * Memoizing queries to pure Intcode programs.
+ *
+ * Searches that come back to inputs they've already asked about, like ones that rescan a region
+ * or probe around an edge, can wrap the pool in a cache to answer repeated queries without
+ * running the program again. Day 19's part 2 walk never asks the same question twice, so it
+ * doesn't use one. Only pure programs are cached, and the least recently used answers are
+ * dropped once the cache is full.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use crate::intcode::pool::{Query, QueryError};
+
+type Answer = Result<Vec<i64>, QueryError>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    // Queries passed straight through because the program isn't pure
+    pub uncached: u64,
+}
+
+pub struct Cache<Q> {
+    inner: Q,
+    capacity: Option<usize>,
+    // Answers along with when they were last used
+    entries: HashMap<Vec<i64>, (Answer, u64)>,
+    // Inputs by when they were last used, oldest first
+    order: BTreeMap<u64, Vec<i64>>,
+    clock: u64,
+    stats: Stats,
+}
+
+impl Stats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.hits + self.misses == 0 { 0.0 } else { self.hits as f64 / (self.hits + self.misses) as f64 }
+    }
+}
+
+impl<Q: Query> Cache<Q> {
+    pub fn new(inner: Q) -> Cache<Q> {
+        Cache {
+            inner,
+            capacity: None,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /*
+     * Creates a cache that remembers at most `capacity` answers.
+     */
+    pub fn with_capacity(inner: Q, capacity: usize) -> Cache<Q> {
+        let mut cache = Cache::new(inner);
+        cache.capacity = Some(capacity);
+        cache
+    }
+
+    pub fn query(&mut self, inputs: &[i64]) -> Answer {
+        if !self.inner.is_pure() {
+            self.stats.uncached += 1;
+            return self.inner.query(inputs);
+        }
+
+        self.clock += 1;
+        if let Some((answer, last_used)) = self.entries.get_mut(inputs) {
+            self.stats.hits += 1;
+            let key = self.order.remove(last_used).unwrap();
+            *last_used = self.clock;
+            self.order.insert(self.clock, key);
+            return answer.clone();
+        }
+
+        self.stats.misses += 1;
+        let answer = self.inner.query(inputs);
+        if self.capacity == Some(0) {
+            return answer;
+        }
+        if Some(self.entries.len()) == self.capacity {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+        self.entries.insert(inputs.to_vec(), (answer.clone(), self.clock));
+        self.order.insert(self.clock, inputs.to_vec());
+        answer
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+}
+
+impl<Q: Query> Query for Cache<Q> {
+    fn query(&mut self, inputs: &[i64]) -> Answer {
+        Cache::query(self, inputs)
+    }
+
+    fn is_pure(&self) -> bool {
+        self.inner.is_pure()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+    use crate::intcode::pool::Pool;
+
+    // Adds up its inputs, counting how often it was asked
+    struct Adder {
+        pure: bool,
+        calls: usize,
+    }
+
+    impl Query for Adder {
+        fn query(&mut self, inputs: &[i64]) -> Answer {
+            self.calls += 1;
+            Ok(vec![inputs.iter().sum()])
+        }
+
+        fn is_pure(&self) -> bool {
+            self.pure
+        }
+    }
+
+    #[test]
+    fn eviction() {
+        let mut cache = Cache::with_capacity(Adder { pure: true, calls: 0 }, 2);
+        assert_eq!(cache.query(&[1, 2]), Ok(vec![3]));
+        assert_eq!(cache.query(&[3, 4]), Ok(vec![7]));
+        assert_eq!(cache.query(&[1, 2]), Ok(vec![3]));
+        // Evicts [3, 4], which was used least recently
+        assert_eq!(cache.query(&[5]), Ok(vec![5]));
+        assert_eq!(cache.query(&[1, 2]), Ok(vec![3]));
+        assert_eq!(cache.query(&[3, 4]), Ok(vec![7]));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.inner().calls, 4);
+        assert_eq!(cache.stats(), Stats { hits: 2, misses: 4, evictions: 2, uncached: 0 });
+        assert!((cache.stats().hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.query(&[1, 2]), Ok(vec![3]));
+        assert_eq!(cache.inner().calls, 5);
+    }
+
+    #[test]
+    fn impure() {
+        let mut cache = Cache::new(Adder { pure: false, calls: 0 });
+        cache.query(&[1]).unwrap();
+        cache.query(&[1]).unwrap();
+        assert_eq!(cache.inner().calls, 2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats().uncached, 2);
+    }
+
+    #[test]
+    fn pool() {
+        // Outputs x + y
+        let program = intcode::parse_program("3,13,3,14,1,13,14,13,4,13,99").unwrap();
+        let mut pool = Pool::new(&program, 20);
+        pool.set_pure(true);
+        let mut cache = Cache::new(pool);
+        for _ in 0..3 {
+            for x in 0..4 {
+                assert_eq!(cache.query(&[x, 2]), Ok(vec![x + 2]));
+            }
+        }
+        assert_eq!(cache.stats().misses, 4);
+        assert_eq!(cache.stats().hits, 8);
+    }
+}
@@ -0,0 +1,1150 @@
+/*
+ * Compiler from a small structured language to Intcode.
+ *
+ * A program is a list of global variables and functions, and execution starts at `main()`:
+ *
+ *     var total = 0;
+ *     var squares[10];
+ *
+ *     fn square(x) { return x * x; }
+ *
+ *     fn main() {
+ *         var i = 0;
+ *         while (i < 10) {
+ *             squares[i] = square(i);
+ *             total = total + squares[i];
+ *             i = i + 1;
+ *         }
+ *         output(total);
+ *     }
+ *
+ * All values are integers. There are `+ - * / %`, comparisons, `&& || !`, if/else, while,
+ * (recursive) functions, global arrays and the builtins `input()`, `output(x)` and `halt()`.
+ * Functions that don't return anything return 0. Division truncates towards zero,
+ * and dividing by zero halts the machine.
+ *
+ * Functions use the same calling convention as the puzzle inputs. A call writes the return
+ * address and the arguments to [rb+0], [rb+1], ... and jumps to the function, which then
+ * moves the relative base past its frame (return address, arguments, locals, temporaries).
+ * The return value is passed back in [rb+1].
+ *
+ * Array elements are read and written by patching the address into the next instruction.
+ * The stack starts right after the compiled program, so machines running it
+ * need some memory past the end of the image.
+ */
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::intcode::disasm::Op;
+
+const KEYWORDS: [&str; 6] = ["var", "fn", "if", "else", "while", "return"];
+const BUILTINS: [&str; 3] = ["input", "output", "halt"];
+
+// Two-character symbols have to come first so that they are matched greedily
+const SYMBOLS: [&str; 23] = [
+    "==", "!=", "<=", ">=", "&&", "||",
+    "+", "-", "*", "/", "%", "<", ">", "!", "=",
+    "(", ")", "{", "}", "[", "]", ",", ";",
+];
+
+// Division is implemented in the language itself, and only included in programs that use it
+const PRELUDE: &str = "
+fn __div(a, b) {
+    if (b == 0) { halt(); }
+    var negative = 0;
+    if (a < 0) { a = -a; negative = !negative; }
+    if (b < 0) { b = -b; negative = !negative; }
+    var quotient = 0;
+    while (a >= b) {
+        var d = b;
+        var m = 1;
+        while (a >= d + d) { d = d + d; m = m + m; }
+        a = a - d;
+        quotient = quotient + m;
+    }
+    if (negative) { return -quotient; }
+    return quotient;
+}
+
+fn __mod(a, b) {
+    return a - b * __div(a, b);
+}
+";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, CompileError> {
+    Err(CompileError { line, message })
+}
+
+/*
+ * Lexer
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Ident(s) => write!(f, "`{}`", s),
+            Token::Symbol(s) => write!(f, "`{}`", s),
+            Token::End => write!(f, "end of input"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, CompileError> {
+    let mut tokens = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+            let token;
+            if c.is_ascii_digit() {
+                let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let n = match rest[..end].parse() {
+                    Ok(n) => n,
+                    Err(_) => return error(line_number, format!("number `{}` is too large", &rest[..end])),
+                };
+                token = Token::Number(n);
+                rest = &rest[end..];
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                token = Token::Ident(rest[..end].to_string());
+                rest = &rest[end..];
+            } else if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+                token = Token::Symbol(symbol);
+                rest = &rest[symbol.len()..];
+            } else {
+                return error(line_number, format!("unexpected character `{}`", c));
+            }
+            tokens.push((token, line_number));
+            rest = rest.trim_start();
+        }
+    }
+    let last_line = tokens.last().map(|t| t.1).unwrap_or(1);
+    tokens.push((Token::End, last_line));
+    Ok(tokens)
+}
+
+/*
+ * Parser
+ */
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add, Sub, Mul, Div, Mod,
+    Lt, Le, Gt, Ge, Eq, Ne,
+    And, Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum UnOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Index(String, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum StmtKind {
+    Var(String, Option<Expr>),
+    Assign(String, Expr),
+    AssignIndex(String, Expr, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Stmt {
+    kind: StmtKind,
+    line: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    line: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Global {
+    Scalar(String, i64),
+    Array(String, usize),
+}
+
+#[derive(Default)]
+struct Ast {
+    globals: Vec<(Global, usize)>,
+    functions: Vec<Function>,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+// Binary operators from the loosest to the tightest binding
+const PRECEDENCE: [&[(&str, BinOp)]; 6] = [
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[("<", BinOp::Lt), ("<=", BinOp::Le), (">", BinOp::Gt), (">=", BinOp::Ge)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Mod)],
+];
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Token::Symbol(s) if *s == symbol => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(s) if s == keyword)
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), CompileError> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            error(self.line(), format!("expected `{}`, found {}", symbol, self.peek()))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, CompileError> {
+        let line = self.line();
+        match self.next() {
+            Token::Ident(s) if KEYWORDS.contains(&s.as_str()) => error(line, format!("unexpected keyword `{}`", s)),
+            Token::Ident(s) => Ok(s),
+            token => error(line, format!("expected a name, found {}", token)),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, CompileError> {
+        let line = self.line();
+        let negative = self.eat("-");
+        match self.next() {
+            Token::Number(n) if negative => Ok(-n),
+            Token::Number(n) => Ok(n),
+            token => error(line, format!("expected a number, found {}", token)),
+        }
+    }
+
+    fn program(&mut self, ast: &mut Ast) -> Result<(), CompileError> {
+        while self.peek() != &Token::End {
+            let line = self.line();
+            if self.is_keyword("var") {
+                self.next();
+                let name = self.ident()?;
+                let global = if self.eat("[") {
+                    let size = self.number()?;
+                    self.expect("]")?;
+                    if size <= 0 {
+                        return error(line, format!("array `{}` must have a positive size", name));
+                    }
+                    Global::Array(name, size as usize)
+                } else if self.eat("=") {
+                    Global::Scalar(name, self.number()?)
+                } else {
+                    Global::Scalar(name, 0)
+                };
+                self.expect(";")?;
+                ast.globals.push((global, line));
+            } else if self.is_keyword("fn") {
+                self.next();
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut params = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        params.push(self.ident()?);
+                        if self.eat(")") { break; }
+                        self.expect(",")?;
+                    }
+                }
+                let body = self.block()?;
+                ast.functions.push(Function { name, params, body, line });
+            } else {
+                return error(line, format!("expected `var` or `fn`, found {}", self.peek()));
+            }
+        }
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, CompileError> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            if self.peek() == &Token::End {
+                return error(self.line(), "unclosed block".to_string());
+            }
+            stmts.push(self.statement()?);
+        }
+        Ok(stmts)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, CompileError> {
+        let line = self.line();
+        let kind = if self.is_keyword("var") {
+            self.next();
+            let name = self.ident()?;
+            if self.peek() == &Token::Symbol("[") {
+                return error(line, format!("array `{}` must be global", name));
+            }
+            let init = if self.eat("=") { Some(self.expression()?) } else { None };
+            self.expect(";")?;
+            StmtKind::Var(name, init)
+        } else if self.is_keyword("if") {
+            self.next();
+            self.expect("(")?;
+            let condition = self.expression()?;
+            self.expect(")")?;
+            let then = self.block()?;
+            let otherwise = if self.is_keyword("else") {
+                self.next();
+                if self.is_keyword("if") { vec![self.statement()?] } else { self.block()? }
+            } else {
+                Vec::new()
+            };
+            StmtKind::If(condition, then, otherwise)
+        } else if self.is_keyword("while") {
+            self.next();
+            self.expect("(")?;
+            let condition = self.expression()?;
+            self.expect(")")?;
+            StmtKind::While(condition, self.block()?)
+        } else if self.is_keyword("return") {
+            self.next();
+            let value = if self.eat(";") {
+                None
+            } else {
+                let value = self.expression()?;
+                self.expect(";")?;
+                Some(value)
+            };
+            StmtKind::Return(value)
+        } else {
+            let name = self.ident()?;
+            let kind = if self.eat("(") {
+                StmtKind::Call(name.clone(), self.arguments()?)
+            } else if self.eat("[") {
+                let index = self.expression()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                StmtKind::AssignIndex(name, index, self.expression()?)
+            } else {
+                self.expect("=")?;
+                StmtKind::Assign(name, self.expression()?)
+            };
+            self.expect(";")?;
+            kind
+        };
+        Ok(Stmt { kind, line })
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expr>, CompileError> {
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.expression()?);
+                if self.eat(")") { break; }
+                self.expect(",")?;
+            }
+        }
+        Ok(args)
+    }
+
+    fn expression(&mut self) -> Result<Expr, CompileError> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, CompileError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for (symbol, op) in PRECEDENCE[level] {
+                if self.eat(symbol) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CompileError> {
+        if self.eat("-") {
+            return match self.unary()? {
+                Expr::Number(n) => Ok(Expr::Number(-n)),
+                e => Ok(Expr::Unary(UnOp::Neg, Box::new(e))),
+            };
+        }
+        if self.eat("!") {
+            return Ok(Expr::Unary(UnOp::Not, Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let e = self.expression()?;
+            self.expect(")")?;
+            return Ok(e);
+        }
+        if let Token::Number(n) = self.peek() {
+            let n = *n;
+            self.next();
+            return Ok(Expr::Number(n));
+        }
+
+        let name = self.ident()?;
+        if self.eat("(") {
+            Ok(Expr::Call(name, self.arguments()?))
+        } else if self.eat("[") {
+            let index = self.expression()?;
+            self.expect("]")?;
+            Ok(Expr::Index(name, Box::new(index)))
+        } else {
+            Ok(Expr::Var(name))
+        }
+    }
+}
+
+/*
+ * Code generation
+ */
+
+#[derive(Clone, Copy, Debug)]
+enum Addr {
+    Const(i64),
+    // Address of a label, plus an offset
+    Label(usize, i64),
+    // Address of a global variable; one past the last global is the bottom of the stack
+    Global(usize),
+    // Multiple of the current function's frame size
+    FrameSize(i64),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operand {
+    Imm(Addr),
+    Pos(Addr),
+    Rel(i64),
+    // Cell in the current function's frame, counted from the return address
+    Frame(i64),
+}
+
+enum Item {
+    Label(usize),
+    Instr(Op, Vec<Operand>),
+}
+
+enum Variable {
+    Scalar(usize),
+    Array(usize),
+}
+
+struct Codegen<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    globals: HashMap<&'a str, Variable>,
+    function_labels: HashMap<&'a str, usize>,
+    labels: usize,
+    items: Vec<Item>,
+    scopes: Vec<HashMap<&'a str, i64>>,
+    next_slot: i64,
+    frame_size: i64,
+}
+
+const IMM_ZERO: Operand = Operand::Imm(Addr::Const(0));
+const IMM_ONE: Operand = Operand::Imm(Addr::Const(1));
+
+impl<'a> Codegen<'a> {
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn function_label(&mut self, name: &'a str) -> usize {
+        if let Some(label) = self.function_labels.get(name) {
+            return *label;
+        }
+        let label = self.label();
+        self.function_labels.insert(name, label);
+        label
+    }
+
+    fn emit(&mut self, op: Op, operands: Vec<Operand>) {
+        self.items.push(Item::Instr(op, operands));
+    }
+
+    fn place(&mut self, label: usize) {
+        self.items.push(Item::Label(label));
+    }
+
+    fn jump(&mut self, label: usize) {
+        self.emit(Op::JumpIfTrue, vec![IMM_ONE, Operand::Imm(Addr::Label(label, 0))]);
+    }
+
+    fn copy(&mut self, src: Operand, dest: Operand) {
+        self.emit(Op::Add, vec![src, IMM_ZERO, dest]);
+    }
+
+    fn alloc(&mut self) -> i64 {
+        self.next_slot += 1;
+        self.frame_size = self.frame_size.max(self.next_slot);
+        self.next_slot - 1
+    }
+
+    fn temp(&mut self) -> Operand {
+        Operand::Frame(self.alloc())
+    }
+
+    fn lookup(&self, name: &str, line: usize) -> Result<Operand, CompileError> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(slot) = scope.get(name) {
+                return Ok(Operand::Frame(*slot));
+            }
+        }
+        match self.globals.get(name) {
+            Some(Variable::Scalar(index)) => Ok(Operand::Pos(Addr::Global(*index))),
+            Some(Variable::Array(..)) => error(line, format!("array `{}` must be indexed", name)),
+            None => error(line, format!("undefined variable `{}`", name)),
+        }
+    }
+
+    fn array(&self, name: &str, line: usize) -> Result<usize, CompileError> {
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            return error(line, format!("`{}` is not an array", name));
+        }
+        match self.globals.get(name) {
+            Some(Variable::Array(index)) => Ok(*index),
+            Some(Variable::Scalar(_)) => error(line, format!("`{}` is not an array", name)),
+            None => error(line, format!("undefined array `{}`", name)),
+        }
+    }
+
+    /*
+     * Returns an operand holding the value of an expression,
+     * evaluating it into a temporary unless it is a constant or a variable.
+     */
+    fn operand(&mut self, e: &'a Expr, line: usize) -> Result<Operand, CompileError> {
+        match e {
+            Expr::Number(n) => Ok(Operand::Imm(Addr::Const(*n))),
+            Expr::Var(name) => self.lookup(name, line),
+            _ => {
+                let t = self.temp();
+                self.expr_into(e, t, line)?;
+                Ok(t)
+            }
+        }
+    }
+
+    fn expr_into(&mut self, e: &'a Expr, dest: Operand, line: usize) -> Result<(), CompileError> {
+        match e {
+            Expr::Number(_) | Expr::Var(_) => {
+                let src = self.operand(e, line)?;
+                self.copy(src, dest);
+            }
+            Expr::Index(name, index) => {
+                let base = self.array(name, line)?;
+                let index = self.operand(index, line)?;
+                let load = self.label();
+                self.emit(Op::Add, vec![index, Operand::Imm(Addr::Global(base)), Operand::Pos(Addr::Label(load, 1))]);
+                self.place(load);
+                self.copy(Operand::Pos(Addr::Const(0)), dest);
+            }
+            Expr::Call(name, args) => {
+                if name == "input" && args.is_empty() {
+                    self.emit(Op::Input, vec![dest]);
+                } else {
+                    self.call(name, args, line)?;
+                    self.copy(Operand::Rel(1), dest);
+                }
+            }
+            Expr::Unary(UnOp::Neg, x) => {
+                let x = self.operand(x, line)?;
+                self.emit(Op::Mul, vec![x, Operand::Imm(Addr::Const(-1)), dest]);
+            }
+            Expr::Unary(UnOp::Not, x) => {
+                let x = self.operand(x, line)?;
+                self.emit(Op::Equals, vec![x, IMM_ZERO, dest]);
+            }
+            Expr::Binary(op @ BinOp::And, a, b) | Expr::Binary(op @ BinOp::Or, a, b) => {
+                // Short-circuit; `&&` bails out on the first zero, `||` on the first non-zero
+                let (bail, bail_value) = match op {
+                    BinOp::And => (Op::JumpIfFalse, 0),
+                    _ => (Op::JumpIfTrue, 1),
+                };
+                let short = self.label();
+                let end = self.label();
+                let a = self.operand(a, line)?;
+                self.emit(bail, vec![a, Operand::Imm(Addr::Label(short, 0))]);
+                let b = self.operand(b, line)?;
+                self.emit(bail, vec![b, Operand::Imm(Addr::Label(short, 0))]);
+                self.copy(Operand::Imm(Addr::Const(1 - bail_value)), dest);
+                self.jump(end);
+                self.place(short);
+                self.copy(Operand::Imm(Addr::Const(bail_value)), dest);
+                self.place(end);
+            }
+            Expr::Binary(BinOp::Div, _, _) | Expr::Binary(BinOp::Mod, _, _) => {
+                let (name, a, b) = match e {
+                    Expr::Binary(BinOp::Div, a, b) => ("__div", a, b),
+                    Expr::Binary(_, a, b) => ("__mod", a, b),
+                    _ => unreachable!(),
+                };
+                self.call_function(name, &[a, b], line)?;
+                self.copy(Operand::Rel(1), dest);
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.operand(a, line)?;
+                let b = self.operand(b, line)?;
+                match op {
+                    BinOp::Add => self.emit(Op::Add, vec![a, b, dest]),
+                    BinOp::Mul => self.emit(Op::Mul, vec![a, b, dest]),
+                    BinOp::Sub => {
+                        let t = self.temp();
+                        self.emit(Op::Mul, vec![b, Operand::Imm(Addr::Const(-1)), t]);
+                        self.emit(Op::Add, vec![a, t, dest]);
+                    }
+                    BinOp::Lt => self.emit(Op::LessThan, vec![a, b, dest]),
+                    BinOp::Gt => self.emit(Op::LessThan, vec![b, a, dest]),
+                    BinOp::Eq => self.emit(Op::Equals, vec![a, b, dest]),
+                    BinOp::Le | BinOp::Ge | BinOp::Ne => {
+                        match op {
+                            BinOp::Le => self.emit(Op::LessThan, vec![b, a, dest]),
+                            BinOp::Ge => self.emit(Op::LessThan, vec![a, b, dest]),
+                            _ => self.emit(Op::Equals, vec![a, b, dest]),
+                        }
+                        self.emit(Op::Equals, vec![dest, IMM_ZERO, dest]);
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, name: &'a str, args: &'a [Expr], line: usize) -> Result<(), CompileError> {
+        if BUILTINS.contains(&name) {
+            return error(line, format!("`{}` can't be used as a value", name));
+        }
+        let args: Vec<&'a Expr> = args.iter().collect();
+        self.call_function(name, &args, line)
+    }
+
+    fn call_function(&mut self, name: &'a str, args: &[&'a Expr], line: usize) -> Result<(), CompileError> {
+        let function = match self.functions.get(name) {
+            Some(function) => *function,
+            None => return error(line, format!("undefined function `{}`", name)),
+        };
+        if function.params.len() != args.len() {
+            return error(line, format!("`{}` takes {} argument(s) but {} were given",
+                                       name, function.params.len(), args.len()));
+        }
+
+        // Evaluate everything first, since nested calls use the space past the frame
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.operand(arg, line)?);
+        }
+        for (i, value) in values.into_iter().enumerate() {
+            self.copy(value, Operand::Rel(1 + i as i64));
+        }
+        let ret = self.label();
+        let target = self.function_label(&function.name);
+        self.copy(Operand::Imm(Addr::Label(ret, 0)), Operand::Rel(0));
+        self.jump(target);
+        self.place(ret);
+        Ok(())
+    }
+
+    fn block(&mut self, stmts: &'a [Stmt]) -> Result<(), CompileError> {
+        let mark = self.next_slot;
+        self.scopes.push(HashMap::new());
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &'a Stmt) -> Result<(), CompileError> {
+        let line = stmt.line;
+        let mark = self.next_slot;
+        match &stmt.kind {
+            StmtKind::Var(name, init) => {
+                if self.scopes.last().unwrap().contains_key(name.as_str()) {
+                    return error(line, format!("`{}` is already defined", name));
+                }
+                let slot = self.alloc();
+                match init {
+                    Some(e) => self.expr_into(e, Operand::Frame(slot), line)?,
+                    None => self.copy(IMM_ZERO, Operand::Frame(slot)),
+                }
+                self.scopes.last_mut().unwrap().insert(name, slot);
+                self.next_slot = mark + 1;
+                return Ok(());
+            }
+            StmtKind::Assign(name, e) => {
+                let dest = self.lookup(name, line)?;
+                self.expr_into(e, dest, line)?;
+            }
+            StmtKind::AssignIndex(name, index, e) => {
+                let base = self.array(name, line)?;
+                let index = self.operand(index, line)?;
+                let value = self.operand(e, line)?;
+                let store = self.label();
+                self.emit(Op::Add, vec![index, Operand::Imm(Addr::Global(base)), Operand::Pos(Addr::Label(store, 3))]);
+                self.place(store);
+                self.copy(value, Operand::Pos(Addr::Const(0)));
+            }
+            StmtKind::If(condition, then, otherwise) => {
+                let condition = self.operand(condition, line)?;
+                let skip = self.label();
+                self.emit(Op::JumpIfFalse, vec![condition, Operand::Imm(Addr::Label(skip, 0))]);
+                self.block(then)?;
+                if otherwise.is_empty() {
+                    self.place(skip);
+                } else {
+                    let end = self.label();
+                    self.jump(end);
+                    self.place(skip);
+                    self.block(otherwise)?;
+                    self.place(end);
+                }
+            }
+            StmtKind::While(condition, body) => {
+                let top = self.label();
+                let end = self.label();
+                self.place(top);
+                let condition = self.operand(condition, line)?;
+                self.emit(Op::JumpIfFalse, vec![condition, Operand::Imm(Addr::Label(end, 0))]);
+                self.next_slot = mark;
+                self.block(body)?;
+                self.jump(top);
+                self.place(end);
+            }
+            StmtKind::Return(value) => {
+                match value {
+                    Some(e) => self.expr_into(e, Operand::Frame(1), line)?,
+                    None => self.copy(IMM_ZERO, Operand::Frame(1)),
+                }
+                self.emit_return();
+            }
+            StmtKind::Call(name, args) => match name.as_str() {
+                "output" if args.len() == 1 => {
+                    let value = self.operand(&args[0], line)?;
+                    self.emit(Op::Output, vec![value]);
+                }
+                "input" if args.is_empty() => {
+                    let t = self.temp();
+                    self.emit(Op::Input, vec![t]);
+                }
+                "halt" if args.is_empty() => self.emit(Op::Halt, vec![]),
+                _ if BUILTINS.contains(&name.as_str()) => {
+                    return error(line, format!("wrong number of arguments to `{}`", name));
+                }
+                _ => self.call(name, args, line)?,
+            },
+        }
+        self.next_slot = mark;
+        Ok(())
+    }
+
+    fn emit_return(&mut self) {
+        self.emit(Op::AdjustBase, vec![Operand::Imm(Addr::FrameSize(-1))]);
+        self.emit(Op::JumpIfFalse, vec![IMM_ZERO, Operand::Rel(0)]);
+    }
+
+    /*
+     * Compiles a function, adding its label, prologue and body to the output.
+     */
+    fn function(&mut self, function: &'a Function) -> Result<(), CompileError> {
+        let mut scope = HashMap::new();
+        for (i, param) in function.params.iter().enumerate() {
+            if scope.insert(param.as_str(), 1 + i as i64).is_some() {
+                return error(function.line, format!("duplicate parameter `{}`", param));
+            }
+        }
+        self.scopes = vec![scope];
+        self.next_slot = 1 + function.params.len() as i64;
+        self.frame_size = self.next_slot;
+
+        let body_start = self.items.len();
+        self.block(&function.body)?;
+        self.copy(IMM_ZERO, Operand::Frame(1));
+        self.emit_return();
+
+        // Now that the frame size is known, frame cells can be turned into relative ones
+        let size = self.frame_size;
+        for item in &mut self.items[body_start..] {
+            if let Item::Instr(_, operands) = item {
+                for operand in operands.iter_mut() {
+                    *operand = match *operand {
+                        Operand::Frame(slot) => Operand::Rel(slot - size),
+                        Operand::Imm(Addr::FrameSize(k)) => Operand::Imm(Addr::Const(k * size)),
+                        other => other,
+                    };
+                }
+            }
+        }
+        let label = self.function_label(&function.name);
+        self.items.insert(body_start, Item::Label(label));
+        self.items.insert(body_start + 1, Item::Instr(Op::AdjustBase, vec![Operand::Imm(Addr::Const(size))]));
+        Ok(())
+    }
+}
+
+fn called_functions<'a>(stmts: &'a [Stmt], calls: &mut Vec<&'a str>) {
+    fn expr<'a>(e: &'a Expr, calls: &mut Vec<&'a str>) {
+        match e {
+            Expr::Number(_) | Expr::Var(_) => (),
+            Expr::Index(_, i) => expr(i, calls),
+            Expr::Call(name, args) => {
+                calls.push(name);
+                args.iter().for_each(|a| expr(a, calls));
+            }
+            Expr::Unary(_, x) => expr(x, calls),
+            Expr::Binary(op, a, b) => {
+                match op {
+                    BinOp::Div => calls.push("__div"),
+                    BinOp::Mod => calls.push("__mod"),
+                    _ => (),
+                }
+                expr(a, calls);
+                expr(b, calls);
+            }
+        }
+    }
+
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::Var(_, init) => init.iter().for_each(|e| expr(e, calls)),
+            StmtKind::Assign(_, e) | StmtKind::Return(Some(e)) => expr(e, calls),
+            StmtKind::AssignIndex(_, i, e) => { expr(i, calls); expr(e, calls); }
+            StmtKind::If(c, a, b) => {
+                expr(c, calls);
+                called_functions(a, calls);
+                called_functions(b, calls);
+            }
+            StmtKind::While(c, body) => { expr(c, calls); called_functions(body, calls); }
+            StmtKind::Return(None) => (),
+            StmtKind::Call(name, args) => {
+                calls.push(name);
+                args.iter().for_each(|a| expr(a, calls));
+            }
+        }
+    }
+}
+
+fn parse(source: &str, ast: &mut Ast) -> Result<(), CompileError> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    parser.program(ast)
+}
+
+/*
+ * Compiles a program into an Intcode memory image.
+ */
+pub fn compile(source: &str) -> Result<Vec<i64>, CompileError> {
+    let mut ast = Ast::default();
+    parse(source, &mut ast)?;
+    let user_functions = ast.functions.len();
+    parse(PRELUDE, &mut ast).expect("prelude should parse");
+
+    let mut codegen = Codegen {
+        functions: HashMap::new(),
+        globals: HashMap::new(),
+        function_labels: HashMap::new(),
+        labels: 0,
+        items: Vec::new(),
+        scopes: Vec::new(),
+        next_slot: 0,
+        frame_size: 0,
+    };
+
+    let mut names = HashSet::new();
+    let mut data = Vec::new();
+    for (global, line) in &ast.globals {
+        let (name, variable, cells) = match global {
+            Global::Scalar(name, value) => (name, Variable::Scalar(data.len()), vec![*value]),
+            Global::Array(name, size) => (name, Variable::Array(data.len()), vec![0; *size]),
+        };
+        if !names.insert(name.as_str()) || BUILTINS.contains(&name.as_str()) {
+            return error(*line, format!("`{}` is already defined", name));
+        }
+        codegen.globals.insert(name, variable);
+        data.extend(cells);
+    }
+    for (i, function) in ast.functions.iter().enumerate() {
+        let user_defined = i < user_functions;
+        if !names.insert(function.name.as_str()) || BUILTINS.contains(&function.name.as_str()) {
+            let line = if user_defined { function.line } else { 1 };
+            return error(line, format!("`{}` is already defined", function.name));
+        }
+        codegen.functions.insert(&function.name, function);
+    }
+
+    let main = match codegen.functions.get("main") {
+        Some(main) if !main.params.is_empty() => return error(main.line, "`main` can't take arguments".to_string()),
+        Some(main) => *main,
+        None => return error(1, "no `main` function".to_string()),
+    };
+
+    // Entry point: set up the stack, call main and halt when it returns
+    codegen.emit(Op::AdjustBase, vec![Operand::Imm(Addr::Global(data.len()))]);
+    codegen.call_function("main", &[], main.line)?;
+    codegen.emit(Op::Halt, vec![]);
+
+    // Only compile functions that can actually be called
+    let mut compiled = HashSet::new();
+    let mut pending = vec!["main"];
+    while let Some(name) = pending.pop() {
+        if !compiled.insert(name) {
+            continue;
+        }
+        let function = codegen.functions[name];
+        codegen.function(function)?;
+        let mut calls = Vec::new();
+        called_functions(&function.body, &mut calls);
+        pending.extend(calls.into_iter().filter(|c| !compiled.contains(c) && !BUILTINS.contains(c)));
+    }
+
+    // The others still have to be valid, so compile them too and throw the code away
+    let used = codegen.items.len();
+    for function in &ast.functions[..user_functions] {
+        if !compiled.contains(function.name.as_str()) {
+            codegen.function(function)?;
+        }
+    }
+    codegen.items.truncate(used);
+
+    Ok(link(&codegen.items, &data))
+}
+
+fn link(items: &[Item], data: &[i64]) -> Vec<i64> {
+    let mut labels = HashMap::new();
+    let mut addr = 0;
+    for item in items {
+        match item {
+            Item::Label(label) => { labels.insert(*label, addr); }
+            Item::Instr(_, operands) => addr += 1 + operands.len() as i64,
+        }
+    }
+    let data_start = addr;
+
+    let resolve = |a: Addr| match a {
+        Addr::Const(n) => n,
+        Addr::Label(label, offset) => labels[&label] + offset,
+        Addr::Global(index) => data_start + index as i64,
+        Addr::FrameSize(_) => unreachable!("frame sizes are resolved per function"),
+    };
+
+    let mut image = Vec::new();
+    for item in items {
+        if let Item::Instr(op, operands) = item {
+            let mut instruction = op.code();
+            let mut params = Vec::new();
+            for (i, operand) in operands.iter().enumerate() {
+                let (mode, value) = match *operand {
+                    Operand::Pos(a) => (0, resolve(a)),
+                    Operand::Imm(a) => (1, resolve(a)),
+                    Operand::Rel(offset) => (2, offset),
+                    Operand::Frame(_) => unreachable!("frame cells are resolved per function"),
+                };
+                instruction += mode * 10i64.pow(2 + i as u32);
+                params.push(value);
+            }
+            image.push(instruction);
+            image.extend(params);
+        }
+    }
+    image.extend(data);
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+
+    fn run(source: &str, inputs: &[i64]) -> Vec<i64> {
+        let mut program = compile(source).unwrap();
+        program.resize(program.len() + 10_000, 0);
+        let mut machine = intcode::IntCode::new(program);
+        machine.extend_input(inputs.iter().cloned());
+
+        let mut outputs = Vec::new();
+        loop {
+            match machine.execute() {
+                intcode::Status::Output(n) => outputs.push(n),
+                intcode::Status::Halt => return outputs,
+                intcode::Status::WaitingInput => panic!("Ran out of inputs"),
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let source = "
+            var g = -3;
+            fn main() {
+                output(1 + 2 * 3 - 4);
+                output((1 + 2) * -(3 - 4));
+                output(g * g - g);
+                output(17 / 5 * 5 + 17 % 5);
+                output(-7 / 2);
+                output(-7 % 2);
+                output(7 / -2);
+                output(100000000000 / 3);
+            }";
+        assert_eq!(run(source, &[]), [3, 3, 12, 17, -3, -1, -3, 33333333333]);
+    }
+
+    #[test]
+    fn comparisons() {
+        let source = "
+            fn loud(x) { output(x); return x; }
+            fn main() {
+                var a = input();
+                var b = input();
+                output(a < b); output(a <= b); output(a > b); output(a >= b);
+                output(a == b); output(a != b); output(!a);
+                // Short-circuiting only evaluates the left side
+                output(loud(0) && loud(10));
+                output(loud(20) || loud(30));
+                output(loud(40) && loud(0) || loud(50));
+            }";
+        assert_eq!(run(source, &[3, 5]), [1, 1, 0, 0, 0, 1, 0, 0, 0, 20, 1, 40, 0, 50, 1]);
+        assert_eq!(run(source, &[5, 5]), [0, 1, 0, 1, 1, 0, 0, 0, 0, 20, 1, 40, 0, 50, 1]);
+        assert_eq!(run(source, &[0, -1])[..7], [0, 0, 1, 1, 0, 1, 1]);
+    }
+
+    #[test]
+    fn recursion() {
+        let source = "
+            fn fib(n) {
+                if (n < 2) { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+            fn ackermann(m, n) {
+                if (m == 0) { return n + 1; }
+                else if (n == 0) { return ackermann(m - 1, 1); }
+                return ackermann(m - 1, ackermann(m, n - 1));
+            }
+            fn main() {
+                var n = input();
+                var i = 0;
+                while (i < n) {
+                    output(fib(i));
+                    i = i + 1;
+                }
+                output(ackermann(2, 3));
+            }";
+        assert_eq!(run(source, &[10]), [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 9]);
+    }
+
+    #[test]
+    fn arrays() {
+        let source = "
+            var composite[100];
+            var primes[30];
+            var count;
+
+            fn sieve(n) {
+                var i = 2;
+                while (i < n) {
+                    if (!composite[i]) {
+                        primes[count] = i;
+                        count = count + 1;
+                        var j = i * i;
+                        while (j < n) { composite[j] = 1; j = j + i; }
+                    }
+                    i = i + 1;
+                }
+            }
+
+            fn main() {
+                sieve(input());
+                var i = 0;
+                while (i < count) { output(primes[i]); i = i + 1; }
+            }";
+        assert_eq!(run(source, &[50]), [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source| compile(source).unwrap_err();
+        assert_eq!(error("fn main() {\n  x = 1;\n}"), CompileError { line: 2, message: "undefined variable `x`".to_string() });
+        assert_eq!(error("fn f(a) {}\nfn main() { f(); }").line, 2);
+        assert_eq!(error("var a[3];\nfn main() { a = 1; }").message, "array `a` must be indexed");
+        assert_eq!(error("fn main() { output(1) }").message, "expected `;`, found `}`");
+        assert_eq!(error("fn main() { var x = output(1); }").message, "`output` can't be used as a value");
+        assert_eq!(error("fn f() {}").message, "no `main` function");
+        assert_eq!(error("fn main() { $ }").message, "unexpected character `$`");
+        assert_eq!(error("fn unused() { return nothere; }\nfn main() { output(1); }"),
+                   CompileError { line: 1, message: "undefined variable `nothere`".to_string() });
+        assert_eq!(error("fn unused() { zz(1, 2, 3); }\nfn main() {}").message, "undefined function `zz`");
+        assert_eq!(error("fn main() {\n  var x;\n  var x = 2;\n}"),
+                   CompileError { line: 3, message: "`x` is already defined".to_string() });
+        assert_eq!(error("fn main() { var a[3]; }").message, "array `a` must be global");
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Coverage collection for Intcode programs.
+ *
+ * Counts how often every instruction is executed and which way the conditional jumps go,
+ * accumulated over any number of runs, and renders it on top of a disassembly.
+ */
+
+use std::collections::BTreeMap;
+
+use crate::intcode::IntCode;
+use crate::intcode::Status;
+use crate::intcode::disasm;
+use crate::intcode::disasm::{Instruction, Line, Mode, Op};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    // Number of times the instruction at each address was executed
+    pub hits: BTreeMap<usize, u64>,
+    // Directions taken by the conditional jumps, by address
+    pub branches: BTreeMap<usize, Branch>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /*
+     * Executes a single instruction on the machine, recording it.
+     * Instructions waiting for input aren't counted until they actually read something.
+     */
+    pub fn step(&mut self, machine: &mut IntCode) -> Option<Status> {
+        let ip = machine.ip();
+        let jump = match Instruction::decode(machine.memory(), ip) {
+            Some(instruction) if instruction.op == Op::JumpIfTrue || instruction.op == Op::JumpIfFalse => {
+                let param = instruction.params[0];
+                let condition = match param.mode {
+                    Mode::Immediate => param.value,
+                    Mode::Position => machine.memory()[param.value as usize],
+                    Mode::Relative => machine.memory()[(machine.relative_base() + param.value) as usize],
+                };
+                Some((condition != 0) == (instruction.op == Op::JumpIfTrue))
+            }
+            _ => None,
+        };
+
+        let status = machine.step();
+        if let Some(Status::WaitingInput) = status {
+            return status;
+        }
+        *self.hits.entry(ip).or_insert(0) += 1;
+        if let Some(taken) = jump {
+            let branch = self.branches.entry(ip).or_default();
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+        status
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (addr, hits) in &other.hits {
+            *self.hits.entry(*addr).or_insert(0) += hits;
+        }
+        for (addr, branch) in &other.branches {
+            let b = self.branches.entry(*addr).or_default();
+            b.taken += branch.taken;
+            b.not_taken += branch.not_taken;
+        }
+    }
+
+    /*
+     * Renders a disassembly of `program` with the number of times each instruction was executed,
+     * followed by how many of the instructions and branch directions were covered.
+     */
+    pub fn render(&self, program: &[i64]) -> String {
+        let mut s = String::new();
+        let (mut instructions, mut covered) = (0, 0);
+        let (mut directions, mut covered_directions) = (0, 0);
+        for line in disasm::disassemble(program) {
+            let instruction = match &line {
+                Line::Code(instruction) => instruction,
+                Line::Data(..) => {
+                    s += &format!("{:>8}  {}\n", "", line);
+                    continue;
+                }
+            };
+            instructions += 1;
+            let hits = match self.hits.get(&instruction.addr) {
+                Some(hits) => {
+                    covered += 1;
+                    hits.to_string()
+                }
+                None => "-".to_string(),
+            };
+            s += &format!("{:>8}  {}", hits, line);
+            if instruction.op == Op::JumpIfTrue || instruction.op == Op::JumpIfFalse {
+                let branch = self.branches.get(&instruction.addr).cloned().unwrap_or_default();
+                if instruction.params[0].mode == Mode::Immediate {
+                    // Unconditional jumps can only go one way
+                    directions += 1;
+                    covered_directions += (branch.taken + branch.not_taken > 0) as usize;
+                } else {
+                    directions += 2;
+                    covered_directions += (branch.taken > 0) as usize + (branch.not_taken > 0) as usize;
+                }
+                s += &format!("  ; taken {}, not taken {}", branch.taken, branch.not_taken);
+            }
+            s += "\n";
+        }
+        let percent = |a: usize, b: usize| if b == 0 { 100.0 } else { 100.0 * a as f64 / b as f64 };
+        s += &format!("{}/{} instructions ({:.1}%), {}/{} branch directions ({:.1}%) covered\n",
+                      covered, instructions, percent(covered, instructions),
+                      covered_directions, directions, percent(covered_directions, directions));
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+
+    // Outputs 999, 1000 or 1001 for inputs below, equal to or above 8
+    const COMPARE: &str = "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99";
+
+    fn run(program: &[i64], input: i64) -> Coverage {
+        let mut machine = IntCode::new(program.to_vec());
+        machine.push_input(input);
+        let mut coverage = Coverage::new();
+        while let None | Some(Status::Output(_)) = coverage.step(&mut machine) {}
+        coverage
+    }
+
+    #[test]
+    fn branches() {
+        let program = intcode::parse_program(COMPARE).unwrap();
+        let below = run(&program, 2);
+        assert_eq!(below.hits.get(&0), Some(&1));
+        assert_eq!(below.hits.get(&36), None);
+        assert_eq!(below.branches[&6], Branch { taken: 0, not_taken: 1 });
+        assert_eq!(below.branches[&13], Branch { taken: 1, not_taken: 0 });
+
+        let mut all = below.clone();
+        all.merge(&run(&program, 8));
+        all.merge(&run(&program, 800));
+        assert_eq!(all.hits[&0], 3);
+        assert_eq!(all.branches[&6], Branch { taken: 1, not_taken: 2 });
+
+        let report = below.render(&program);
+        assert!(report.contains("       1      6: JNZ [20], 22  ; taken 0, not taken 1\n"));
+        assert!(report.contains("       -     36: ADD 1000, 1, [20]\n"));
+        assert!(report.ends_with("8/15 instructions (53.3%), 3/8 branch directions (37.5%) covered\n"));
+        assert!(all.render(&program).ends_with("15/15 instructions (100.0%), 8/8 branch directions (100.0%) covered\n"));
+    }
+
+    #[test]
+    fn execute_with_coverage() {
+        // Counts down from 3
+        let program = intcode::parse_program("104,3,1001,1,-1,1,1005,1,0,99").unwrap();
+        let mut machine = IntCode::new(program);
+        let mut coverage = Coverage::new();
+        while let Status::Output(_) = machine.execute_with_coverage(&mut coverage) {}
+        assert_eq!(coverage.hits.values().collect::<Vec<_>>(), [&3, &3, &3, &1]);
+        assert_eq!(coverage.branches[&6], Branch { taken: 2, not_taken: 1 });
+    }
+}
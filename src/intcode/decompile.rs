@@ -0,0 +1,1012 @@
+/*
+ * Decompiler from Intcode to C-like pseudo-code.
+ *
+ * Functions are found through the calling convention shared by the puzzle inputs and
+ * intcode::compiler. The caller writes the return address to [rb+0] and the arguments to
+ * [rb+1], [rb+2], ..., then jumps to the function. The function moves the relative base past
+ * its frame and, to return, moves it back and jumps to [rb+0], leaving its result in [rb+1].
+ * Following the relative base through each function turns its frame into `argN` and `localN`,
+ * while cells used by address become variables, declared `static` if only one function uses them.
+ *
+ * Within a function, loops are recovered from backward jumps and if/else from forward
+ * conditional jumps, with a comparison that only feeds a jump folded into its condition.
+ * Whatever doesn't fit those patterns becomes a goto. Cells that lie inside the code,
+ * like the ones self-modifying programs patch, stay `mem[addr]`.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+use crate::intcode::disasm;
+use crate::intcode::disasm::{Instruction, Line, Mode, Op, Param};
+
+// How far before a jump the return address can be written for it to count as a call
+const CALL_WINDOW: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Var {
+    Arg(i64),
+    Local(i64),
+    // Frame slots outside of the arguments and locals, like the return address
+    Frame(i64),
+    Global(usize),
+    Mem(usize),
+    // A relative address where the relative base isn't known
+    Rb(i64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Var(Var),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Call(usize, Vec<Expr>),
+    Input,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Assign(Var, Expr),
+    Output(Expr),
+    Call(Expr),
+    AdjustBase(Expr),
+    Halt,
+    Return(Option<Expr>),
+    Goto(usize),
+    GotoIndirect(Expr),
+    Break,
+    Continue,
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    DoWhile(Vec<Stmt>, Expr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Stmt {
+    // Lowest address of the instructions the statement was made from
+    start: usize,
+    kind: Kind,
+}
+
+// A cell as seen from inside a function
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Cell {
+    Slot(i64),
+    Global(usize),
+}
+
+#[derive(Clone, Copy)]
+struct Loop {
+    header: usize,
+    exit: usize,
+    // Whether jumping to the header means `continue`, which isn't so for do-while loops
+    continues: bool,
+}
+
+struct Function {
+    entry: usize,
+    // Reachable instructions, by address
+    code: Vec<Instruction>,
+    index: HashMap<usize, usize>,
+    // Addresses the function runs into that aren't valid instructions
+    invalid: BTreeSet<usize>,
+    // Offset of the relative base from the frame, before each instruction
+    offsets: HashMap<usize, Option<i64>>,
+    // Addresses jumped to from within the function
+    targets: HashSet<usize>,
+}
+
+struct CallSite {
+    callee: usize,
+    // Instructions writing the arguments, by slot
+    args: BTreeMap<i64, usize>,
+    // The instruction using the result, if it comes right after the call
+    result_use: Option<usize>,
+}
+
+struct Decompiler<'a> {
+    mem: &'a [i64],
+    // Calls by the address of their jump: the function and the return address
+    calls: HashMap<usize, (usize, usize)>,
+    functions: BTreeMap<usize, Function>,
+    num_args: HashMap<usize, usize>,
+    // Cells of reachable instructions
+    code_cells: HashSet<usize>,
+    // Number of instructions reading each cell by address, over the whole program
+    global_readers: HashMap<usize, usize>,
+}
+
+// State for decompiling one function
+struct Body<'a> {
+    program: &'a Decompiler<'a>,
+    function: &'a Function,
+    // Instructions that became part of another statement
+    folded: HashSet<usize>,
+    calls: HashMap<usize, CallSite>,
+    // Calls whose result is used by the instruction at this index
+    results: HashMap<usize, usize>,
+    // Return jumps, with the instruction writing the result if there is one
+    returns: HashMap<usize, Option<usize>>,
+    // Conditional jumps with the comparison folded into them
+    conditions: HashMap<usize, usize>,
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Var::Arg(n) => write!(f, "arg{}", n),
+            Var::Local(n) => write!(f, "local{}", n),
+            Var::Frame(n) => write!(f, "frame[{}]", n),
+            Var::Global(addr) => write!(f, "v{}", addr),
+            Var::Mem(addr) => write!(f, "mem[{}]", addr),
+            Var::Rb(k) if *k < 0 => write!(f, "mem[rb{}]", k),
+            Var::Rb(k) => write!(f, "mem[rb+{}]", k),
+        }
+    }
+}
+
+fn name(entry: usize) -> String {
+    if entry == 0 { "main".to_string() } else { format!("f{}", entry) }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Nested operators are always parenthesized, so there are no precedence rules to know
+        let operand = |e: &Expr| match e {
+            Expr::Binary(..) => format!("({})", e),
+            _ => e.to_string(),
+        };
+        match self {
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::Input => write!(f, "input()"),
+            Expr::Var(var) => write!(f, "{}", var),
+            Expr::Binary(op, a, b) => write!(f, "{} {} {}", operand(a), op, operand(b)),
+            Expr::Not(e) => write!(f, "!{}", operand(e)),
+            Expr::Neg(e) => write!(f, "-{}", operand(e)),
+            Expr::Call(entry, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", name(*entry), args.join(", "))
+            }
+        }
+    }
+}
+
+fn binary(op: &'static str, a: Expr, b: Expr) -> Expr {
+    match (op, &a, &b) {
+        ("+", Expr::Num(x), Expr::Num(y)) if x.checked_add(*y).is_some() => Expr::Num(x + y),
+        ("*", Expr::Num(x), Expr::Num(y)) if x.checked_mul(*y).is_some() => Expr::Num(x * y),
+        ("+", _, Expr::Num(0)) | ("*", _, Expr::Num(1)) => a,
+        ("+", Expr::Num(0), _) | ("*", Expr::Num(1), _) => b,
+        ("+", _, Expr::Num(n)) if *n < 0 && *n != i64::MIN => Expr::Binary("-", Box::new(a), Box::new(Expr::Num(-n))),
+        ("*", _, Expr::Num(-1)) => Expr::Neg(Box::new(a)),
+        ("*", Expr::Num(-1), _) => Expr::Neg(Box::new(b)),
+        _ => Expr::Binary(op, Box::new(a), Box::new(b)),
+    }
+}
+
+fn negate(e: Expr) -> Expr {
+    match e {
+        Expr::Binary(op, a, b) if ["<", ">=", "==", "!="].contains(&op) => {
+            let op = match op {
+                "<" => ">=",
+                ">=" => "<",
+                "==" => "!=",
+                _ => "==",
+            };
+            Expr::Binary(op, a, b)
+        }
+        Expr::Not(e) => *e,
+        Expr::Num(n) => Expr::Num((n == 0) as i64),
+        e => Expr::Not(Box::new(e)),
+    }
+}
+
+fn is_jump(instruction: &Instruction) -> bool {
+    instruction.op == Op::JumpIfTrue || instruction.op == Op::JumpIfFalse
+}
+
+/*
+ * Whether a jump's condition is a constant, and if so, whether it's taken.
+ */
+fn constant_condition(instruction: &Instruction) -> Option<bool> {
+    let condition = instruction.params[0];
+    if condition.mode != Mode::Immediate {
+        return None;
+    }
+    Some((condition.value != 0) == (instruction.op == Op::JumpIfTrue))
+}
+
+fn immediate_target(instruction: &Instruction) -> Option<usize> {
+    match instruction.params[1] {
+        Param { mode: Mode::Immediate, value } if value >= 0 => Some(value as usize),
+        _ => None,
+    }
+}
+
+fn write_param(instruction: &Instruction) -> Option<Param> {
+    instruction.op.write_param().map(|i| instruction.params[i])
+}
+
+fn read_params(instruction: &Instruction) -> impl Iterator<Item = &Param> {
+    let write = instruction.op.write_param();
+    instruction.params.iter().enumerate().filter(move |(i, _)| Some(*i) != write).map(|(_, param)| param)
+}
+
+/*
+ * Finds the jumps that are calls: unconditional jumps to a fixed address shortly after an
+ * instruction stores the address following the jump in [rb+0].
+ */
+fn find_calls(mem: &[i64]) -> HashMap<usize, (usize, usize)> {
+    let mut calls = HashMap::new();
+    for line in disasm::disassemble(mem) {
+        let instruction = match line {
+            Line::Code(instruction) => instruction,
+            Line::Data(..) => continue,
+        };
+        let return_addr = match (instruction.op, write_param(&instruction)) {
+            (Op::Add, Some(Param { mode: Mode::Relative, value: 0 })) | (Op::Mul, Some(Param { mode: Mode::Relative, value: 0 })) => {
+                let (a, b) = (instruction.params[0], instruction.params[1]);
+                if a.mode != Mode::Immediate || b.mode != Mode::Immediate {
+                    continue;
+                }
+                let value = if instruction.op == Op::Add { a.value.checked_add(b.value) } else { a.value.checked_mul(b.value) };
+                match value {
+                    Some(value) if value >= 3 => value as usize,
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        let jump_addr = return_addr - 3;
+        if jump_addr <= instruction.addr || jump_addr - instruction.addr > CALL_WINDOW {
+            continue;
+        }
+        if let Some(jump) = Instruction::decode(mem, jump_addr) {
+            if is_jump(&jump) && constant_condition(&jump) == Some(true) {
+                if let Some(target) = immediate_target(&jump) {
+                    calls.insert(jump_addr, (target, return_addr));
+                }
+            }
+        }
+    }
+    calls
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(mem: &'a [i64]) -> Decompiler<'a> {
+        let mut program = Decompiler {
+            mem,
+            calls: find_calls(mem),
+            functions: BTreeMap::new(),
+            num_args: HashMap::new(),
+            code_cells: HashSet::new(),
+            global_readers: HashMap::new(),
+        };
+        let mut entries = vec![0];
+        while let Some(entry) = entries.pop() {
+            if entry >= mem.len() || program.functions.contains_key(&entry) {
+                continue;
+            }
+            let function = program.explore(entry);
+            for instruction in &function.code {
+                if let Some((callee, _)) = program.calls.get(&instruction.addr) {
+                    entries.push(*callee);
+                }
+            }
+            program.functions.insert(entry, function);
+        }
+
+        for function in program.functions.values() {
+            for instruction in &function.code {
+                program.code_cells.extend(instruction.addr..instruction.next());
+                for param in read_params(instruction) {
+                    if param.mode == Mode::Position {
+                        *program.global_readers.entry(param.value as usize).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        program
+    }
+
+    fn successors(&self, instruction: &Instruction) -> Vec<usize> {
+        match instruction.op {
+            Op::Halt => Vec::new(),
+            Op::JumpIfTrue | Op::JumpIfFalse => {
+                let mut successors = Vec::new();
+                let condition = constant_condition(instruction);
+                if condition != Some(true) {
+                    successors.push(instruction.next());
+                }
+                if condition != Some(false) {
+                    match self.calls.get(&instruction.addr) {
+                        Some((_, return_addr)) => successors.push(*return_addr),
+                        None => successors.extend(immediate_target(instruction)),
+                    }
+                }
+                successors
+            }
+            _ => vec![instruction.next()],
+        }
+    }
+
+    /*
+     * Follows the code of the function starting at `entry`, without going into the functions it calls.
+     */
+    fn explore(&self, entry: usize) -> Function {
+        let mut found = BTreeMap::new();
+        let mut invalid = BTreeSet::new();
+        let mut todo = vec![entry];
+        while let Some(addr) = todo.pop() {
+            if found.contains_key(&addr) || invalid.contains(&addr) {
+                continue;
+            }
+            match Instruction::decode(self.mem, addr) {
+                Some(instruction) => {
+                    todo.extend(self.successors(&instruction));
+                    found.insert(addr, instruction);
+                }
+                None => {
+                    invalid.insert(addr);
+                }
+            }
+        }
+
+        // The first function usually starts by moving the relative base to where the stack goes
+        let mut start = 0;
+        if let Some(instruction) = found.get(&entry) {
+            if entry == 0 && instruction.op == Op::AdjustBase && instruction.params[0].mode == Mode::Immediate {
+                start = -instruction.params[0].value;
+            }
+        }
+        let mut offsets: HashMap<usize, Option<i64>> = HashMap::new();
+        let mut todo = vec![(entry, Some(start))];
+        while let Some((addr, offset)) = todo.pop() {
+            let instruction = match found.get(&addr) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let offset = match offsets.get(&addr) {
+                None => offset,
+                Some(previous) if *previous == offset || previous.is_none() => continue,
+                Some(_) => None,
+            };
+            offsets.insert(addr, offset);
+            let after = match instruction.op {
+                Op::AdjustBase if instruction.params[0].mode == Mode::Immediate => offset.map(|o| o + instruction.params[0].value),
+                Op::AdjustBase => None,
+                _ => offset,
+            };
+            for successor in self.successors(instruction) {
+                todo.push((successor, after));
+            }
+        }
+
+        let code: Vec<Instruction> = found.into_values().collect();
+        let index = code.iter().enumerate().map(|(i, instruction)| (instruction.addr, i)).collect();
+        let targets = code.iter()
+            .filter(|instruction| is_jump(instruction) && !self.calls.contains_key(&instruction.addr))
+            .filter_map(immediate_target)
+            .collect();
+        Function { entry, code, index, invalid, offsets, targets }
+    }
+}
+
+impl Function {
+    fn offset(&self, i: usize) -> Option<i64> {
+        self.offsets.get(&self.code[i].addr).cloned().flatten()
+    }
+
+    fn cell(&self, i: usize, param: Param) -> Option<Cell> {
+        match param.mode {
+            Mode::Position if param.value >= 0 => Some(Cell::Global(param.value as usize)),
+            Mode::Relative => self.offset(i).map(|offset| Cell::Slot(offset + param.value)),
+            _ => None,
+        }
+    }
+
+    // Whether the instruction at `i` directly follows the one before it, with nothing else jumping in between
+    fn follows(&self, i: usize) -> bool {
+        i > 0 && self.code[i - 1].next() == self.code[i].addr && !self.targets.contains(&self.code[i].addr)
+    }
+
+    fn is_return(&self, i: usize) -> bool {
+        let instruction = &self.code[i];
+        is_jump(instruction) && constant_condition(instruction) == Some(true)
+            && instruction.params[1].mode == Mode::Relative
+            && self.cell(i, instruction.params[1]) == Some(Cell::Slot(0))
+    }
+}
+
+impl<'a> Body<'a> {
+    fn new(program: &'a Decompiler<'a>, function: &'a Function) -> Body<'a> {
+        let mut body = Body {
+            program,
+            function,
+            folded: HashSet::new(),
+            calls: HashMap::new(),
+            results: HashMap::new(),
+            returns: HashMap::new(),
+            conditions: HashMap::new(),
+        };
+        for i in 0..function.code.len() {
+            if program.calls.contains_key(&function.code[i].addr) {
+                body.find_call(i);
+            } else if function.is_return(i) {
+                body.find_return(i);
+            }
+        }
+        body.find_conditions();
+        body
+    }
+
+    /*
+     * Collects the writes of the arguments before a call, and the use of its result after it.
+     */
+    fn find_call(&mut self, c: usize) {
+        let function = self.function;
+        let (callee, _) = self.program.calls[&function.code[c].addr];
+        let offset = function.offset(c);
+        let mut args = BTreeMap::new();
+        let mut i = c;
+        while function.follows(i) || (i == c && i > 0 && function.code[i - 1].next() == function.code[i].addr) {
+            let instruction = &function.code[i - 1];
+            let slot = match (write_param(instruction), offset) {
+                (Some(param), Some(offset)) if param.mode == Mode::Relative && function.offset(i - 1) == Some(offset) => param.value,
+                _ => break,
+            };
+            if slot < 0 || instruction.op == Op::Input {
+                break;
+            }
+            i -= 1;
+            args.entry(slot).or_insert(i);
+        }
+        for i in args.values() {
+            self.folded.insert(*i);
+        }
+
+        // The result is in [rb+1]; use it directly if the next instruction reads it once and nothing else
+        let mut result_use = None;
+        let next = c + 1;
+        if next < function.code.len() && function.follows(next) && function.offset(next) == offset {
+            let reads = read_params(&function.code[next])
+                .filter(|param| param.mode == Mode::Relative && param.value == 1)
+                .count();
+            let overwrites = write_param(&function.code[next]).is_some_and(|param| param.mode == Mode::Relative && param.value == 1);
+            if reads == 1 && !overwrites {
+                result_use = Some(next);
+                self.results.insert(next, c);
+            }
+        }
+        args.remove(&0);
+        self.calls.insert(c, CallSite { callee, args, result_use });
+    }
+
+    /*
+     * Folds the write of the result into a return, skipping over moves of the relative base.
+     */
+    fn find_return(&mut self, r: usize) {
+        let function = self.function;
+        let mut i = r;
+        let mut result = None;
+        while function.follows(i) {
+            let instruction = &function.code[i - 1];
+            if instruction.op == Op::AdjustBase && instruction.params[0].mode == Mode::Immediate {
+                i -= 1;
+                continue;
+            }
+            if let Some(param) = write_param(instruction) {
+                if function.cell(i - 1, param) == Some(Cell::Slot(1)) {
+                    result = Some(i - 1);
+                    self.folded.insert(i - 1);
+                }
+            }
+            break;
+        }
+        self.returns.insert(r, result);
+    }
+
+    /*
+     * Finds comparisons whose result is only ever read by the jump right after them.
+     */
+    fn find_conditions(&mut self) {
+        let function = self.function;
+        let mut candidates: HashMap<Cell, Vec<(usize, usize)>> = HashMap::new();
+        let mut readers: HashMap<Cell, usize> = HashMap::new();
+        for (i, instruction) in function.code.iter().enumerate() {
+            for param in read_params(instruction) {
+                if let Some(cell) = function.cell(i, *param) {
+                    *readers.entry(cell).or_insert(0) += 1;
+                }
+            }
+            if !is_jump(instruction) || constant_condition(instruction).is_some() || !function.follows(i) {
+                continue;
+            }
+            let comparison = &function.code[i - 1];
+            if comparison.op != Op::LessThan && comparison.op != Op::Equals {
+                continue;
+            }
+            let written = function.cell(i - 1, comparison.params[2]);
+            if let Some(cell) = written.filter(|cell| Some(*cell) == function.cell(i, instruction.params[0])) {
+                candidates.entry(cell).or_default().push((i - 1, i));
+            }
+        }
+        for (cell, pairs) in candidates {
+            let only_read_here = match cell {
+                Cell::Slot(_) => readers[&cell] == pairs.len(),
+                Cell::Global(addr) => self.program.global_readers.get(&addr) == Some(&pairs.len()),
+            };
+            if only_read_here {
+                for (comparison, jump) in pairs {
+                    self.folded.insert(comparison);
+                    self.conditions.insert(jump, comparison);
+                }
+            }
+        }
+    }
+
+    fn var(&self, i: usize, param: Param) -> Var {
+        match param.mode {
+            Mode::Position if param.value >= 0 && self.program.code_cells.contains(&(param.value as usize)) => Var::Mem(param.value as usize),
+            Mode::Position if param.value >= 0 => Var::Global(param.value as usize),
+            Mode::Relative => match self.function.offset(i) {
+                Some(offset) => {
+                    let slot = offset + param.value;
+                    let num_args = self.program.num_args.get(&self.function.entry).cloned().unwrap_or(0) as i64;
+                    if self.function.entry == 0 && slot >= 0 {
+                        Var::Local(slot)
+                    } else if slot >= 1 && slot <= num_args {
+                        Var::Arg(slot)
+                    } else if slot > num_args {
+                        Var::Local(slot)
+                    } else {
+                        Var::Frame(slot)
+                    }
+                }
+                None => Var::Rb(param.value),
+            },
+            // Negative addresses crash the machine
+            _ => Var::Rb(param.value),
+        }
+    }
+
+    fn operand(&self, i: usize, param: Param) -> Expr {
+        if param.mode == Mode::Immediate {
+            return Expr::Num(param.value);
+        }
+        if let Some(c) = self.results.get(&i) {
+            if param.mode == Mode::Relative && param.value == 1 {
+                return self.call_expr(*c);
+            }
+        }
+        Expr::Var(self.var(i, param))
+    }
+
+    fn call_expr(&self, c: usize) -> Expr {
+        let call = &self.calls[&c];
+        let count = call.args.keys().last().cloned().unwrap_or(0);
+        let args = (1..=count).map(|slot| match call.args.get(&slot) {
+            Some(i) => self.value(*i),
+            None => Expr::Var(Var::Frame(slot)),
+        }).collect();
+        Expr::Call(call.callee, args)
+    }
+
+    // What an instruction writes
+    fn value(&self, i: usize) -> Expr {
+        let instruction = &self.function.code[i];
+        let operand = |n: usize| self.operand(i, instruction.params[n]);
+        match instruction.op {
+            Op::Add => binary("+", operand(0), operand(1)),
+            Op::Mul => binary("*", operand(0), operand(1)),
+            Op::LessThan => Expr::Binary("<", Box::new(operand(0)), Box::new(operand(1))),
+            Op::Equals => Expr::Binary("==", Box::new(operand(0)), Box::new(operand(1))),
+            Op::Input => Expr::Input,
+            _ => unreachable!(),
+        }
+    }
+
+    // The condition under which a conditional jump is taken
+    fn taken(&self, i: usize) -> Expr {
+        let instruction = &self.function.code[i];
+        let condition = match self.conditions.get(&i) {
+            Some(comparison) => self.value(*comparison),
+            None => self.operand(i, instruction.params[0]),
+        };
+        if instruction.op == Op::JumpIfTrue { condition } else { negate(condition) }
+    }
+
+    // Start address of the statement made from the instruction at `i`, including what was folded into it
+    fn start(&self, i: usize) -> usize {
+        let mut start = self.function.code[i].addr;
+        if let Some(comparison) = self.conditions.get(&i) {
+            start = start.min(self.function.code[*comparison].addr);
+        }
+        if let Some(call) = self.calls.get(&i) {
+            if let Some(first) = call.args.values().min() {
+                start = start.min(self.function.code[*first].addr);
+            }
+        }
+        if let Some(c) = self.results.get(&i) {
+            start = start.min(self.start(*c));
+        }
+        if let Some(Some(result)) = self.returns.get(&i) {
+            start = start.min(self.function.code[*result].addr);
+        }
+        start
+    }
+
+    /*
+     * Finds the last jump in `i..end` back to the instruction at `i`, which makes a loop.
+     */
+    fn back_jump(&self, i: usize, end: usize) -> Option<usize> {
+        let header = self.function.code[i].addr;
+        (i..end).rev().find(|&b| {
+            let instruction = &self.function.code[b];
+            is_jump(instruction) && constant_condition(instruction) != Some(false)
+                && !self.program.calls.contains_key(&instruction.addr)
+                && immediate_target(instruction) == Some(header)
+        })
+    }
+
+    fn jump_to(&self, target: usize, lp: Option<Loop>) -> Kind {
+        match lp {
+            Some(lp) if target == lp.exit => Kind::Break,
+            Some(lp) if target == lp.header && lp.continues => Kind::Continue,
+            _ => Kind::Goto(target),
+        }
+    }
+
+    /*
+     * Turns the instructions in `range` into statements.
+     */
+    fn block(&self, range: Range<usize>, lp: Option<Loop>) -> Vec<Stmt> {
+        let code = &self.function.code;
+        let mut stmts = Vec::new();
+        let mut i = range.start;
+        while i < range.end {
+            if let Some(b) = self.back_jump(i, range.end) {
+                let header = code[i].addr;
+                let exit = code[b].next();
+                let kind = if constant_condition(&code[b]) == Some(true) {
+                    let lp = Some(Loop { header, exit, continues: true });
+                    let mut first = i;
+                    while first < b && self.folded.contains(&first) {
+                        first += 1;
+                    }
+                    let instruction = &code[first];
+                    if first < b && is_jump(instruction) && constant_condition(instruction).is_none()
+                        && immediate_target(instruction) == Some(exit) {
+                        Kind::While(negate(self.taken(first)), self.block(first + 1..b, lp))
+                    } else {
+                        Kind::While(Expr::Num(1), self.block(i..b, lp))
+                    }
+                } else {
+                    let lp = Some(Loop { header, exit, continues: false });
+                    Kind::DoWhile(self.block(i..b, lp), self.taken(b))
+                };
+                stmts.push(Stmt { start: header, kind });
+                i = b + 1;
+                continue;
+            }
+            if self.folded.contains(&i) {
+                i += 1;
+                continue;
+            }
+
+            let instruction = &code[i];
+            let start = self.start(i);
+            let operand = |n: usize| self.operand(i, instruction.params[n]);
+            let mut next = i + 1;
+            let kind = match instruction.op {
+                Op::Add | Op::Mul | Op::LessThan | Op::Equals => Some(Kind::Assign(self.var(i, instruction.params[2]), self.value(i))),
+                Op::Input => Some(Kind::Assign(self.var(i, instruction.params[0]), Expr::Input)),
+                Op::Output => Some(Kind::Output(operand(0))),
+                Op::AdjustBase if instruction.params[0].mode == Mode::Immediate && self.function.offset(i).is_some() => None,
+                Op::AdjustBase => Some(Kind::AdjustBase(operand(0))),
+                Op::Halt => Some(Kind::Halt),
+                Op::JumpIfTrue | Op::JumpIfFalse => match constant_condition(instruction) {
+                    Some(false) => None,
+                    Some(true) => match (self.calls.get(&i), self.returns.get(&i)) {
+                        // A call whose result is used is part of the next statement
+                        (Some(call), _) if call.result_use.is_some() => None,
+                        (Some(_), _) => Some(Kind::Call(self.call_expr(i))),
+                        (None, Some(result)) => Some(Kind::Return(result.map(|r| self.value(r)))),
+                        (None, None) => Some(match immediate_target(instruction) {
+                            Some(target) => self.jump_to(target, lp),
+                            None => Kind::GotoIndirect(operand(1)),
+                        }),
+                    },
+                    None => {
+                        let (kind, after) = self.conditional(i, range.end, lp);
+                        next = after;
+                        Some(kind)
+                    }
+                },
+            };
+            if let Some(kind) = kind {
+                stmts.push(Stmt { start, kind });
+            }
+            i = next;
+        }
+        stmts
+    }
+
+    /*
+     * Turns a conditional jump into an if statement, with the index of the instruction after it.
+     */
+    fn conditional(&self, i: usize, end: usize, lp: Option<Loop>) -> (Kind, usize) {
+        let code = &self.function.code;
+        let taken = self.taken(i);
+        let target = match immediate_target(&code[i]) {
+            Some(target) => target,
+            None => return (Kind::If(taken, vec![Stmt { start: code[i].addr, kind: Kind::GotoIndirect(self.operand(i, code[i].params[1])) }], Vec::new()), i + 1),
+        };
+        let jump = self.jump_to(target, lp);
+        let t = match self.function.index.get(&target) {
+            Some(&t) if t > i && t <= end && jump == Kind::Goto(target) => t,
+            _ => return (Kind::If(taken, vec![Stmt { start: code[i].addr, kind: jump }], Vec::new()), i + 1),
+        };
+
+        // A jump over an else branch at the end of the then branch
+        if t > i + 1 {
+            let last = &code[t - 1];
+            if is_jump(last) && constant_condition(last) == Some(true) && !self.program.calls.contains_key(&last.addr) && !self.returns.contains_key(&(t - 1)) {
+                if let Some(e) = immediate_target(last).and_then(|e| self.function.index.get(&e).cloned()) {
+                    if e > t && e <= end && self.jump_to(code[e].addr, lp) == Kind::Goto(code[e].addr) {
+                        return (Kind::If(negate(taken), self.block(i + 1..t - 1, lp), self.block(t..e, lp)), e);
+                    }
+                }
+            }
+        }
+        (Kind::If(negate(taken), self.block(i + 1..t, lp), Vec::new()), t)
+    }
+}
+
+fn collect(stmts: &[Stmt], starts: &mut HashSet<usize>, gotos: &mut HashSet<usize>, vars: &mut BTreeSet<Var>) {
+    let expr = |e: &Expr, vars: &mut BTreeSet<Var>| visit(e, vars);
+    for stmt in stmts {
+        starts.insert(stmt.start);
+        match &stmt.kind {
+            Kind::Assign(var, e) => {
+                vars.insert(*var);
+                expr(e, vars);
+            }
+            Kind::Output(e) | Kind::Call(e) | Kind::AdjustBase(e) | Kind::GotoIndirect(e) | Kind::Return(Some(e)) => expr(e, vars),
+            Kind::Goto(target) => {
+                gotos.insert(*target);
+            }
+            Kind::If(e, then, els) => {
+                expr(e, vars);
+                collect(then, starts, gotos, vars);
+                collect(els, starts, gotos, vars);
+            }
+            Kind::While(e, body) | Kind::DoWhile(body, e) => {
+                expr(e, vars);
+                collect(body, starts, gotos, vars);
+            }
+            Kind::Halt | Kind::Return(None) | Kind::Break | Kind::Continue => (),
+        }
+    }
+}
+
+fn visit(e: &Expr, vars: &mut BTreeSet<Var>) {
+    match e {
+        Expr::Num(_) | Expr::Input => (),
+        Expr::Var(var) => {
+            vars.insert(*var);
+        }
+        Expr::Binary(_, a, b) => {
+            visit(a, vars);
+            visit(b, vars);
+        }
+        Expr::Not(e) | Expr::Neg(e) => visit(e, vars),
+        Expr::Call(_, args) => args.iter().for_each(|arg| visit(arg, vars)),
+    }
+}
+
+fn render(stmts: &[Stmt], depth: usize, labels: &HashSet<usize>, out: &mut String) {
+    let indent = "    ".repeat(depth);
+    for stmt in stmts {
+        if labels.contains(&stmt.start) {
+            out.push_str(&format!("{}L{}:\n", "    ".repeat(depth - 1), stmt.start));
+        }
+        let line = match &stmt.kind {
+            Kind::Assign(var, e) => format!("{} = {};", var, e),
+            Kind::Output(e) => format!("output({});", e),
+            Kind::Call(e) => format!("{};", e),
+            Kind::AdjustBase(e) => format!("rb += {};", e),
+            Kind::Halt => "halt();".to_string(),
+            Kind::Return(None) => "return;".to_string(),
+            Kind::Return(Some(e)) => format!("return {};", e),
+            Kind::Goto(target) if labels.contains(target) => format!("goto L{};", target),
+            Kind::Goto(target) => format!("goto *{};", target),
+            Kind::GotoIndirect(e) => format!("goto *{};", e),
+            Kind::Break => "break;".to_string(),
+            Kind::Continue => "continue;".to_string(),
+            Kind::If(cond, then, els) => {
+                out.push_str(&format!("{}if ({}) {{\n", indent, cond));
+                render(then, depth + 1, labels, out);
+                if !els.is_empty() {
+                    out.push_str(&format!("{}}} else {{\n", indent));
+                    render(els, depth + 1, labels, out);
+                }
+                "}".to_string()
+            }
+            Kind::While(cond, body) => {
+                out.push_str(&format!("{}while ({}) {{\n", indent, cond));
+                render(body, depth + 1, labels, out);
+                "}".to_string()
+            }
+            Kind::DoWhile(body, cond) => {
+                out.push_str(&format!("{}do {{\n", indent));
+                render(body, depth + 1, labels, out);
+                format!("}} while ({});", cond)
+            }
+        };
+        out.push_str(&format!("{}{}\n", indent, line));
+    }
+}
+
+/*
+ * Decompiles a program, starting from the function at address 0.
+ */
+pub fn decompile(program: &[i64]) -> String {
+    let mut decompiler = Decompiler::new(program);
+    let mut num_args = HashMap::new();
+    for function in decompiler.functions.values() {
+        let body = Body::new(&decompiler, function);
+        for call in body.calls.values() {
+            let count = call.args.keys().last().cloned().unwrap_or(0) as usize;
+            let n = num_args.entry(call.callee).or_insert(0);
+            *n = count.max(*n);
+        }
+    }
+    decompiler.num_args = num_args;
+
+    // Variables used by a single function are declared inside it
+    let mut functions = Vec::new();
+    let mut users: BTreeMap<usize, usize> = BTreeMap::new();
+    for function in decompiler.functions.values() {
+        let body = Body::new(&decompiler, function);
+        let stmts = body.block(0..function.code.len(), None);
+        let (mut starts, mut gotos, mut vars) = (HashSet::new(), HashSet::new(), BTreeSet::new());
+        collect(&stmts, &mut starts, &mut gotos, &mut vars);
+        for var in &vars {
+            if let Var::Global(addr) = var {
+                *users.entry(*addr).or_insert(0) += 1;
+            }
+        }
+        let labels: HashSet<usize> = starts.intersection(&gotos).cloned().collect();
+        functions.push((function, stmts, labels, vars));
+    }
+
+    let initial = |addr: usize| program.get(addr).cloned().unwrap_or(0);
+    let mut out = String::new();
+    for (addr, _) in users.iter().filter(|(_, n)| **n > 1) {
+        out.push_str(&format!("var v{} = {};\n", addr, initial(*addr)));
+    }
+    for (function, stmts, labels, vars) in functions {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let num_args = decompiler.num_args.get(&function.entry).cloned().unwrap_or(0);
+        let args: Vec<String> = (1..=num_args).map(|n| format!("arg{}", n)).collect();
+        out.push_str(&format!("fn {}({}) {{\n", name(function.entry), args.join(", ")));
+        if function.entry == 0 {
+            if let Some(code) = function.code.first() {
+                if code.op == Op::AdjustBase && code.params[0].mode == Mode::Immediate {
+                    out.push_str(&format!("    // the stack starts at {}\n", code.params[0].value));
+                }
+            }
+        }
+        for var in &vars {
+            if let Var::Global(addr) = var {
+                if users[addr] == 1 {
+                    out.push_str(&format!("    static v{} = {};\n", addr, initial(*addr)));
+                }
+            }
+        }
+        let locals: Vec<String> = vars.iter().filter(|var| matches!(var, Var::Local(_))).map(|var| var.to_string()).collect();
+        if !locals.is_empty() {
+            out.push_str(&format!("    var {};\n", locals.join(", ")));
+        }
+        render(&stmts, 1, &labels, &mut out);
+        for addr in &function.invalid {
+            out.push_str(&format!("    // runs into {} at {}, which isn't an instruction\n", initial(*addr), addr));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::compiler;
+
+    const SOURCE: &str = "
+        var total = 0;
+        fn add(a, b) {
+            var c = a + b;
+            return c;
+        }
+        fn main() {
+            var i = 0;
+            while (i < 5) {
+                if (i == 2) { output(i); } else { total = add(total, i); }
+                i = i + 1;
+            }
+            output(total);
+        }";
+
+    #[test]
+    fn compiled() {
+        let program = compiler::compile(SOURCE).unwrap();
+        assert_eq!(decompile(&program), "\
+fn main() {
+    // the stack starts at 97
+    f10();
+    halt();
+}
+
+fn f10() {
+    static v96 = 0;
+    var local1;
+    local1 = 0;
+    while (local1 < 5) {
+        if (local1 == 2) {
+            output(local1);
+        } else {
+            v96 = f72(v96, local1);
+        }
+        local1 = local1 + 1;
+    }
+    output(v96);
+    return 0;
+}
+
+fn f72(arg1, arg2) {
+    var local3;
+    local3 = arg1 + arg2;
+    return local3;
+}
+");
+    }
+
+    #[test]
+    fn unstructured() {
+        // Day 2's example writes into its own code
+        assert_eq!(decompile(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]), "\
+fn main() {
+    static v9 = 30;
+    static v10 = 40;
+    static v11 = 50;
+    mem[3] = v9 + v10;
+    mem[0] = mem[3] * v11;
+    halt();
+}
+");
+        // A jump into the middle of the loop's own jump, and from there into data
+        assert_eq!(decompile(&[3, 20, 1005, 20, 9, 4, 20, 1105, 1, 2, 1006, 20, 5, 1105, 1, 17, 99, 7]), "\
+fn main() {
+    static v20 = 0;
+    static v1006 = 0;
+    v20 = input();
+    while (1) {
+        if (v20) {
+            goto L9;
+        }
+        output(v20);
+    }
+L9:
+    mem[5] = v1006 * v20;
+    goto *17;
+    // runs into 7 at 17, which isn't an instruction
+}
+");
+    }
+}
@@ -0,0 +1,327 @@
+/*
+ * Disassembler for Intcode programs.
+ *
+ * Parameters are written as `[12]` for position mode, `12` for immediate mode
+ * and `[rb+12]` for relative mode.
+ */
+
+use std::fmt;
+
+use crate::intcode::IntCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpIfTrue,
+    JumpIfFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Param {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub op: Op,
+    pub params: Vec<Param>,
+}
+
+/*
+ * A single line of a disassembly; cells that don't decode into a valid instruction are data.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Code(Instruction),
+    Data(usize, i64),
+}
+
+impl Op {
+    pub fn from_code(code: i64) -> Option<Op> {
+        match code {
+            1 => Some(Op::Add),
+            2 => Some(Op::Mul),
+            3 => Some(Op::Input),
+            4 => Some(Op::Output),
+            5 => Some(Op::JumpIfTrue),
+            6 => Some(Op::JumpIfFalse),
+            7 => Some(Op::LessThan),
+            8 => Some(Op::Equals),
+            9 => Some(Op::AdjustBase),
+            99 => Some(Op::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Op::Add => 1,
+            Op::Mul => 2,
+            Op::Input => 3,
+            Op::Output => 4,
+            Op::JumpIfTrue => 5,
+            Op::JumpIfFalse => 6,
+            Op::LessThan => 7,
+            Op::Equals => 8,
+            Op::AdjustBase => 9,
+            Op::Halt => 99,
+        }
+    }
+
+    pub fn num_params(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => 3,
+            Op::JumpIfTrue | Op::JumpIfFalse => 2,
+            Op::Input | Op::Output | Op::AdjustBase => 1,
+            Op::Halt => 0,
+        }
+    }
+
+    /*
+     * Index of the parameter this instruction writes to, if any.
+     */
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => Some(2),
+            Op::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Add => "ADD",
+            Op::Mul => "MUL",
+            Op::Input => "IN",
+            Op::Output => "OUT",
+            Op::JumpIfTrue => "JNZ",
+            Op::JumpIfFalse => "JZ",
+            Op::LessThan => "LT",
+            Op::Equals => "EQ",
+            Op::AdjustBase => "ARB",
+            Op::Halt => "HLT",
+        }
+    }
+}
+
+impl Mode {
+    pub fn from_code(code: i64) -> Option<Mode> {
+        match code {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+impl Instruction {
+    /*
+     * Decodes the instruction at `addr`.
+     * Returns None if the cell isn't a valid instruction, including writes in immediate mode,
+     * or if the instruction runs past the end of memory.
+     */
+    pub fn decode(mem: &[i64], addr: usize) -> Option<Instruction> {
+        let instruction = *mem.get(addr)?;
+        if instruction < 0 {
+            return None;
+        }
+        let op = Op::from_code(instruction % 100)?;
+        if instruction >= 10i64.pow(2 + op.num_params() as u32) {
+            return None; // Modes for parameters that don't exist
+        }
+
+        let mut params = Vec::new();
+        for i in 0..op.num_params() {
+            let mode = Mode::from_code((instruction / 10i64.pow(2 + i as u32)) % 10)?;
+            if mode == Mode::Immediate && op.write_param() == Some(i) {
+                return None;
+            }
+            params.push(Param { mode, value: *mem.get(addr + 1 + i)? });
+        }
+        Some(Instruction { addr, op, params })
+    }
+
+    /*
+     * Number of memory cells taken up by the instruction.
+     */
+    pub fn size(&self) -> usize {
+        self.params.len() + 1
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let mut instruction = self.op.code();
+        for (i, param) in self.params.iter().enumerate() {
+            instruction += param.mode.code() * 10i64.pow(2 + i as u32);
+        }
+        let mut cells = vec![instruction];
+        cells.extend(self.params.iter().map(|p| p.value));
+        cells
+    }
+
+    /*
+     * Address of the instruction that follows this one if no jump is taken.
+     */
+    pub fn next(&self) -> usize {
+        self.addr + self.size()
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb{}]", self.value),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic())?;
+        for (i, param) in self.params.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, param)?;
+        }
+        Ok(())
+    }
+}
+
+impl Line {
+    pub fn addr(&self) -> usize {
+        match self {
+            Line::Code(instruction) => instruction.addr,
+            Line::Data(addr, _) => *addr,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Code(instruction) => write!(f, "{:>5}: {}", instruction.addr, instruction),
+            Line::Data(addr, value) => write!(f, "{:>5}: DATA {}", addr, value),
+        }
+    }
+}
+
+/*
+ * Disassembles memory with a linear sweep.
+ * Intcode doesn't separate code from data, so anything that happens to decode
+ * as an instruction is shown as one.
+ */
+pub fn disassemble(mem: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+    while addr < mem.len() {
+        match Instruction::decode(mem, addr) {
+            Some(instruction) => {
+                addr = instruction.next();
+                lines.push(Line::Code(instruction));
+            }
+            None => {
+                lines.push(Line::Data(addr, mem[addr]));
+                addr += 1;
+            }
+        }
+    }
+    lines
+}
+
+/*
+ * Returns a line describing the instruction the machine is about to execute,
+ * along with the values of the parameters it reads.
+ */
+pub fn trace(machine: &IntCode) -> String {
+    let instruction = match Instruction::decode(machine.memory(), machine.ip()) {
+        Some(instruction) => instruction,
+        None => match machine.memory().get(machine.ip()) {
+            Some(value) => return format!("{:>5}: <invalid instruction {}>", machine.ip(), value),
+            None => return format!("{:>5}: <out of bounds>", machine.ip()),
+        },
+    };
+
+    let values: Vec<String> = instruction.params
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| instruction.op.write_param() != Some(*i))
+        .filter(|(_, param)| param.mode != Mode::Immediate)
+        .map(|(_, param)| {
+            let addr = match param.mode {
+                Mode::Relative => machine.relative_base() + param.value,
+                _ => param.value,
+            };
+            match machine.memory().get(addr as usize) {
+                Some(value) if addr >= 0 => format!("{}={}", param, value),
+                _ => format!("{}=<out of bounds>", param),
+            }
+        }).collect();
+
+    let line = format!("{:>5}: {:<32} rb={}", machine.ip(), instruction.to_string(), machine.relative_base());
+    if values.is_empty() {
+        line
+    } else {
+        format!("{} ; {}", line, values.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+
+    #[test]
+    fn decode() {
+        let mem = intcode::parse_program("1002,4,3,4,33,21101,-1,7,-2").unwrap();
+        let lines: Vec<String> = disassemble(&mem).iter().map(|l| l.to_string()).collect();
+        assert_eq!(lines, [
+            "    0: MUL [4], 3, [4]",
+            "    4: DATA 33",
+            "    5: ADD -1, 7, [rb-2]",
+        ]);
+        for line in disassemble(&mem) {
+            if let Line::Code(instruction) = line {
+                assert_eq!(instruction.encode(), &mem[instruction.addr..instruction.next()]);
+            }
+        }
+
+        // Immediate writes and trailing modes aren't valid instructions
+        assert_eq!(Instruction::decode(&[11101, 1, 1, 0], 0), None);
+        assert_eq!(Instruction::decode(&[1099], 0), None);
+        assert_eq!(Instruction::decode(&[1, 0, 0], 0), None);
+    }
+
+    #[test]
+    fn trace() {
+        let mut machine = IntCode::new(intcode::parse_program("109,2,1201,-1,5,7,99,0").unwrap());
+        assert_eq!(super::trace(&machine), format!("    0: {:<32} rb=0", "ARB 2"));
+        machine.step();
+        assert_eq!(super::trace(&machine), format!("    2: {:<32} rb=2 ; [rb-1]=2", "ADD [rb-1], 5, [7]"));
+    }
+}
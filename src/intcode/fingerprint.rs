@@ -0,0 +1,181 @@
+/*
+ * Recognizing which puzzle an Intcode program belongs to.
+ *
+ * Every puzzle's program has a recognizable shape: a typical length, the instructions it uses
+ * and, most of all, the same first few instructions for everyone (up to a few addresses and
+ * constants, which are wildcards here). Inputs also differ in whether they copy a constant
+ * with `1101 a,0` or `1102 a,1`, so those opcodes are wildcards too. The day solutions check their input against these,
+ * so that passing the wrong file gives a warning rather than a puzzling failure.
+ * Set INTCODE_STRICT_INPUTS=1 to make that an error instead.
+ */
+
+use std::collections::HashMap;
+use std::env;
+use std::ops::RangeInclusive;
+
+use crate::intcode::disasm;
+use crate::intcode::disasm::{Line, Op};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub len: usize,
+    // Instructions found by a linear sweep, so data may be counted as well
+    pub histogram: HashMap<Op, usize>,
+}
+
+pub struct Signature {
+    pub day: u32,
+    pub len: RangeInclusive<usize>,
+    // Comma-separated values the program starts with, where `*` matches anything
+    pub prologue: &'static str,
+    pub requires: &'static [Op],
+}
+
+const IO: &[Op] = &[Op::Input, Op::Output];
+const IO_RELATIVE: &[Op] = &[Op::Input, Op::Output, Op::AdjustBase];
+
+pub const SIGNATURES: &[Signature] = &[
+    Signature { day: 2, len: 100..=200, prologue: "1,*,*,3,1,1,2,3,1,3,4,3,1,5,0,3", requires: &[Op::Add, Op::Mul] },
+    Signature { day: 5, len: 600..=720, prologue: "3,225,1,225,6,6,1100,1,238,225,104,0", requires: IO },
+    Signature { day: 7, len: 450..=560, prologue: "3,8,1001,8,*,8,105,1,0,0", requires: IO },
+    Signature { day: 9, len: 900..=1050, prologue: "1102,34463338,34463338,63,1007,63,34463338,63,1005,63", requires: IO_RELATIVE },
+    Signature { day: 11, len: 550..=700, prologue: "3,8,1005,8,*,1106,0,11,0,0,0,104,1,104,0", requires: IO_RELATIVE },
+    Signature { day: 13, len: 1900..=3200, prologue: "1,380,379,385,1008,*,*,381,1005,381,12,99,109", requires: IO_RELATIVE },
+    Signature { day: 15, len: 1000..=1100, prologue: "3,1033,1008,1033,1,1032,1005,1032,31,1008,1033,2,1032,1005,1032", requires: IO_RELATIVE },
+    Signature { day: 17, len: 1300..=1700, prologue: "1,330,331,332,109", requires: IO_RELATIVE },
+    Signature { day: 19, len: 380..=500, prologue: "109,*,203,1", requires: IO_RELATIVE },
+    Signature { day: 21, len: 1800..=2300, prologue: "109,2050", requires: IO_RELATIVE },
+    Signature { day: 23, len: 1900..=2300, prologue: "3,62,1001,62,11,10,109", requires: IO_RELATIVE },
+    Signature { day: 25, len: 4000..=6000, prologue: "109,*,*", requires: IO_RELATIVE },
+];
+
+pub fn fingerprint(program: &[i64]) -> Fingerprint {
+    let mut histogram = HashMap::new();
+    for line in disasm::disassemble(program) {
+        if let Line::Code(instruction) = line {
+            *histogram.entry(instruction.op).or_insert(0) += 1;
+        }
+    }
+    Fingerprint { len: program.len(), histogram }
+}
+
+impl Signature {
+    /*
+     * Lists the ways the program doesn't look like this puzzle's; empty if it does.
+     */
+    pub fn mismatches(&self, program: &[i64], fingerprint: &Fingerprint) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let prologue_matches = self.prologue.split(',').enumerate().all(|(i, pattern)| {
+            pattern == "*" || program.get(i).map(|n| n.to_string()) == Some(pattern.to_string())
+        });
+        if !prologue_matches {
+            reasons.push(format!("it doesn't start with {}", self.prologue));
+        }
+        if !self.len.contains(&fingerprint.len) {
+            reasons.push(format!("its length of {} isn't within {}..={}", fingerprint.len, self.len.start(), self.len.end()));
+        }
+        for op in self.requires {
+            if !fingerprint.histogram.contains_key(op) {
+                reasons.push(format!("it has no {} instructions", op.mnemonic()));
+            }
+        }
+        reasons
+    }
+}
+
+/*
+ * Finds the puzzle a program belongs to.
+ */
+pub fn recognize(program: &[i64]) -> Option<u32> {
+    let fingerprint = fingerprint(program);
+    SIGNATURES.iter()
+        .find(|signature| signature.mismatches(program, &fingerprint).is_empty())
+        .map(|signature| signature.day)
+}
+
+/*
+ * Checks that a program looks like the one for the given day, explaining why not otherwise.
+ */
+pub fn check(day: u32, program: &[i64]) -> Result<(), String> {
+    let signature = match SIGNATURES.iter().find(|signature| signature.day == day) {
+        Some(signature) => signature,
+        None => return Ok(()),
+    };
+    let reasons = signature.mismatches(program, &fingerprint(program));
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("the input for day {} doesn't look like its Intcode program: {}", day, reasons.join(", "));
+    if let Some(other) = recognize(program) {
+        message += &format!(" (it looks like day {}'s)", other);
+    }
+    Err(message)
+}
+
+/*
+ * Warns about an input that doesn't look like the day's program, or panics if INTCODE_STRICT_INPUTS is set.
+ * The examples used in the tests are never checked.
+ */
+pub fn check_input(day: u32, program: &[i64]) {
+    if cfg!(test) {
+        return;
+    }
+    if let Err(message) = check(day, program) {
+        match env::var("INTCODE_STRICT_INPUTS") {
+            Ok(value) if !value.is_empty() && value != "0" => panic!("{}", message),
+            _ => eprintln!("warning: {}", message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Puts a prologue at the start of an otherwise empty program of the given length
+    fn program(prologue: &str, len: usize) -> Vec<i64> {
+        let mut program: Vec<i64> = prologue.split(',').map(|n| n.parse().unwrap()).collect();
+        program.resize(len, 0);
+        program
+    }
+
+    #[test]
+    fn recognize() {
+        let day15 = program("3,1033,1008,1033,1,1032,1005,1032,31,1008,1033,2,1032,1005,1032,58,204,1,109,1", 1045);
+        let day13 = program("1,380,379,385,1008,2719,168406,381,1005,381,12,99,109,2720,1101,0,0,383,3,0,4,0", 2720);
+        assert_eq!(super::recognize(&day15), Some(15));
+        assert_eq!(super::recognize(&day13), Some(13));
+        assert!(check(15, &day15).is_ok());
+
+        let error = check(11, &day13).unwrap_err();
+        assert!(error.starts_with("the input for day 11 doesn't look like its Intcode program: it doesn't start with 3,8,1005,8"));
+        assert!(error.contains("its length of 2720 isn't within 550..=700"));
+        assert!(error.ends_with("(it looks like day 13's)"));
+
+        // Right start, but no room for the rest of the program
+        let error = check(15, &day15[..40]).unwrap_err();
+        assert!(error.ends_with("its length of 40 isn't within 1000..=1100"));
+        assert_eq!(super::recognize(&[1, 0, 0, 0, 99]), None);
+        assert!(check(1, &[1, 0, 0, 0, 99]).is_ok());
+    }
+
+    #[test]
+    fn equivalent_opcodes() {
+        // Day 25 inputs start by copying a constant with either an ADD or a MUL
+        let add = program("109,4815,21101,3124,0,1,21101,13,0,0,1106,0,1424,203,1,204,1,99", 4900);
+        let mul = program("109,4815,21102,3124,1,1,21102,1,13,0,1106,0,1424,203,1,204,1,99", 4900);
+        assert!(check(25, &add).is_ok());
+        assert!(check(25, &mul).is_ok());
+        assert_eq!(super::recognize(&mul), Some(25));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let fingerprint = super::fingerprint(&[1101, 1, 2, 0, 104, 3, 1, 0, 0, 0, 99]);
+        assert_eq!(fingerprint.len, 11);
+        assert_eq!(fingerprint.histogram[&Op::Add], 2);
+        assert_eq!(fingerprint.histogram[&Op::Output], 1);
+        assert_eq!(fingerprint.histogram[&Op::Halt], 1);
+        assert!(!fingerprint.histogram.contains_key(&Op::Mul));
+    }
+}
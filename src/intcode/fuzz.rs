@@ -0,0 +1,707 @@
+/*
+ * Differential fuzzing of the Intcode machines.
+ *
+ * Random, well-formed programs are run on every machine that understands them and the
+ * outputs, final memory and the way they stopped are compared. The day 2 machine only knows
+ * ADD and MUL in position mode, and the day 5 machine has no relative mode and reads the same
+ * input over and over, so programs are generated in families matching those subsets.
+ * The full family needs a second machine that understands the whole instruction set, so its
+ * cases are only generated when there is one, like the JIT.
+ *
+ * Programs are kept in a structured form where parameters refer to data cells and
+ * instructions by index, so that parts of them can be removed while minimizing a failing
+ * case without breaking the rest.
+ */
+
+use std::fmt;
+use std::ops::Range;
+use std::panic;
+
+use crate::day02;
+use crate::day05;
+use crate::intcode::IntCode;
+use crate::intcode::Status;
+use crate::intcode::disasm;
+use crate::intcode::disasm::{Instruction, Mode, Op, Param};
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+use crate::intcode::jit::Jit;
+use crate::intcode::optimize::Stop;
+
+/*
+ * Xorshift generator, so that a run can be reproduced from its seed.
+ */
+pub struct Rng(u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    Day02,
+    Day05,
+    Full,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Imm(i64),
+    // Position of a data cell
+    Data(usize),
+    // Address of a data cell, as an immediate
+    Pointer(usize),
+    // Position of an instruction's first cell; one past the last instruction is the final halt
+    Code(usize),
+    // Address of an instruction, as an immediate
+    Target(usize),
+    Rel(i64),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaseOp {
+    pub op: Op,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inputs {
+    Queue(Vec<i64>),
+    // Every input instruction reads the same value, like on day 5
+    Repeat(i64),
+}
+
+/*
+ * A program made of instructions followed by a halt and data cells, along with its inputs.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub family: Family,
+    pub ops: Vec<CaseOp>,
+    pub data: Vec<i64>,
+    pub inputs: Inputs,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub outputs: Vec<i64>,
+    pub mem: Vec<i64>,
+    pub stop: Stop,
+}
+
+pub trait Engine {
+    fn name(&self) -> &'static str;
+    fn supports(&self, family: Family) -> bool;
+    /*
+     * Runs a program until it stops or has executed `max_steps` instructions.
+     */
+    fn run(&self, program: &[i64], inputs: &Inputs, max_steps: usize) -> Outcome;
+}
+
+pub struct Day02Engine;
+pub struct Day05Engine;
+pub struct IntCodeEngine;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub struct JitEngine;
+
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub case: Case,
+    pub outcomes: Vec<(&'static str, Outcome)>,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        min + (self.next_u64() % (max - min + 1) as u64) as i64
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+impl Family {
+    fn ops(self) -> &'static [Op] {
+        match self {
+            Family::Day02 => &[Op::Add, Op::Mul],
+            Family::Day05 => &[Op::Add, Op::Mul, Op::Input, Op::Output, Op::JumpIfTrue, Op::JumpIfFalse, Op::LessThan, Op::Equals],
+            Family::Full => &[Op::Add, Op::Mul, Op::Input, Op::Output, Op::JumpIfTrue, Op::JumpIfFalse, Op::LessThan, Op::Equals, Op::AdjustBase],
+        }
+    }
+
+    /*
+     * Whether every machine running this family behaves the same on an instruction.
+     */
+    fn understands(self, instruction: &Instruction, raw: i64) -> bool {
+        match self {
+            // The day 2 machine doesn't look at parameter modes at all
+            Family::Day02 => raw == 1 || raw == 2 || raw == 99,
+            Family::Day05 => instruction.op != Op::AdjustBase && instruction.params.iter().all(|p| p.mode != Mode::Relative),
+            Family::Full => true,
+        }
+    }
+}
+
+impl Case {
+    /*
+     * Lays the case out in memory.
+     */
+    pub fn image(&self) -> Vec<i64> {
+        let mut addrs = vec![0];
+        for op in &self.ops {
+            addrs.push(addrs.last().unwrap() + 1 + op.op.num_params());
+        }
+        let data_base = addrs.last().unwrap() + 1;
+        let data = |i: usize| (data_base + i % self.data.len().max(1)) as i64;
+        let code = |j: usize| addrs[j.min(self.ops.len())] as i64;
+
+        let mut image = Vec::new();
+        for (op, addr) in self.ops.iter().zip(&addrs) {
+            let params = op.operands.iter().map(|operand| {
+                let (mode, value) = match *operand {
+                    Operand::Imm(n) => (Mode::Immediate, n),
+                    Operand::Data(i) => (Mode::Position, data(i)),
+                    Operand::Pointer(i) => (Mode::Immediate, data(i)),
+                    Operand::Code(j) => (Mode::Position, code(j)),
+                    Operand::Target(j) => (Mode::Immediate, code(j)),
+                    Operand::Rel(n) => (Mode::Relative, n),
+                };
+                Param { mode, value }
+            }).collect();
+            image.extend(Instruction { addr: *addr, op: op.op, params }.encode());
+        }
+        image.push(99);
+        image.extend(&self.data);
+        image
+    }
+
+    fn remove_ops(&self, range: Range<usize>) -> Case {
+        let mut case = self.clone();
+        case.ops.drain(range.clone());
+        for op in &mut case.ops {
+            for operand in &mut op.operands {
+                if let Operand::Code(j) | Operand::Target(j) = operand {
+                    if *j >= range.end {
+                        *j -= range.len();
+                    } else if *j >= range.start {
+                        *j = range.start;
+                    }
+                }
+            }
+        }
+        case
+    }
+
+    fn remove_data(&self, range: Range<usize>) -> Case {
+        let mut case = self.clone();
+        case.data.drain(range.clone());
+        for op in &mut case.ops {
+            for operand in &mut op.operands {
+                if let Operand::Data(i) | Operand::Pointer(i) = operand {
+                    if *i >= range.end {
+                        *i -= range.len();
+                    } else if *i >= range.start {
+                        *i = range.start;
+                    }
+                }
+            }
+        }
+        case
+    }
+
+    fn remove_inputs(&self, range: Range<usize>) -> Case {
+        let mut case = self.clone();
+        if let Inputs::Queue(values) = &mut case.inputs {
+            values.drain(range);
+        }
+        case
+    }
+
+    fn num_inputs(&self) -> usize {
+        match &self.inputs {
+            Inputs::Queue(values) => values.len(),
+            Inputs::Repeat(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:?} program with inputs {:?}", self.family, self.inputs)?;
+        for line in disasm::disassemble(&self.image()) {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.case)?;
+        for (name, outcome) in &self.outcomes {
+            writeln!(f, "{}: {:?}, outputs {:?}, memory {:?}", name, outcome.stop, outcome.outputs, outcome.mem)?;
+        }
+        Ok(())
+    }
+}
+
+fn random_read(rng: &mut Rng, family: Family, num_ops: usize, num_data: usize) -> Operand {
+    let roll = rng.below(100);
+    match family {
+        Family::Day02 if roll < 90 => Operand::Data(rng.below(num_data)),
+        Family::Day05 if roll < 40 => Operand::Imm(rng.range(-9, 9)),
+        Family::Day05 if roll < 90 => Operand::Data(rng.below(num_data)),
+        Family::Full if roll < 30 => Operand::Imm(rng.range(-9, 9)),
+        Family::Full if roll < 65 => Operand::Data(rng.below(num_data)),
+        Family::Full if roll < 90 => Operand::Rel(rng.below(num_data) as i64),
+        _ => Operand::Code(rng.below(num_ops + 1)),
+    }
+}
+
+fn random_write(rng: &mut Rng, family: Family, num_ops: usize, num_data: usize) -> Operand {
+    if rng.chance(5) {
+        // Self-modifying code
+        Operand::Code(rng.below(num_ops + 1))
+    } else if family == Family::Full && rng.chance(30) {
+        Operand::Rel(rng.below(num_data) as i64)
+    } else {
+        Operand::Data(rng.below(num_data))
+    }
+}
+
+/*
+ * Generates a random case of up to a dozen instructions.
+ */
+pub fn generate(rng: &mut Rng, family: Family) -> Case {
+    let num_data = rng.range(2, 8) as usize;
+    let num_ops = rng.range(1, 12) as usize;
+    let data = (0..num_data).map(|_| rng.range(-9, 9)).collect();
+
+    let mut ops = Vec::new();
+    if family == Family::Full {
+        // Point the relative base at the data, so that relative parameters stay in bounds
+        ops.push(CaseOp { op: Op::AdjustBase, operands: vec![Operand::Pointer(0)] });
+    }
+    while ops.len() < num_ops {
+        let op = family.ops()[rng.below(family.ops().len())];
+        let operands = (0..op.num_params()).map(|i| match op {
+            _ if op.write_param() == Some(i) => random_write(rng, family, num_ops, num_data),
+            Op::JumpIfTrue | Op::JumpIfFalse if i == 1 => Operand::Target(rng.below(num_ops + 1)),
+            Op::AdjustBase => Operand::Imm(rng.range(-2, 2)),
+            _ => random_read(rng, family, num_ops, num_data),
+        }).collect();
+        ops.push(CaseOp { op, operands });
+    }
+
+    let inputs = match family {
+        Family::Day02 => Inputs::Queue(Vec::new()),
+        Family::Day05 => Inputs::Repeat(rng.range(-9, 9)),
+        Family::Full => Inputs::Queue((0..rng.below(4)).map(|_| rng.range(-9, 9)).collect()),
+    };
+    Case { family, ops, data, inputs }
+}
+
+/*
+ * Calls `step` until it returns how the machine stopped or `max_steps` instructions have been
+ * executed. Panics are turned into crashes.
+ */
+fn drive<F: FnMut() -> Option<Stop>>(max_steps: usize, mut step: F) -> Stop {
+    panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        for _ in 0..max_steps {
+            if let Some(stop) = step() {
+                return stop;
+            }
+        }
+        Stop::StepLimit
+    })).unwrap_or(Stop::Crash)
+}
+
+impl Engine for Day02Engine {
+    fn name(&self) -> &'static str {
+        "day02"
+    }
+
+    fn supports(&self, family: Family) -> bool {
+        family == Family::Day02
+    }
+
+    fn run(&self, program: &[i64], _inputs: &Inputs, max_steps: usize) -> Outcome {
+        let mut machine = day02::IntCode::new(program.iter().map(|n| *n as i32).collect());
+        let stop = drive(max_steps, || if machine.step() { None } else { Some(Stop::Halt) });
+        Outcome { outputs: Vec::new(), mem: machine.memory().iter().map(|n| *n as i64).collect(), stop }
+    }
+}
+
+impl Engine for Day05Engine {
+    fn name(&self) -> &'static str {
+        "day05"
+    }
+
+    fn supports(&self, family: Family) -> bool {
+        family == Family::Day05
+    }
+
+    fn run(&self, program: &[i64], inputs: &Inputs, max_steps: usize) -> Outcome {
+        let input = match inputs {
+            Inputs::Repeat(n) => *n,
+            Inputs::Queue(_) => panic!("The day 5 machine can only repeat a single input"),
+        };
+        let mut machine = day05::IntCode::new(program.to_vec());
+        let mut outputs = Vec::new();
+        let stop = drive(max_steps, || if machine.step(input, &mut outputs) { None } else { Some(Stop::Halt) });
+        Outcome { outputs, mem: machine.memory().to_vec(), stop }
+    }
+}
+
+impl Engine for IntCodeEngine {
+    fn name(&self) -> &'static str {
+        "intcode"
+    }
+
+    fn supports(&self, _family: Family) -> bool {
+        true
+    }
+
+    fn run(&self, program: &[i64], inputs: &Inputs, max_steps: usize) -> Outcome {
+        let mut machine = IntCode::new(program.to_vec());
+        if let Inputs::Queue(values) = inputs {
+            machine.extend_input(values.iter().cloned());
+        }
+        let mut outputs = Vec::new();
+        let stop = drive(max_steps, || loop {
+            match machine.step() {
+                None => return None,
+                Some(Status::Output(n)) => {
+                    outputs.push(n);
+                    return None;
+                }
+                Some(Status::Halt) => return Some(Stop::Halt),
+                Some(Status::WaitingInput) => match inputs {
+                    Inputs::Repeat(n) => machine.push_input(*n),
+                    Inputs::Queue(_) => return Some(Stop::WaitingInput),
+                },
+            }
+        });
+        Outcome { outputs, mem: machine.memory().to_vec(), stop }
+    }
+}
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+impl Engine for JitEngine {
+    fn name(&self) -> &'static str {
+        "jit"
+    }
+
+    fn supports(&self, _family: Family) -> bool {
+        true
+    }
+
+    fn run(&self, program: &[i64], inputs: &Inputs, max_steps: usize) -> Outcome {
+        let mut jit = Jit::new(IntCode::new(program.to_vec()));
+        if let Inputs::Queue(values) = inputs {
+            jit.extend_input(values.iter().cloned());
+        }
+        let mut outputs = Vec::new();
+        let mut budget = max_steps;
+        let stop = panic::catch_unwind(panic::AssertUnwindSafe(|| loop {
+            match jit.execute_limited(&mut budget) {
+                None => return Stop::StepLimit,
+                Some(Status::Output(n)) => outputs.push(n),
+                Some(Status::Halt) => return Stop::Halt,
+                Some(Status::WaitingInput) => match inputs {
+                    Inputs::Repeat(n) => jit.push_input(*n),
+                    Inputs::Queue(_) => return Stop::WaitingInput,
+                },
+            }
+        })).unwrap_or(Stop::Crash);
+        Outcome { outputs, mem: jit.into_inner().memory().to_vec(), stop }
+    }
+}
+
+pub fn engines() -> Vec<Box<dyn Engine>> {
+    #[allow(unused_mut)]
+    let mut engines: Vec<Box<dyn Engine>> = vec![Box::new(Day02Engine), Box::new(Day05Engine), Box::new(IntCodeEngine)];
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    engines.push(Box::new(JitEngine));
+    engines
+}
+
+/*
+ * Whether a case stays within its family for its first `max_steps` instructions, which
+ * self-modifying code can break. Values written by day 2 programs must also fit in an i32.
+ */
+pub fn is_valid(case: &Case, max_steps: usize) -> bool {
+    let mut machine = IntCode::new(case.image());
+    if let Inputs::Queue(values) = &case.inputs {
+        machine.extend_input(values.iter().cloned());
+    }
+    let valid = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        for _ in 0..max_steps {
+            let raw = machine.memory()[machine.ip()];
+            match Instruction::decode(machine.memory(), machine.ip()) {
+                Some(instruction) if case.family.understands(&instruction, raw) => (),
+                Some(_) => return false,
+                // Unknown opcodes crash every machine, but unusual parameter modes don't
+                None => return Op::from_code(raw % 100).is_none(),
+            }
+            let mut written = None;
+            let status = machine.step_with(|addr| written = Some(addr));
+            if let Some(addr) = written {
+                if case.family == Family::Day02 && machine.memory()[addr] as i32 as i64 != machine.memory()[addr] {
+                    return false;
+                }
+            }
+            match (status, &case.inputs) {
+                (None, _) | (Some(Status::Output(_)), _) => (),
+                (Some(Status::WaitingInput), Inputs::Repeat(n)) => machine.push_input(*n),
+                _ => return true,
+            }
+        }
+        true
+    }));
+    // The machines should all crash the same way
+    valid.unwrap_or(true)
+}
+
+/*
+ * Whether at least two of the engines support a family, so that its cases compare something.
+ */
+pub fn is_comparable(family: Family, engines: &[Box<dyn Engine>]) -> bool {
+    engines.iter().filter(|engine| engine.supports(family)).count() >= 2
+}
+
+/*
+ * Runs a case on every engine that supports its family and compares the outcomes.
+ * Panics if fewer than two engines support it, since there would be nothing to compare.
+ */
+pub fn check(case: &Case, engines: &[Box<dyn Engine>], max_steps: usize) -> Result<(), Mismatch> {
+    assert!(is_comparable(case.family, engines), "Fewer than two engines support {:?} cases", case.family);
+    let program = case.image();
+    let outcomes: Vec<_> = engines.iter()
+        .filter(|engine| engine.supports(case.family))
+        .map(|engine| (engine.name(), engine.run(&program, &case.inputs, max_steps)))
+        .collect();
+    if outcomes.windows(2).all(|pair| pair[0].1 == pair[1].1) {
+        Ok(())
+    } else {
+        Err(Mismatch { case: case.clone(), outcomes })
+    }
+}
+
+/*
+ * Removes chunks of `len(case)` items, halving the chunk size whenever none can be removed.
+ */
+fn shrink<F, L, R>(mut case: Case, fails: &F, len: L, remove: R) -> Case
+where F: Fn(&Case) -> bool, L: Fn(&Case) -> usize, R: Fn(&Case, Range<usize>) -> Case {
+    let mut chunk = (len(&case) / 2).max(1);
+    while len(&case) > 0 {
+        let mut removed = false;
+        let mut start = 0;
+        while start < len(&case) {
+            let candidate = remove(&case, start..(start + chunk).min(len(&case)));
+            if fails(&candidate) {
+                case = candidate;
+                removed = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if !removed {
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+    }
+    case
+}
+
+/*
+ * Tries to replace every immediate, data cell and input by 0, 1 or half of itself.
+ */
+fn simplify_values<F: Fn(&Case) -> bool>(mut case: Case, fails: &F) -> Case {
+    fn smaller(n: i64) -> Vec<i64> {
+        vec![0, 1, n / 2].into_iter().filter(|m| m.abs() < n.abs()).collect()
+    }
+    for i in 0..case.ops.len() {
+        for j in 0..case.ops[i].operands.len() {
+            if let Operand::Imm(n) = case.ops[i].operands[j] {
+                for m in smaller(n) {
+                    let mut candidate = case.clone();
+                    candidate.ops[i].operands[j] = Operand::Imm(m);
+                    if fails(&candidate) {
+                        case = candidate;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    for i in 0..case.data.len() {
+        for m in smaller(case.data[i]) {
+            let mut candidate = case.clone();
+            candidate.data[i] = m;
+            if fails(&candidate) {
+                case = candidate;
+                break;
+            }
+        }
+    }
+    let inputs = match &case.inputs {
+        Inputs::Queue(values) => values.clone(),
+        Inputs::Repeat(n) => vec![*n],
+    };
+    for (i, n) in inputs.iter().enumerate() {
+        for m in smaller(*n) {
+            let mut candidate = case.clone();
+            match &mut candidate.inputs {
+                Inputs::Queue(values) => values[i] = m,
+                Inputs::Repeat(n) => *n = m,
+            }
+            if fails(&candidate) {
+                case = candidate;
+                break;
+            }
+        }
+    }
+    case
+}
+
+/*
+ * Shrinks a case for as long as `fails` keeps returning true for it.
+ */
+pub fn minimize<F: Fn(&Case) -> bool>(case: &Case, fails: F) -> Case {
+    let mut case = case.clone();
+    loop {
+        let before = case.clone();
+        case = shrink(case, &fails, |c| c.ops.len(), Case::remove_ops);
+        // Always keep one data cell for the data parameters to point at
+        case = shrink(case, &fails, |c| c.data.len().saturating_sub(1), Case::remove_data);
+        case = shrink(case, &fails, Case::num_inputs, Case::remove_inputs);
+        case = simplify_values(case, &fails);
+        if case == before {
+            return case;
+        }
+    }
+}
+
+/*
+ * Checks `cases` random cases, cycling through the families at least two engines support.
+ * Cases that stray outside their family are skipped; returns how many were checked, or the first
+ * mismatch after minimizing it.
+ */
+pub fn fuzz(seed: u64, cases: usize, engines: &[Box<dyn Engine>], max_steps: usize) -> Result<usize, Mismatch> {
+    let families: Vec<Family> = [Family::Day02, Family::Day05, Family::Full].iter().cloned()
+        .filter(|&family| is_comparable(family, engines))
+        .collect();
+    if families.is_empty() {
+        return Ok(0);
+    }
+    let mut rng = Rng::new(seed);
+    let mut checked = 0;
+    for i in 0..cases {
+        let case = generate(&mut rng, families[i % families.len()]);
+        if !is_valid(&case, max_steps) {
+            continue;
+        }
+        checked += 1;
+        if check(&case, engines, max_steps).is_err() {
+            let fails = |c: &Case| is_valid(c, max_steps) && check(c, engines, max_steps).is_err();
+            return Err(check(&minimize(&case, fails), engines, max_steps).unwrap_err());
+        }
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Set INTCODE_FUZZ_SEED and INTCODE_FUZZ_CASES to fuzz for longer
+    fn env_or(name: &str, default: u64) -> u64 {
+        std::env::var(name).ok().and_then(|s| s.parse().ok()).unwrap_or(default)
+    }
+
+    #[test]
+    fn engines_agree() {
+        let seed = env_or("INTCODE_FUZZ_SEED", 2019);
+        let cases = env_or("INTCODE_FUZZ_CASES", 900) as usize;
+        match fuzz(seed, cases, &engines(), 200) {
+            Ok(checked) => assert!(checked > cases / 2),
+            Err(mismatch) => panic!("machines disagree (seed {}):\n{}", seed, mismatch),
+        }
+    }
+
+    #[test]
+    fn step_limit() {
+        // Loops forever
+        let case = Case {
+            family: Family::Day05,
+            ops: vec![CaseOp { op: Op::JumpIfTrue, operands: vec![Operand::Imm(1), Operand::Target(0)] }],
+            data: vec![0],
+            inputs: Inputs::Repeat(0),
+        };
+        assert!(is_valid(&case, 10));
+        assert!(check(&case, &engines(), 10).is_ok());
+        assert_eq!(IntCodeEngine.run(&case.image(), &case.inputs, 10).stop, Stop::StepLimit);
+    }
+
+    #[test]
+    fn single_engine() {
+        let engines: Vec<Box<dyn Engine>> = vec![Box::new(Day02Engine), Box::new(IntCodeEngine)];
+        assert!(is_comparable(Family::Day02, &engines));
+        assert!(!is_comparable(Family::Full, &engines));
+        let case = generate(&mut Rng::new(1), Family::Full);
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| check(&case, &engines, 10))).is_err());
+        assert_eq!(fuzz(1, 10, &engines[1..], 200).ok(), Some(0));
+    }
+
+    // Executes every MUL as an ADD
+    struct MulAsAdd;
+
+    impl Engine for MulAsAdd {
+        fn name(&self) -> &'static str {
+            "mul-as-add"
+        }
+
+        fn supports(&self, family: Family) -> bool {
+            family == Family::Day02
+        }
+
+        fn run(&self, program: &[i64], _inputs: &Inputs, max_steps: usize) -> Outcome {
+            let mut machine = IntCode::new(program.to_vec());
+            let stop = drive(max_steps, || {
+                let (ip, instruction) = (machine.ip(), machine.memory()[machine.ip()]);
+                if instruction == 2 {
+                    machine.poke(ip, 1).unwrap();
+                }
+                let status = machine.step();
+                if instruction == 2 && machine.memory()[ip] == 1 {
+                    machine.poke(ip, 2).unwrap();
+                }
+                status.map(|_| Stop::Halt)
+            });
+            Outcome { outputs: Vec::new(), mem: machine.memory().to_vec(), stop }
+        }
+    }
+
+    #[test]
+    fn minimize_buggy_engine() {
+        let engines: Vec<Box<dyn Engine>> = vec![Box::new(IntCodeEngine), Box::new(MulAsAdd)];
+        let mismatch = fuzz(1, 300, &engines, 200).unwrap_err();
+        assert_eq!(mismatch.case.ops.len(), 1);
+        assert_eq!(mismatch.case.ops[0].op, Op::Mul);
+        assert_eq!(mismatch.case.data.len(), 1);
+        assert_ne!(mismatch.outcomes[0].1, mismatch.outcomes[1].1);
+    }
+}
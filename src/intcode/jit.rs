@@ -0,0 +1,855 @@
+/*
+ * Native code compiler for Intcode on x86-64 Linux, enabled by the `jit` feature.
+ *
+ * Runs of instructions are translated into machine code the first time they're reached.
+ * A block only ends at an unconditional jump; conditional jumps leave it when they're taken.
+ * Blocks jump straight into each other through a table of entry points indexed by address.
+ * Anything a block can't handle makes it return to `Jit`, and the `IntCode` interpreter then
+ * executes that instruction. This covers input, output, halting, and anything that would
+ * panic in the interpreter, such as an out-of-bounds access or an overflow. That way the
+ * results are the same down to the panics.
+ *
+ * Every write checks whether it lands on compiled code. If it does, the block leaves before
+ * writing, and the interpreter performs the write and throws away the blocks covering that address.
+ */
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::mem;
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::ptr;
+
+use crate::intcode::IntCode;
+use crate::intcode::Status;
+use crate::intcode::disasm::{Instruction, Mode, Op, Param};
+
+const CODE_SIZE: usize = 4 << 20;
+// Longest block, in instructions
+const MAX_BLOCK: usize = 64;
+// More than the machine code for any block, exit stubs included
+const MAX_BLOCK_CODE: usize = MAX_BLOCK * 200 + 64;
+// Positions are compiled into 32-bit displacements
+const MAX_MEMORY: usize = 1 << 28;
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const PROT_EXEC: c_int = 4;
+const MAP_PRIVATE: c_int = 2;
+const MAP_ANONYMOUS: c_int = 0x20;
+
+extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RDX: u8 = 2;
+const RBX: u8 = 3;
+const RSI: u8 = 6;
+const RDI: u8 = 7;
+const R8: u8 = 8;
+const R9: u8 = 9;
+const R10: u8 = 10;
+const R11: u8 = 11;
+
+// Registers while running compiled code; RAX, RBX and R11 are scratch
+const MEM: u8 = RDI;
+const STATE: u8 = RSI;
+const LEN: u8 = RDX;
+const CODE_MAP: u8 = RCX;
+const BUDGET: u8 = R8;
+const BASE: u8 = R9;
+const ENTRIES: u8 = R10;
+
+// Condition codes
+const OVERFLOW: u8 = 0x0;
+const BELOW: u8 = 0x2;
+const ABOVE_OR_EQUAL: u8 = 0x3;
+const EQUAL: u8 = 0x4;
+const NOT_EQUAL: u8 = 0x5;
+const LESS: u8 = 0xC;
+
+// Shared with the compiled code, which uses these offsets
+#[repr(C)]
+struct State {
+    mem: *mut i64,       // 0
+    len: u64,            // 8
+    code_map: *const u8, // 16
+    entries: *const u64, // 24
+    budget: u64,         // 32
+    base: i64,           // 40
+    ip: u64,             // 48
+}
+
+type Trampoline = unsafe extern "sysv64" fn(*mut State, *const u8);
+
+#[derive(Clone, Copy)]
+enum Rm {
+    Reg(u8),
+    // [base + disp]
+    Disp(u8, i32),
+    // [base + index << scale]
+    Index(u8, u8, u8),
+}
+
+/*
+ * Just enough of an x86-64 assembler for the code below.
+ */
+struct Asm {
+    // Offset of the code within the code buffer, for jumps to the common exit
+    origin: usize,
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn new(origin: usize) -> Asm {
+        Asm { origin, code: Vec::new() }
+    }
+
+    fn pos(&self) -> usize {
+        self.origin + self.code.len()
+    }
+
+    fn op(&mut self, wide: bool, opcode: &[u8], reg: u8, rm: Rm) {
+        let (index, base) = match rm {
+            Rm::Reg(r) => (0, r),
+            Rm::Disp(base, _) => (0, base),
+            Rm::Index(base, index, _) => (index, base),
+        };
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3) << 2 | (index >> 3) << 1 | base >> 3;
+        if rex != 0x40 {
+            self.code.push(rex);
+        }
+        self.code.extend_from_slice(opcode);
+        let reg = (reg & 7) << 3;
+        match rm {
+            Rm::Reg(r) => self.code.push(0xC0 | reg | (r & 7)),
+            Rm::Disp(base, disp) => {
+                self.code.push(0x80 | reg | (base & 7));
+                if base & 7 == 4 {
+                    self.code.push(0x24);
+                }
+                self.code.extend_from_slice(&disp.to_le_bytes());
+            }
+            Rm::Index(base, index, scale) => {
+                // RBP and R13 can only be a base with a displacement
+                let disp8 = base & 7 == 5;
+                self.code.push(if disp8 { 0x44 } else { 0x04 } | reg);
+                self.code.push(scale << 6 | (index & 7) << 3 | (base & 7));
+                if disp8 {
+                    self.code.push(0);
+                }
+            }
+        }
+    }
+
+    fn load(&mut self, dst: u8, src: Rm) {
+        self.op(true, &[0x8B], dst, src);
+    }
+
+    fn store(&mut self, dst: Rm, src: u8) {
+        self.op(true, &[0x89], src, dst);
+    }
+
+    fn mov(&mut self, dst: u8, src: u8) {
+        self.op(true, &[0x89], src, Rm::Reg(dst));
+    }
+
+    fn mov_imm(&mut self, dst: u8, value: i64) {
+        self.code.push(0x48 | dst >> 3);
+        self.code.push(0xB8 | (dst & 7));
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn add(&mut self, dst: u8, src: u8) {
+        self.op(true, &[0x01], src, Rm::Reg(dst));
+    }
+
+    fn imul(&mut self, dst: u8, src: u8) {
+        self.op(true, &[0x0F, 0xAF], dst, Rm::Reg(src));
+    }
+
+    fn cmp(&mut self, a: u8, b: u8) {
+        self.op(true, &[0x39], b, Rm::Reg(a));
+    }
+
+    fn test(&mut self, a: u8, b: u8) {
+        self.op(true, &[0x85], b, Rm::Reg(a));
+    }
+
+    // ADD, SUB or CMP with an immediate, picked by `ext`
+    fn alu_imm(&mut self, ext: u8, dst: u8, value: i32) {
+        self.op(true, &[0x81], ext, Rm::Reg(dst));
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn add_imm(&mut self, dst: u8, value: i32) {
+        self.alu_imm(0, dst, value);
+    }
+
+    fn sub_imm(&mut self, dst: u8, value: i32) {
+        self.alu_imm(5, dst, value);
+    }
+
+    fn cmp_imm(&mut self, dst: u8, value: i32) {
+        self.alu_imm(7, dst, value);
+    }
+
+    fn cmp_byte_zero(&mut self, rm: Rm) {
+        self.op(false, &[0x80], 7, rm);
+        self.code.push(0);
+    }
+
+    // Sets RAX to 1 if the condition holds, 0 otherwise
+    fn set_rax(&mut self, cc: u8) {
+        self.code.extend_from_slice(&[0x0F, 0x90 | cc, 0xC0, 0x0F, 0xB6, 0xC0]);
+    }
+
+    // Jumps return where to patch in the target
+    fn jcc(&mut self, cc: u8) -> usize {
+        self.code.extend_from_slice(&[0x0F, 0x80 | cc, 0, 0, 0, 0]);
+        self.code.len() - 4
+    }
+
+    fn jmp(&mut self) -> usize {
+        self.code.extend_from_slice(&[0xE9, 0, 0, 0, 0]);
+        self.code.len() - 4
+    }
+
+    fn jmp_reg(&mut self, r: u8) {
+        self.op(false, &[0xFF], 4, Rm::Reg(r));
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        let rel = target as i64 - (self.origin + at + 4) as i64;
+        self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+    }
+}
+
+/*
+ * Executable memory, writable only while code is being added.
+ */
+struct CodeBuffer {
+    ptr: *mut u8,
+}
+
+impl CodeBuffer {
+    fn new() -> CodeBuffer {
+        let ptr = unsafe {
+            mmap(ptr::null_mut(), CODE_SIZE, PROT_READ | PROT_EXEC, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0)
+        };
+        if ptr as isize == -1 {
+            panic!("Could not allocate executable memory");
+        }
+        CodeBuffer { ptr: ptr as *mut u8 }
+    }
+
+    fn write(&mut self, at: usize, code: &[u8]) {
+        assert!(at + code.len() <= CODE_SIZE);
+        unsafe {
+            assert_eq!(mprotect(self.ptr as *mut c_void, CODE_SIZE, PROT_READ | PROT_WRITE), 0);
+            ptr::copy_nonoverlapping(code.as_ptr(), self.ptr.add(at), code.len());
+            assert_eq!(mprotect(self.ptr as *mut c_void, CODE_SIZE, PROT_READ | PROT_EXEC), 0);
+        }
+    }
+
+    fn at(&self, offset: usize) -> *const u8 {
+        unsafe { self.ptr.add(offset) }
+    }
+}
+
+impl Drop for CodeBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.ptr as *mut c_void, CODE_SIZE);
+        }
+    }
+}
+
+/*
+ * Assembles the code that enters a block and the exit all blocks return through.
+ * Returns the code and the offset of the exit.
+ */
+fn trampoline() -> (Vec<u8>, usize) {
+    let mut asm = Asm::new(0);
+    asm.code.push(0x53); // push rbx
+    asm.mov(RAX, RSI);
+    asm.mov(STATE, RDI);
+    asm.load(MEM, Rm::Disp(STATE, 0));
+    asm.load(LEN, Rm::Disp(STATE, 8));
+    asm.load(CODE_MAP, Rm::Disp(STATE, 16));
+    asm.load(ENTRIES, Rm::Disp(STATE, 24));
+    asm.load(BUDGET, Rm::Disp(STATE, 32));
+    asm.load(BASE, Rm::Disp(STATE, 40));
+    asm.jmp_reg(RAX);
+
+    // Blocks leave with the address to continue at in RAX
+    let exit = asm.pos();
+    asm.store(Rm::Disp(STATE, 32), BUDGET);
+    asm.store(Rm::Disp(STATE, 40), BASE);
+    asm.store(Rm::Disp(STATE, 48), RAX);
+    asm.code.push(0x5B); // pop rbx
+    asm.code.push(0xC3); // ret
+    (asm.code, exit)
+}
+
+/*
+ * Finds the instructions of the block starting at `start`, stopping at the first one that
+ * can't be compiled or after an unconditional jump.
+ */
+fn find_block(mem: &[i64], start: usize) -> Vec<Instruction> {
+    let mut block = Vec::new();
+    let mut addr = start;
+    while block.len() < MAX_BLOCK {
+        let instruction = match Instruction::decode(mem, addr) {
+            Some(instruction) => instruction,
+            None => break,
+        };
+        let compiled = match instruction.op {
+            Op::Input | Op::Output | Op::Halt => false,
+            _ => instruction.params.iter().all(|param| match param.mode {
+                Mode::Immediate => true,
+                Mode::Position => (param.value as usize) < mem.len(),
+                Mode::Relative => i32::try_from(param.value).is_ok(),
+            }),
+        };
+        if !compiled {
+            break;
+        }
+        let condition = instruction.params.first().filter(|param| param.mode == Mode::Immediate);
+        let unconditional = match instruction.op {
+            Op::JumpIfTrue => condition.is_some_and(|param| param.value != 0),
+            Op::JumpIfFalse => condition.is_some_and(|param| param.value == 0),
+            _ => false,
+        };
+        addr = instruction.next();
+        block.push(instruction);
+        if unconditional {
+            break;
+        }
+    }
+    block
+}
+
+struct Block {
+    asm: Asm,
+    exit: usize,
+    size: usize,
+    // Jumps to stubs that leave at an address, refunding part of the budget
+    stubs: BTreeMap<(usize, usize), Vec<usize>>,
+}
+
+impl Block {
+    // Jumps to a stub leaving at the instruction `index`, before executing it
+    fn fault(&mut self, cc: u8, ip: usize, index: usize) {
+        let at = self.asm.jcc(cc);
+        self.stubs.entry((ip, self.size - index)).or_default().push(at);
+    }
+
+    /*
+     * Computes the address of a relative parameter into R11, leaving if it's out of bounds.
+     */
+    fn relative(&mut self, param: Param, ip: usize, index: usize) {
+        self.asm.mov(R11, BASE);
+        self.asm.add_imm(R11, param.value as i32);
+        self.fault(OVERFLOW, ip, index);
+        self.asm.cmp(R11, LEN);
+        self.fault(ABOVE_OR_EQUAL, ip, index);
+    }
+
+    fn read(&mut self, param: Param, dst: u8, ip: usize, index: usize) {
+        match param.mode {
+            Mode::Immediate => self.asm.mov_imm(dst, param.value),
+            Mode::Position => self.asm.load(dst, Rm::Disp(MEM, param.value as i32 * 8)),
+            Mode::Relative => {
+                self.relative(param, ip, index);
+                self.asm.load(dst, Rm::Index(MEM, R11, 3));
+            }
+        }
+    }
+
+    // Writes RAX, unless the cell is compiled code
+    fn write(&mut self, param: Param, ip: usize, index: usize) {
+        match param.mode {
+            Mode::Position => {
+                self.asm.cmp_byte_zero(Rm::Disp(CODE_MAP, param.value as i32));
+                self.fault(NOT_EQUAL, ip, index);
+                self.asm.store(Rm::Disp(MEM, param.value as i32 * 8), RAX);
+            }
+            Mode::Relative => {
+                self.relative(param, ip, index);
+                self.asm.cmp_byte_zero(Rm::Index(CODE_MAP, R11, 0));
+                self.fault(NOT_EQUAL, ip, index);
+                self.asm.store(Rm::Index(MEM, R11, 3), RAX);
+            }
+            Mode::Immediate => unreachable!(),
+        }
+    }
+
+    /*
+     * Continues at the address in RAX, directly if it has been compiled.
+     */
+    fn dispatch(&mut self) {
+        self.asm.cmp(RAX, LEN);
+        let out_of_bounds = self.asm.jcc(ABOVE_OR_EQUAL);
+        self.asm.load(R11, Rm::Index(ENTRIES, RAX, 3));
+        self.asm.test(R11, R11);
+        let not_compiled = self.asm.jcc(EQUAL);
+        self.asm.jmp_reg(R11);
+        self.asm.patch(out_of_bounds, self.exit);
+        self.asm.patch(not_compiled, self.exit);
+    }
+}
+
+/*
+ * Compiles a block to be placed at `origin` in the code buffer.
+ * It charges the budget for all of its instructions on entry and refunds the ones it didn't get to.
+ */
+fn assemble(instructions: &[Instruction], origin: usize, exit: usize) -> Vec<u8> {
+    let size = instructions.len();
+    let mut block = Block { asm: Asm::new(origin), exit, size, stubs: BTreeMap::new() };
+    let start = instructions[0].addr;
+    block.asm.cmp_imm(BUDGET, size as i32);
+    let too_few = block.asm.jcc(BELOW);
+    block.stubs.entry((start, 0)).or_default().push(too_few);
+    block.asm.sub_imm(BUDGET, size as i32);
+
+    let mut falls_through = true;
+    for (index, instruction) in instructions.iter().enumerate() {
+        let (ip, params) = (instruction.addr, &instruction.params);
+        match instruction.op {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+                block.read(params[0], RAX, ip, index);
+                block.read(params[1], RBX, ip, index);
+                match instruction.op {
+                    Op::Add => {
+                        block.asm.add(RAX, RBX);
+                        block.fault(OVERFLOW, ip, index);
+                    }
+                    Op::Mul => {
+                        block.asm.imul(RAX, RBX);
+                        block.fault(OVERFLOW, ip, index);
+                    }
+                    Op::LessThan => {
+                        block.asm.cmp(RAX, RBX);
+                        block.asm.set_rax(LESS);
+                    }
+                    _ => {
+                        block.asm.cmp(RAX, RBX);
+                        block.asm.set_rax(EQUAL);
+                    }
+                }
+                block.write(params[2], ip, index);
+            }
+            Op::AdjustBase => {
+                block.read(params[0], RAX, ip, index);
+                block.asm.mov(RBX, BASE);
+                block.asm.add(RBX, RAX);
+                block.fault(OVERFLOW, ip, index);
+                block.asm.mov(BASE, RBX);
+            }
+            Op::JumpIfTrue | Op::JumpIfFalse => {
+                let jump_if_true = instruction.op == Op::JumpIfTrue;
+                let not_taken = if params[0].mode == Mode::Immediate {
+                    if (params[0].value != 0) != jump_if_true {
+                        continue;
+                    }
+                    falls_through = false;
+                    None
+                } else {
+                    block.read(params[0], RAX, ip, index);
+                    block.asm.test(RAX, RAX);
+                    Some(block.asm.jcc(if jump_if_true { EQUAL } else { NOT_EQUAL }))
+                };
+                block.read(params[1], RAX, ip, index);
+                let refund = size - index - 1;
+                if refund > 0 {
+                    block.asm.add_imm(BUDGET, refund as i32);
+                }
+                block.dispatch();
+                if let Some(at) = not_taken {
+                    let here = block.asm.pos();
+                    block.asm.patch(at, here);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    if falls_through {
+        let next = instructions[size - 1].next();
+        block.asm.mov_imm(RAX, next as i64);
+        block.dispatch();
+    }
+
+    let Block { mut asm, stubs, .. } = block;
+    for ((ip, refund), jumps) in stubs {
+        let here = asm.pos();
+        for at in jumps {
+            asm.patch(at, here);
+        }
+        asm.mov_imm(RAX, ip as i64);
+        if refund > 0 {
+            asm.add_imm(BUDGET, refund as i32);
+        }
+        let at = asm.jmp();
+        asm.patch(at, exit);
+    }
+    asm.code
+}
+
+/*
+ * The compiled blocks, by the addresses they start at and cover.
+ */
+#[derive(Default)]
+struct Blocks {
+    // Entry point of the block starting at each address, or 0
+    entries: Vec<u64>,
+    // Whether each cell is part of a block
+    code_map: Vec<u8>,
+    // Cells covered by each block, with what they held when it was compiled
+    ranges: Vec<(Range<usize>, Vec<i64>)>,
+    invalidated: usize,
+}
+
+impl Blocks {
+    fn clear(&mut self, size: usize) {
+        self.entries = vec![0; size];
+        self.code_map = vec![0; size];
+        self.ranges.clear();
+    }
+
+    fn add(&mut self, mem: &[i64], range: Range<usize>, entry: *const u8) {
+        self.entries[range.start] = entry as u64;
+        for cell in &mut self.code_map[range.clone()] {
+            *cell = 1;
+        }
+        self.ranges.push((range.clone(), mem[range].to_vec()));
+    }
+
+    fn remove<F: Fn(&Range<usize>, &[i64]) -> bool>(&mut self, stale: F) {
+        let Blocks { entries, ranges, invalidated, .. } = self;
+        ranges.retain(|(range, cells)| {
+            if stale(range, cells) {
+                entries[range.start] = 0;
+                *invalidated += 1;
+                false
+            } else {
+                true
+            }
+        });
+        for cell in &mut self.code_map {
+            *cell = 0;
+        }
+        for (range, _) in &self.ranges {
+            for cell in &mut self.code_map[range.clone()] {
+                *cell = 1;
+            }
+        }
+    }
+
+    /*
+     * Throws away the blocks covering a cell that was written to.
+     */
+    fn written(&mut self, addr: usize) {
+        if self.code_map.get(addr) == Some(&1) {
+            self.remove(|range, _| range.contains(&addr));
+        }
+    }
+}
+
+/*
+ * An IntCode machine that runs compiled code where it can.
+ */
+pub struct Jit {
+    machine: IntCode,
+    code: CodeBuffer,
+    // Bytes of the code buffer in use
+    used: usize,
+    // Offset of the exit blocks return through
+    exit: usize,
+    trampoline_size: usize,
+    blocks: Blocks,
+    compiled: usize,
+}
+
+impl Jit {
+    pub fn new(machine: IntCode) -> Jit {
+        let mut code = CodeBuffer::new();
+        let (trampoline, exit) = trampoline();
+        code.write(0, &trampoline);
+        let mut jit = Jit {
+            machine,
+            code,
+            used: trampoline.len(),
+            exit,
+            trampoline_size: trampoline.len(),
+            blocks: Blocks::default(),
+            compiled: 0,
+        };
+        jit.flush();
+        jit
+    }
+
+    pub fn machine(&self) -> &IntCode {
+        &self.machine
+    }
+
+    /*
+     * Gives access to the machine, throwing away all compiled code in case its memory is changed.
+     */
+    pub fn machine_mut(&mut self) -> &mut IntCode {
+        self.flush();
+        &mut self.machine
+    }
+
+    pub fn into_inner(self) -> IntCode {
+        self.machine
+    }
+
+    /*
+     * Same as IntCode::reset(), keeping the compiled code that still matches the program.
+     */
+    pub fn reset(&mut self) {
+        self.machine.reset();
+        let mem = &self.machine.mem;
+        self.blocks.remove(|range, cells| mem.get(range.clone()) != Some(cells));
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.machine.push_input(value);
+    }
+
+    pub fn extend_input<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        self.machine.extend_input(values);
+    }
+
+    pub fn push_ascii(&mut self, s: &str) {
+        self.machine.push_ascii(s);
+    }
+
+    /*
+     * Number of blocks compiled so far, and how many of them were thrown away after the program wrote to them.
+     */
+    pub fn stats(&self) -> (usize, usize) {
+        (self.compiled, self.blocks.invalidated)
+    }
+
+    fn flush(&mut self) {
+        self.used = self.trampoline_size;
+        self.blocks.clear(self.machine.mem.len());
+    }
+
+    fn compile(&mut self, ip: usize) -> Option<*const u8> {
+        if self.machine.mem.len() > MAX_MEMORY {
+            return None;
+        }
+        let instructions = find_block(&self.machine.mem, ip);
+        if instructions.is_empty() {
+            return None;
+        }
+        if self.used + MAX_BLOCK_CODE > CODE_SIZE {
+            self.flush();
+        }
+        let code = assemble(&instructions, self.used, self.exit);
+        assert!(code.len() <= MAX_BLOCK_CODE);
+        self.code.write(self.used, &code);
+        let entry = self.code.at(self.used);
+        self.used += code.len();
+        self.blocks.add(&self.machine.mem, ip..instructions[instructions.len() - 1].next(), entry);
+        self.compiled += 1;
+        Some(entry)
+    }
+
+    fn run(&mut self, entry: *const u8, budget: &mut usize) {
+        let mut state = State {
+            mem: self.machine.mem.as_mut_ptr(),
+            len: self.machine.mem.len() as u64,
+            code_map: self.blocks.code_map.as_ptr(),
+            entries: self.blocks.entries.as_ptr(),
+            budget: *budget as u64,
+            base: self.machine.relative_base,
+            ip: 0,
+        };
+        unsafe {
+            let trampoline: Trampoline = mem::transmute(self.code.at(0));
+            trampoline(&mut state, entry);
+        }
+        self.machine.ip = state.ip as usize;
+        self.machine.relative_base = state.base;
+        *budget = state.budget as usize;
+    }
+
+    /*
+     * Executes a single instruction in the interpreter.
+     */
+    pub fn step(&mut self) -> Option<Status> {
+        let Jit { machine, blocks, .. } = self;
+        machine.step_with(|addr| blocks.written(addr))
+    }
+
+    pub fn execute(&mut self) -> Status {
+        loop {
+            let mut budget = usize::MAX;
+            if let Some(status) = self.execute_limited(&mut budget) {
+                return status;
+            }
+        }
+    }
+
+    /*
+     * Same as IntCode::execute_limited(), counting the instructions run by compiled code as well.
+     */
+    pub fn execute_limited(&mut self, budget: &mut usize) -> Option<Status> {
+        if self.blocks.entries.len() != self.machine.mem.len() {
+            self.flush();
+        }
+        loop {
+            if *budget == 0 {
+                return None;
+            }
+            let ip = self.machine.ip;
+            let entry = match self.blocks.entries.get(ip) {
+                Some(0) => self.compile(ip),
+                Some(entry) => Some(*entry as *const u8),
+                None => None,
+            };
+            if let Some(entry) = entry {
+                let before = *budget;
+                self.run(entry, budget);
+                if *budget != before {
+                    continue;
+                }
+            }
+            // The block stopped before its first instruction, so let the interpreter handle it
+            match self.step() {
+                None => *budget -= 1,
+                Some(Status::Output(n)) => {
+                    *budget -= 1;
+                    return Some(Status::Output(n));
+                }
+                Some(status) => return Some(status),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+    use crate::intcode::compiler;
+
+    // Runs a program to completion on both machines, checking they agree
+    fn compare(program: &[i64], inputs: &[i64], memory: usize) -> (Vec<i64>, Jit) {
+        let mut machine = IntCode::new(program.to_vec());
+        machine.resize(memory.max(program.len()));
+        machine.extend_input(inputs.iter().cloned());
+        let mut jit = Jit::new(machine.clone());
+
+        let mut outputs = Vec::new();
+        loop {
+            match (machine.execute(), jit.execute()) {
+                (Status::Output(a), Status::Output(b)) if a == b => outputs.push(a),
+                (Status::Halt, Status::Halt) | (Status::WaitingInput, Status::WaitingInput) => break,
+                _ => panic!("The machines disagree"),
+            }
+        }
+        assert_eq!(jit.machine().memory(), machine.memory());
+        assert_eq!(jit.machine().relative_base(), machine.relative_base());
+        (outputs, jit)
+    }
+
+    #[test]
+    fn examples() {
+        let quine = intcode::parse_program("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99").unwrap();
+        assert_eq!(compare(&quine, &[], 200).0, quine);
+        let big = intcode::parse_program("1102,34915192,34915192,7,4,7,99,0").unwrap();
+        assert_eq!(compare(&big, &[], 0).0, [1219070632396864]);
+
+        // Outputs 999, 1000 or 1001 for inputs below, equal to or above 8
+        let compare8 = intcode::parse_program("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99").unwrap();
+        for (input, output) in &[(7, 999), (8, 1000), (9, 1001)] {
+            assert_eq!(compare(&compare8, &[*input], 0).0, [*output]);
+        }
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Array accesses patch the instructions that do them
+        let program = compiler::compile("
+            var primes[100];
+            fn main() {
+                var count = 0;
+                var n = 2;
+                while (count < 30) {
+                    var i = 0;
+                    var prime = 1;
+                    while (i < count) {
+                        if (n % primes[i] == 0) { prime = 0; }
+                        i = i + 1;
+                    }
+                    if (prime) { primes[count] = n; count = count + 1; output(n); }
+                    n = n + 1;
+                }
+            }").unwrap();
+        let (outputs, jit) = compare(&program, &[], program.len() + 1000);
+        assert_eq!(outputs.len(), 30);
+        assert_eq!(outputs[29], 113);
+        let (compiled, invalidated) = jit.stats();
+        assert!(compiled > 0 && invalidated > 0);
+    }
+
+    #[test]
+    fn reset() {
+        // Given a non-zero input, patches the ADD at 9 to output 7 instead of 0
+        let program = intcode::parse_program("3,20,1006,20,9,1101,7,0,11,1101,0,0,21,4,21,99").unwrap();
+        let mut machine = IntCode::new(program);
+        machine.resize(22);
+        let mut jit = Jit::new(machine);
+        jit.push_input(1);
+        assert!(matches!(jit.execute(), Status::Output(7)));
+        assert!(matches!(jit.execute(), Status::Halt));
+
+        // The block compiled from the patched ADD must not survive the reset
+        jit.reset();
+        jit.push_input(0);
+        assert!(matches!(jit.execute(), Status::Output(0)));
+        jit.reset();
+        jit.push_input(1);
+        assert!(matches!(jit.execute(), Status::Output(7)));
+    }
+
+    #[test]
+    fn execute_limited() {
+        // Counts down from 3, outputting every value, then halts
+        let program = intcode::parse_program("104,3,1001,1,-1,1,1005,1,0,99").unwrap();
+        let mut jit = Jit::new(IntCode::new(program.clone()));
+        let mut budget = 100;
+        let mut outputs = Vec::new();
+        while let Some(Status::Output(n)) = jit.execute_limited(&mut budget) {
+            outputs.push(n);
+        }
+        assert_eq!(outputs, [3, 2, 1]);
+        assert_eq!(budget, 100 - 9);
+
+        let mut jit = Jit::new(IntCode::new(program));
+        let mut budget = 4;
+        assert!(matches!(jit.execute_limited(&mut budget), Some(Status::Output(3))));
+        assert!(matches!(jit.execute_limited(&mut budget), Some(Status::Output(2))));
+        assert!(jit.execute_limited(&mut budget).is_none());
+        assert_eq!(budget, 0);
+    }
+
+    #[test]
+    fn crashes() {
+        // Jumps to the relative read at 5 with a base that puts it out of bounds
+        let mut jit = Jit::new(IntCode::new(intcode::parse_program("109,-5,1105,1,5,1201,0,0,0,99").unwrap()));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| jit.execute()));
+        assert!(result.is_err());
+        assert_eq!(jit.machine().ip(), 5);
+    }
+}
@@ -0,0 +1,176 @@
+/*
+ * Comparing memory between snapshots of a machine.
+ *
+ * `diff` lists the cells that changed between two snapshots, while `Scanner` works like a game
+ * trainer: start with every address as a candidate, then keep only the ones whose values
+ * behave as expected as the program runs, e.g. "equals the score that was just output",
+ * or "went down after losing a life".
+ */
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::intcode::IntCode;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub addr: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Equal(i64),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    // Changed by exactly this much
+    ChangedBy(i64),
+}
+
+pub struct Scanner {
+    candidates: Vec<usize>,
+    last: Vec<i64>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}: {} -> {}", self.addr, self.old, self.new)
+    }
+}
+
+fn cell(mem: &[i64], addr: usize) -> i64 {
+    mem.get(addr).cloned().unwrap_or(0)
+}
+
+/*
+ * Lists the cells that differ between two memories. Cells past the end of the shorter one count as zero.
+ */
+pub fn diff_mem(old: &[i64], new: &[i64]) -> Vec<Change> {
+    (0..old.len().max(new.len()))
+        .map(|addr| Change { addr, old: cell(old, addr), new: cell(new, addr) })
+        .filter(|change| change.old != change.new)
+        .collect()
+}
+
+pub fn diff(old: &IntCode, new: &IntCode) -> Vec<Change> {
+    diff_mem(old.memory(), new.memory())
+}
+
+/*
+ * Groups changes into runs of consecutive addresses.
+ */
+pub fn ranges(changes: &[Change]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for change in changes {
+        match ranges.last_mut() {
+            Some(range) if range.end == change.addr => range.end += 1,
+            _ => ranges.push(change.addr..change.addr + 1),
+        }
+    }
+    ranges
+}
+
+impl Filter {
+    fn matches(self, old: i64, new: i64) -> bool {
+        match self {
+            Filter::Equal(n) => new == n,
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::ChangedBy(n) => new.checked_sub(old) == Some(n),
+        }
+    }
+}
+
+impl Scanner {
+    /*
+     * Starts with every address of the machine's memory as a candidate.
+     */
+    pub fn new(machine: &IntCode) -> Scanner {
+        Scanner {
+            candidates: (0..machine.memory_size()).collect(),
+            last: machine.memory().to_vec(),
+        }
+    }
+
+    /*
+     * Keeps the candidates whose value in `machine` passes the filter, compared to the previous snapshot.
+     */
+    pub fn scan(&mut self, machine: &IntCode, filter: Filter) -> &[usize] {
+        let last = &self.last;
+        self.candidates.retain(|&addr| filter.matches(cell(last, addr), cell(machine.memory(), addr)));
+        self.last = machine.memory().to_vec();
+        &self.candidates
+    }
+
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Status;
+    use crate::intcode::compiler;
+
+    #[test]
+    fn diff() {
+        let old = IntCode::new(vec![1, 2, 3, 4, 5]);
+        let mut new = old.clone();
+        new.resize(6);
+        for &(addr, value) in &[(1, 7), (2, 8), (4, 0), (5, 6)] {
+            new.poke(addr, value).unwrap();
+        }
+        let changes = super::diff(&old, &new);
+        assert_eq!(changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+                   ["    1: 2 -> 7", "    2: 3 -> 8", "    4: 5 -> 0", "    5: 0 -> 6"]);
+        assert_eq!(ranges(&changes), [1..3, 4..6]);
+    }
+
+    #[test]
+    fn scanner() {
+        // Scores 10 points for every 1, and loses a life for anything else
+        let program = compiler::compile("
+            var score = 0;
+            var lives = 3;
+            fn main() {
+                while (lives > 0) {
+                    if (input() == 1) { score = score + 10; } else { lives = lives - 1; }
+                    output(score);
+                }
+            }").unwrap();
+        let mut game = IntCode::new(program.clone());
+        game.resize(program.len() + 100);
+        let play = |game: &mut IntCode, key| {
+            game.push_input(key);
+            match game.execute() {
+                Status::Output(score) => score,
+                _ => panic!("Was expecting the score"),
+            }
+        };
+
+        let mut score = Scanner::new(&game);
+        let mut lives = Scanner::new(&game);
+        for key in &[1, 1, 0, 1, 0] {
+            let before = game.clone();
+            let points = play(&mut game, *key);
+            let changes = super::diff(&before, &game);
+            score.scan(&game, Filter::Equal(points));
+            if *key == 1 {
+                assert!(changes.iter().any(|c| c.new == points && c.old == points - 10));
+                lives.scan(&game, Filter::Unchanged);
+            } else {
+                lives.scan(&game, Filter::ChangedBy(-1));
+            }
+        }
+        assert_eq!(score.candidates().len(), 1);
+        assert_eq!(game.memory()[score.candidates()[0]], 30);
+        assert_eq!(lives.candidates().len(), 1);
+        assert_eq!(game.memory()[lives.candidates()[0]], 1);
+    }
+}
@@ -0,0 +1,601 @@
+/*
+ * Peephole optimizer for Intcode programs.
+ *
+ * optimize() keeps every address where it was, so programs that use their own image
+ * as data keep working. It folds arithmetic on immediates, threads jumps that land on
+ * other unconditional jumps, turns jumps to a halt into a halt, and reports the dead code
+ * found after unconditional jumps. compact() then removes that dead code,
+ * returning a relocation map from old to new addresses, and relocating the operands
+ * it can tell are addresses.
+ *
+ * Instructions that the program writes to, reads as data or that overlap other reachable
+ * instructions are left alone. Accesses through the relative base or through patched
+ * operands are assumed to stay out of the code, which holds for stack-based programs
+ * like the puzzle inputs and the ones from intcode::compiler.
+ */
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::panic;
+
+use crate::intcode::IntCode;
+use crate::intcode::Status;
+use crate::intcode::disasm::Instruction;
+use crate::intcode::disasm::Mode;
+use crate::intcode::disasm::Op;
+use crate::intcode::disasm::Param;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Optimized {
+    pub program: Vec<i64>,
+    // Number of arithmetic instructions on immediates that were folded into a copy
+    pub folded: usize,
+    // Number of jumps that were retargeted or turned into halts
+    pub threaded: usize,
+    pub dead: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Compacted {
+    pub program: Vec<i64>,
+    // New address of every cell of the original program, or None if it was removed
+    pub relocations: Vec<Option<usize>>,
+}
+
+/*
+ * What static analysis can tell about a program.
+ */
+struct Analysis {
+    // Reachable instructions, indexed by address
+    code: Vec<Option<Instruction>>,
+    // Number of reachable instructions covering each cell
+    coverage: Vec<usize>,
+    // Cells that some instruction writes to
+    written: Vec<bool>,
+    // Cells that some instruction reads as data
+    read: Vec<bool>,
+}
+
+fn immediate(param: &Param) -> Option<i64> {
+    match param.mode {
+        Mode::Immediate => Some(param.value),
+        _ => None,
+    }
+}
+
+/*
+ * Returns Some(target) for a jump that is always taken, with None as the target if it is dynamic.
+ */
+fn unconditional_target(instruction: &Instruction) -> Option<Option<i64>> {
+    let condition = immediate(instruction.params.first()?)?;
+    let taken = match instruction.op {
+        Op::JumpIfTrue => condition != 0,
+        Op::JumpIfFalse => condition == 0,
+        _ => false,
+    };
+    if taken { Some(immediate(&instruction.params[1])) } else { None }
+}
+
+fn ends_block(instruction: &Instruction) -> bool {
+    instruction.op == Op::Halt || unconditional_target(instruction).is_some()
+}
+
+fn analyze(program: &[i64]) -> Analysis {
+    let len = program.len();
+    let in_range = |n: i64| n >= 0 && (n as usize) < len;
+
+    let mut code: Vec<Option<Instruction>> = vec![None; len];
+    let mut covered = vec![false; len];
+    let mut tried = vec![false; len];
+    let mut entries = vec![0];
+    let mut dynamic = false;
+    loop {
+        while let Some(addr) = entries.pop() {
+            if tried[addr] {
+                continue;
+            }
+            tried[addr] = true;
+            let instruction = match Instruction::decode(program, addr) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+
+            let never_taken = match (instruction.op, instruction.params.first().and_then(immediate)) {
+                (Op::JumpIfTrue, Some(0)) => true,
+                (Op::JumpIfFalse, Some(n)) => n != 0,
+                _ => false,
+            };
+            if let Op::JumpIfTrue | Op::JumpIfFalse = instruction.op {
+                if !never_taken {
+                    match immediate(&instruction.params[1]) {
+                        Some(target) if in_range(target) => entries.push(target as usize),
+                        Some(_) => (),
+                        None => dynamic = true,
+                    }
+                }
+            }
+            if !ends_block(&instruction) && instruction.next() < len {
+                entries.push(instruction.next());
+            }
+            covered[instruction.addr..instruction.next()].iter_mut().for_each(|c| *c = true);
+            code[addr] = Some(instruction);
+        }
+        if !dynamic {
+            break;
+        }
+
+        // A dynamic jump could go anywhere an immediate points to, such as the return
+        // addresses pushed by calls; assume it doesn't land in the middle of an instruction
+        entries = code.iter()
+            .flatten()
+            .flat_map(|instruction| instruction.params.iter().filter_map(immediate))
+            .filter(|n| in_range(*n) && !covered[*n as usize] && !tried[*n as usize])
+            .map(|n| n as usize)
+            .collect();
+        if entries.is_empty() {
+            break;
+        }
+    }
+
+    let mut coverage = vec![0; len];
+    let mut written = vec![false; len];
+    let mut read = vec![false; len];
+    for instruction in code.iter().flatten() {
+        coverage[instruction.addr..instruction.next()].iter_mut().for_each(|c| *c += 1);
+        for (i, param) in instruction.params.iter().enumerate() {
+            if param.mode != Mode::Position || !in_range(param.value) {
+                continue;
+            }
+            if instruction.op.write_param() == Some(i) {
+                written[param.value as usize] = true;
+            } else {
+                read[param.value as usize] = true;
+            }
+        }
+    }
+    Analysis { code, coverage, written, read }
+}
+
+impl Analysis {
+    /*
+     * Whether the cells of an instruction never change and belong to it alone.
+     */
+    fn is_stable(&self, instruction: &Instruction) -> bool {
+        (instruction.addr..instruction.next()).all(|cell| !self.written[cell] && self.coverage[cell] == 1)
+    }
+
+    /*
+     * Whether an instruction can be rewritten without anything noticing.
+     */
+    fn is_rewritable(&self, instruction: &Instruction) -> bool {
+        self.is_stable(instruction) && (instruction.addr..instruction.next()).all(|cell| !self.read[cell])
+    }
+
+    fn instruction_at(&self, addr: i64) -> Option<&Instruction> {
+        if addr < 0 { return None; }
+        self.code.get(addr as usize)?.as_ref()
+    }
+
+    /*
+     * Follows a chain of unconditional jumps, returning where it finally ends up.
+     */
+    fn follow(&self, mut target: i64) -> i64 {
+        let mut seen = HashSet::new();
+        while let Some(instruction) = self.instruction_at(target) {
+            if !seen.insert(target) || !self.is_stable(instruction) {
+                break;
+            }
+            match unconditional_target(instruction) {
+                Some(Some(next)) => target = next,
+                _ => break,
+            }
+        }
+        target
+    }
+
+    /*
+     * Runs of cells after unconditional jumps and halts that are never reached.
+     */
+    fn dead_code(&self, program: &[i64]) -> Vec<Range<usize>> {
+        let mut dead = Vec::new();
+        for instruction in self.code.iter().flatten() {
+            if !ends_block(instruction) {
+                continue;
+            }
+            let start = instruction.next();
+            let mut end = start;
+            while end < program.len() && self.coverage[end] == 0 {
+                match Instruction::decode(program, end) {
+                    Some(dead) if (dead.addr..dead.next()).all(|c| self.coverage[c] == 0 && !self.read[c] && !self.written[c]) => {
+                        end = dead.next();
+                    }
+                    _ => break,
+                }
+            }
+            if end > start {
+                dead.push(start..end);
+            }
+        }
+        dead.sort_by_key(|range| range.start);
+        dead
+    }
+}
+
+fn write(program: &mut [i64], instruction: &Instruction) {
+    let cells = instruction.encode();
+    program[instruction.addr..instruction.addr + cells.len()].copy_from_slice(&cells);
+}
+
+/*
+ * Applies one round of rewrites, returning the number of folds and threaded jumps.
+ */
+fn rewrite(program: &mut [i64], analysis: &Analysis) -> (usize, usize) {
+    let mut folded = 0;
+    let mut threaded = 0;
+    for instruction in analysis.code.iter().flatten() {
+        if !analysis.is_rewritable(instruction) {
+            continue;
+        }
+        let params = &instruction.params;
+        match instruction.op {
+            Op::Add | Op::Mul | Op::LessThan | Op::Equals => {
+                let (a, b) = match (immediate(&params[0]), immediate(&params[1])) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => continue,
+                };
+                let value = match instruction.op {
+                    Op::Add => a.checked_add(b),
+                    Op::Mul => a.checked_mul(b),
+                    Op::LessThan => Some((a < b) as i64),
+                    _ => Some((a == b) as i64),
+                };
+                let value = match value {
+                    Some(value) => value,
+                    None => continue, // Leave overflows to the machine
+                };
+                if instruction.op == Op::Add && b == 0 {
+                    continue; // Already a copy
+                }
+                let mut copy = instruction.clone();
+                copy.op = Op::Add;
+                copy.params[0] = Param { mode: Mode::Immediate, value };
+                copy.params[1] = Param { mode: Mode::Immediate, value: 0 };
+                write(program, &copy);
+                folded += 1;
+            }
+            Op::JumpIfTrue | Op::JumpIfFalse => {
+                let target = match immediate(&params[1]) {
+                    Some(target) => target,
+                    None => continue,
+                };
+                let end = analysis.follow(target);
+                let lands_on_halt = analysis.instruction_at(end)
+                    .is_some_and(|i| i.op == Op::Halt && analysis.is_stable(i));
+
+                if unconditional_target(instruction).is_some() && lands_on_halt {
+                    // The leftover parameters become dead code
+                    program[instruction.addr] = Op::Halt.code();
+                    threaded += 1;
+                } else if end != target {
+                    let mut jump = instruction.clone();
+                    jump.params[1].value = end;
+                    write(program, &jump);
+                    threaded += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    (folded, threaded)
+}
+
+pub fn optimize(program: &[i64]) -> Optimized {
+    let mut optimized = Optimized {
+        program: program.to_vec(),
+        folded: 0,
+        threaded: 0,
+        dead: Vec::new(),
+    };
+    loop {
+        let analysis = analyze(&optimized.program);
+        let (folded, threaded) = rewrite(&mut optimized.program, &analysis);
+        optimized.folded += folded;
+        optimized.threaded += threaded;
+        if folded + threaded == 0 {
+            optimized.dead = analysis.dead_code(&optimized.program);
+            return optimized;
+        }
+    }
+}
+
+/*
+ * For an ADD x,0 or MUL x,1 (either way round), the index of the parameter being copied.
+ */
+fn copied(instruction: &Instruction) -> Option<usize> {
+    let identity = match instruction.op {
+        Op::Add => 0,
+        Op::Mul => 1,
+        _ => return None,
+    };
+    let params = &instruction.params;
+    if immediate(&params[1]) == Some(identity) {
+        Some(0)
+    } else if immediate(&params[0]) == Some(identity) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/*
+ * Removes the dead code found by optimize(), moving everything after it.
+ *
+ * There is no way to tell addresses and plain numbers apart in general, so only operands
+ * whose use is known are relocated: position mode operands, jump targets, and immediates
+ * copied into cells that jumps go through (like return addresses) or added into the operands
+ * of other instructions (like array bases). Any other immediate or data cell is left alone,
+ * so programs that keep addresses elsewhere can break; check_equivalence() will tell.
+ */
+pub fn compact(optimized: &Optimized) -> Result<Compacted, String> {
+    let program = &optimized.program;
+    if optimized.dead.is_empty() {
+        return Ok(Compacted { program: program.clone(), relocations: (0..program.len()).map(Some).collect() });
+    }
+
+    let mut relocations = Vec::new();
+    let mut next = 0;
+    for addr in 0..program.len() {
+        if optimized.dead.iter().any(|range| range.contains(&addr)) {
+            relocations.push(None);
+        } else {
+            relocations.push(Some(next));
+            next += 1;
+        }
+    }
+    let relocate = |value: i64| -> Result<i64, String> {
+        if value < 0 || value as usize >= program.len() {
+            return Ok(value); // Scratch memory past the program doesn't move
+        }
+        match relocations[value as usize] {
+            Some(addr) => Ok(addr as i64),
+            None => Err(format!("address {} refers to dead code", value)),
+        }
+    };
+
+    let analysis = analyze(program);
+    // Cells and relative offsets that jumps take their target from
+    let mut target_cells = HashSet::new();
+    let mut target_offsets = HashSet::new();
+    for instruction in analysis.code.iter().flatten() {
+        if let Op::JumpIfTrue | Op::JumpIfFalse = instruction.op {
+            let target = &instruction.params[1];
+            match target.mode {
+                Mode::Position => target_cells.insert(target.value),
+                Mode::Relative => target_offsets.insert(target.value),
+                Mode::Immediate => false,
+            };
+        }
+    }
+    let holds_address = |param: &Param| match param.mode {
+        Mode::Position => {
+            let patches_code = param.value >= 0 && analysis.coverage.get(param.value as usize).is_some_and(|&c| c > 0);
+            target_cells.contains(&param.value) || patches_code
+        }
+        Mode::Relative => target_offsets.contains(&param.value),
+        Mode::Immediate => false,
+    };
+
+    let mut compacted = program.clone();
+    for instruction in analysis.code.iter().flatten() {
+        // Which immediate, if any, is an address being stored
+        let stored = match (instruction.op.write_param(), copied(instruction)) {
+            (Some(dest), _) if !holds_address(&instruction.params[dest]) => None,
+            (Some(_), Some(source)) => Some(source),
+            (Some(_), None) if instruction.op == Op::Add => {
+                match (immediate(&instruction.params[0]), immediate(&instruction.params[1])) {
+                    (Some(_), None) => Some(0),
+                    (None, Some(_)) => Some(1),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let mut relocated = instruction.clone();
+        for (i, param) in relocated.params.iter_mut().enumerate() {
+            let is_target = i == 1 && matches!(instruction.op, Op::JumpIfTrue | Op::JumpIfFalse);
+            match param.mode {
+                Mode::Position => param.value = relocate(param.value)?,
+                Mode::Immediate if is_target || stored == Some(i) => param.value = relocate(param.value)?,
+                _ => (),
+            }
+        }
+        write(&mut compacted, &relocated);
+    }
+
+    let program = compacted.into_iter()
+        .enumerate()
+        .filter(|(addr, _)| relocations[*addr].is_some())
+        .map(|(_, value)| value)
+        .collect();
+    Ok(Compacted { program, relocations })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Halt,
+    WaitingInput,
+    StepLimit,
+    Crash,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub outputs: Vec<i64>,
+    pub stop: Stop,
+    pub steps: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    pub inputs: Vec<i64>,
+    pub original: Run,
+    pub optimized: Run,
+}
+
+impl Comparison {
+    pub fn is_equivalent(&self) -> bool {
+        self.original.outputs == self.optimized.outputs && self.original.stop == self.optimized.stop
+    }
+}
+
+/*
+ * Runs a program until it halts, needs more input or exceeds `max_steps` instructions.
+ */
+pub fn run(program: &[i64], inputs: &[i64], memory: usize, max_steps: usize) -> Run {
+    let mut mem = program.to_vec();
+    if mem.len() < memory {
+        mem.resize(memory, 0);
+    }
+    let mut machine = IntCode::new(mem);
+    machine.extend_input(inputs.iter().cloned());
+
+    let mut budget = max_steps;
+    let mut outputs = Vec::new();
+    let stop = panic::catch_unwind(panic::AssertUnwindSafe(|| loop {
+        match machine.execute_limited(&mut budget) {
+            Some(Status::Output(n)) => outputs.push(n),
+            Some(Status::Halt) => return Stop::Halt,
+            Some(Status::WaitingInput) => return Stop::WaitingInput,
+            None => return Stop::StepLimit,
+        }
+    })).unwrap_or(Stop::Crash);
+    Run { outputs, stop, steps: max_steps - budget }
+}
+
+/*
+ * Runs both programs on every set of inputs, comparing their outputs and instruction counts.
+ * Returns the first set of inputs they disagree on as an error.
+ */
+pub fn check_equivalence(original: &[i64], optimized: &[i64], inputs: &[Vec<i64>],
+                         memory: usize, max_steps: usize) -> Result<Vec<Comparison>, Comparison> {
+    let mut comparisons = Vec::new();
+    for inputs in inputs {
+        let comparison = Comparison {
+            inputs: inputs.clone(),
+            original: run(original, inputs, memory, max_steps),
+            optimized: run(optimized, inputs, memory, max_steps),
+        };
+        if !comparison.is_equivalent() {
+            return Err(comparison);
+        }
+        comparisons.push(comparison);
+    }
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+
+    fn parse(s: &str) -> Vec<i64> {
+        intcode::parse_program(s).unwrap()
+    }
+
+    #[test]
+    fn folding() {
+        let optimized = optimize(&parse("1101,2,3,13,1102,4,5,14,1107,4,5,15,99,0,0,0"));
+        assert_eq!(optimized.program, parse("1101,5,0,13,1101,20,0,14,1101,1,0,15,99,0,0,0"));
+        assert_eq!(optimized.folded, 3);
+
+        // The second instruction overwrites the first one's parameters, so it must stay
+        let program = parse("1101,2,3,0,1101,7,0,1,4,0,99");
+        assert_eq!(optimize(&program).program, program);
+    }
+
+    #[test]
+    fn jump_threading() {
+        // 0: JZ [13], 7; 3: OUT 1; 5: HLT; 6: DATA 0; 7: JNZ 1, 10; 10: JNZ 1, 3; 13: DATA 0
+        let program = parse("1006,13,7,104,1,99,0,1105,1,10,1105,1,3,0");
+        let optimized = optimize(&program);
+        assert_eq!(optimized.program, parse("1006,13,3,104,1,99,0,1105,1,3,1105,1,3,0"));
+        assert_eq!(optimized.threaded, 2);
+
+        // A jump to a halt is a halt
+        let optimized = optimize(&parse("1106,0,5,104,1,99"));
+        assert_eq!(optimized.program, parse("99,0,5,104,1,99"));
+
+        let comparisons = check_equivalence(&program, &optimize(&program).program, &[vec![]], 100, 1000).unwrap();
+        assert_eq!(comparisons[0].original.outputs, [1]);
+        assert_eq!((comparisons[0].original.steps, comparisons[0].optimized.steps), (4, 2));
+    }
+
+    #[test]
+    fn compaction() {
+        // 0: JNZ 1, 6; 3: OUT 7 (dead); 5: HLT (dead); 6: OUT [9]; 8: HLT; 9: DATA 42
+        let optimized = optimize(&parse("1105,1,6,104,7,99,4,9,99,42"));
+        assert_eq!(optimized.dead, vec![3..6]);
+        let compacted = compact(&optimized).unwrap();
+        assert_eq!(compacted.program, parse("1105,1,3,4,6,99,42"));
+        assert_eq!(compacted.relocations[..4], [Some(0), Some(1), Some(2), None]);
+        assert_eq!(compacted.relocations[9], Some(6));
+        assert_eq!(run(&compacted.program, &[], 0, 100).outputs, [42]);
+
+        // 0: JNZ 1, 6; 3: OUT 7 (dead); 5: HLT (dead); 6: [16] = 13; 10: JNZ 1, [16]; 13: OUT 9; 15: HLT; 16: DATA 0
+        // 13 is a jump target, so it moves, but 9 is just a number even though it looks like an address
+        let optimized = optimize(&parse("1105,1,6,104,7,99,1101,13,0,16,105,1,16,104,9,99,0"));
+        assert_eq!(optimized.dead, vec![3..6]);
+        let compacted = compact(&optimized).unwrap();
+        assert_eq!(compacted.program, parse("1105,1,3,1101,10,0,13,105,1,13,104,9,99,0"));
+        assert_eq!(run(&compacted.program, &[], 0, 100).outputs, [9]);
+    }
+
+    #[test]
+    fn compact_compiled() {
+        // The code after the return is dead, and the program is full of small constants
+        let source = "
+            var table[4];
+            fn g(a) { return a + 1; output(5); }
+            fn main() {
+                table[2] = 7;
+                output(g(input()));
+                output(55);
+                output(table[2] * 3);
+            }";
+        let program = intcode::compiler::compile(source).unwrap();
+        let optimized = optimize(&program);
+        assert!(!optimized.dead.is_empty());
+        let compacted = compact(&optimized).unwrap();
+        assert!(compacted.program.len() < program.len());
+
+        let inputs: Vec<Vec<i64>> = (0..5).map(|n| vec![n]).collect();
+        let comparisons = check_equivalence(&program, &compacted.program, &inputs, 10_000, 100_000).unwrap();
+        assert_eq!(comparisons[3].optimized.outputs, [4, 55, 21]);
+    }
+
+    #[test]
+    fn compiled_programs() {
+        // The then branch jumps past the else branch, straight into the jump back to the top of the loop
+        let source = "
+            var squares[20];
+            fn main() {
+                var n = input();
+                var i = 0;
+                while (i < n) {
+                    i = i + 1;
+                    if (i % 3 == 0) { squares[i] = i * i; output(squares[i]); } else { output(0 - i); }
+                }
+            }";
+        let program = intcode::compiler::compile(source).unwrap();
+        let optimized = optimize(&program);
+        assert!(optimized.threaded > 0);
+
+        let inputs: Vec<Vec<i64>> = (0..15).map(|n| vec![n]).collect();
+        let comparisons = check_equivalence(&program, &optimized.program, &inputs, 10_000, 1_000_000).unwrap();
+        for comparison in &comparisons {
+            assert_eq!(comparison.original.stop, Stop::Halt);
+            assert!(comparison.optimized.steps <= comparison.original.steps);
+        }
+        assert!(comparisons[14].optimized.steps < comparisons[14].original.steps);
+    }
+}
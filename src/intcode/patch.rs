@@ -0,0 +1,208 @@
+/*
+ * Patch files for Intcode programs.
+ *
+ * A patch lists the cells to overwrite, one per line, optionally with the value each cell is
+ * expected to hold beforehand, so that a patch made for one program is refused by another:
+ *
+ *     # insert quarters
+ *     0: 1 -> 2
+ *     # widen the paddle to the whole bottom row
+ *     1543..1584: 3
+ *
+ * Patches are all-or-nothing: every address and expected value is checked before anything is written.
+ */
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::intcode::IntCode;
+use crate::intcode::OutOfBounds;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub addrs: Range<usize>,
+    // What every cell in the range should hold before patching
+    pub expected: Option<i64>,
+    pub value: i64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Patch {
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    Syntax { line: usize, message: String },
+    Io { path: String, message: String },
+    OutOfBounds(OutOfBounds),
+    Mismatch { addr: usize, expected: i64, found: i64 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            PatchError::Io { path, message } => write!(f, "could not read {}: {}", path, message),
+            PatchError::OutOfBounds(e) => write!(f, "{}", e),
+            PatchError::Mismatch { addr, expected, found } => {
+                write!(f, "expected {} at address {}, but found {}; the patch may be for another program", expected, addr, found)
+            }
+        }
+    }
+}
+
+impl Error for PatchError {}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.addrs.len() == 1 {
+            write!(f, "{}: ", self.addrs.start)?;
+        } else {
+            write!(f, "{}..{}: ", self.addrs.start, self.addrs.end)?;
+        }
+        if let Some(expected) = self.expected {
+            write!(f, "{} -> ", expected)?;
+        }
+        write!(f, "{}", self.value)
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_entry(line: &str) -> Result<Entry, String> {
+    let number = |s: &str| s.trim().parse::<i64>().map_err(|_| format!("invalid value `{}`", s.trim()));
+    let addr = |s: &str| s.trim().parse::<usize>().map_err(|_| format!("invalid address `{}`", s.trim()));
+
+    let (addrs, values) = match line.find(':') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => return Err("expected `address: value`".to_string()),
+    };
+    let addrs = match addrs.find("..") {
+        Some(i) => addr(&addrs[..i])?..addr(&addrs[i + 2..])?,
+        None => {
+            let addr = addr(addrs)?;
+            addr..addr + 1
+        }
+    };
+    if addrs.is_empty() {
+        return Err(format!("empty range {}..{}", addrs.start, addrs.end));
+    }
+    let (expected, value) = match values.find("->") {
+        Some(i) => (Some(number(&values[..i])?), number(&values[i + 2..])?),
+        None => (None, number(values)?),
+    };
+    Ok(Entry { addrs, expected, value })
+}
+
+impl Patch {
+    pub fn parse(text: &str) -> Result<Patch, PatchError> {
+        let mut entries = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let entry = parse_entry(line).map_err(|message| PatchError::Syntax { line: i + 1, message })?;
+            entries.push(entry);
+        }
+        Ok(Patch { entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Patch, PatchError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|e| PatchError::Io { path: path.display().to_string(), message: e.to_string() })?;
+        Patch::parse(&text)
+    }
+
+    /*
+     * Checks that the patch fits the machine's memory and that every cell holds its expected value.
+     */
+    pub fn check(&self, machine: &IntCode) -> Result<(), PatchError> {
+        for entry in &self.entries {
+            for addr in entry.addrs.clone() {
+                let found = machine.peek(addr).map_err(PatchError::OutOfBounds)?;
+                match entry.expected {
+                    Some(expected) if expected != found => return Err(PatchError::Mismatch { addr, expected, found }),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Writes the patch into the machine's memory, leaving it untouched if the patch doesn't apply.
+     * The patched cells are kept across `IntCode::reset`.
+     */
+    pub fn apply(&self, machine: &mut IntCode) -> Result<(), PatchError> {
+        self.check(machine)?;
+        for entry in &self.entries {
+            for addr in entry.addrs.clone() {
+                machine.patch(addr, entry.value).unwrap();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "
+        # Comments and blank lines are ignored
+
+        0: 1 -> 2
+        3..6: 7   # no expected value
+    ";
+
+    #[test]
+    fn parse() {
+        let patch = Patch::parse(PATCH).unwrap();
+        assert_eq!(patch.entries, [
+            Entry { addrs: 0..1, expected: Some(1), value: 2 },
+            Entry { addrs: 3..6, expected: None, value: 7 },
+        ]);
+        assert_eq!(patch.to_string(), "0: 1 -> 2\n3..6: 7\n");
+        assert_eq!(Patch::parse(&patch.to_string()), Ok(patch));
+
+        let error = |text| Patch::parse(text).unwrap_err().to_string();
+        assert_eq!(error("0: 1\n\n5 -> 3"), "line 3: expected `address: value`");
+        assert_eq!(error("x: 1"), "line 1: invalid address `x`");
+        assert_eq!(error("-1: 1"), "line 1: invalid address `-1`");
+        assert_eq!(error("1: 2 -> y"), "line 1: invalid value `y`");
+        assert_eq!(error("4..4: 0"), "line 1: empty range 4..4");
+    }
+
+    #[test]
+    fn apply() {
+        let patch = Patch::parse(PATCH).unwrap();
+        let mut machine = IntCode::new(vec![1, 0, 0, 0, 99, 0, 0]);
+        patch.apply(&mut machine).unwrap();
+        assert_eq!(machine.memory(), [2, 0, 0, 7, 7, 7, 0]);
+        machine.reset();
+        assert_eq!(machine.memory(), [2, 0, 0, 7, 7, 7, 0]);
+
+        // Nothing is written unless the whole patch applies
+        let mut machine = IntCode::new(vec![2, 0, 0, 0, 99, 0, 0]);
+        assert_eq!(patch.apply(&mut machine), Err(PatchError::Mismatch { addr: 0, expected: 1, found: 2 }));
+        let mut machine = IntCode::new(vec![1, 0, 0, 0, 99]);
+        assert_eq!(patch.apply(&mut machine), Err(PatchError::OutOfBounds(OutOfBounds { addr: 5, size: 5 })));
+        assert_eq!(machine.memory(), [1, 0, 0, 0, 99]);
+    }
+}
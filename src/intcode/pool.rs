@@ -0,0 +1,236 @@
+/*
+ * Reusable machines for programs that answer many independent queries, like day 19's drone.
+ *
+ * Instead of cloning the whole machine for every query, the pool keeps a pristine image of
+ * the program and a single machine whose writes are tracked a page at a time, so that only
+ * the pages a query touched need to be restored before the next one.
+ */
+
+use std::fmt;
+use std::panic;
+use std::thread;
+
+use crate::intcode::IntCode;
+use crate::intcode::Status;
+
+const PAGE_SIZE: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    // The program asked for more inputs than the query had
+    WaitingInput,
+    StepLimit,
+    Crash,
+}
+
+/*
+ * Anything that answers queries by running a program on a list of inputs.
+ */
+pub trait Query {
+    fn query(&mut self, inputs: &[i64]) -> Result<Vec<i64>, QueryError>;
+    /*
+     * Whether the answers depend on nothing but the inputs, so that they can be reused.
+     */
+    fn is_pure(&self) -> bool;
+}
+
+#[derive(Clone)]
+struct Worker {
+    machine: IntCode,
+    dirty: Vec<bool>,
+    dirty_pages: Vec<usize>,
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    image: Vec<i64>,
+    memory: usize,
+    max_steps: Option<usize>,
+    pure: bool,
+    worker: Worker,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::WaitingInput => write!(f, "program is waiting for more input"),
+            QueryError::StepLimit => write!(f, "step limit reached"),
+            QueryError::Crash => write!(f, "program crashed"),
+        }
+    }
+}
+
+impl Worker {
+    fn new(image: &[i64], memory: usize) -> Worker {
+        let mut mem = image.to_vec();
+        mem.resize(memory.max(image.len()), 0);
+        let num_pages = mem.len().div_ceil(PAGE_SIZE);
+        Worker {
+            machine: IntCode::new(mem),
+            dirty: vec![false; num_pages],
+            dirty_pages: Vec::new(),
+        }
+    }
+
+    /*
+     * Restores the pages written to since the last reset.
+     */
+    fn reset(&mut self, image: &[i64]) {
+        let mem = &mut self.machine.mem;
+        for page in self.dirty_pages.drain(..) {
+            self.dirty[page] = false;
+            for addr in page * PAGE_SIZE..((page + 1) * PAGE_SIZE).min(mem.len()) {
+                mem[addr] = image.get(addr).cloned().unwrap_or(0);
+            }
+        }
+        self.machine.ip = 0;
+        self.machine.relative_base = 0;
+        self.machine.input_queue.clear();
+    }
+
+    fn query(&mut self, image: &[i64], inputs: &[i64], max_steps: Option<usize>) -> Result<Vec<i64>, QueryError> {
+        self.reset(image);
+        self.machine.extend_input(inputs.iter().cloned());
+
+        let Worker { machine, dirty, dirty_pages } = self;
+        let mut outputs = Vec::new();
+        let mut steps = 0;
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| loop {
+            if Some(steps) == max_steps {
+                return Err(QueryError::StepLimit);
+            }
+            let status = machine.step_with(|addr| {
+                let page = addr / PAGE_SIZE;
+                if !dirty[page] {
+                    dirty[page] = true;
+                    dirty_pages.push(page);
+                }
+            });
+            match status {
+                None => (),
+                Some(Status::Output(n)) => outputs.push(n),
+                Some(Status::Halt) => return Ok(()),
+                Some(Status::WaitingInput) => return Err(QueryError::WaitingInput),
+            }
+            steps += 1;
+        }));
+        match result {
+            Ok(result) => result.map(|_| outputs),
+            Err(_) => {
+                // Don't trust the page tracking after a panic
+                self.dirty_pages = (0..self.dirty.len()).collect();
+                Err(QueryError::Crash)
+            }
+        }
+    }
+}
+
+impl Pool {
+    /*
+     * Creates a pool for a program, giving each machine `memory` cells.
+     */
+    pub fn new(program: &[i64], memory: usize) -> Pool {
+        Pool {
+            image: program.to_vec(),
+            memory,
+            max_steps: None,
+            pure: false,
+            worker: Worker::new(program, memory),
+        }
+    }
+
+    pub fn set_max_steps(&mut self, max_steps: Option<usize>) {
+        self.max_steps = max_steps;
+    }
+
+    /*
+     * Marks the program as computing a pure function of its inputs, which lets a cache remember the answers.
+     */
+    pub fn set_pure(&mut self, pure: bool) {
+        self.pure = pure;
+    }
+
+    /*
+     * Runs the program from scratch on `inputs` until it halts, returning all of its outputs.
+     */
+    pub fn query(&mut self, inputs: &[i64]) -> Result<Vec<i64>, QueryError> {
+        self.worker.query(&self.image, inputs, self.max_steps)
+    }
+
+    /*
+     * Answers a batch of queries, split between `threads` threads.
+     * The results are in the same order as the queries.
+     */
+    pub fn query_batch(&self, batch: &[Vec<i64>], threads: usize) -> Vec<Result<Vec<i64>, QueryError>> {
+        if batch.is_empty() {
+            return Vec::new();
+        }
+        let chunk_size = batch.len().div_ceil(threads.max(1));
+        thread::scope(|scope| {
+            let handles: Vec<_> = batch.chunks(chunk_size).map(|chunk| {
+                scope.spawn(move || {
+                    let mut worker = Worker::new(&self.image, self.memory);
+                    chunk.iter()
+                        .map(|inputs| worker.query(&self.image, inputs, self.max_steps))
+                        .collect::<Vec<_>>()
+                })
+            }).collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        })
+    }
+}
+
+impl Query for Pool {
+    fn query(&mut self, inputs: &[i64]) -> Result<Vec<i64>, QueryError> {
+        Pool::query(self, inputs)
+    }
+
+    fn is_pure(&self) -> bool {
+        self.pure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::compiler;
+
+    #[test]
+    fn query() {
+        // Adds the input to a counter kept in memory and on the stack
+        let program = compiler::compile("
+            var count = 0;
+            var history[100];
+            fn add(n) { var total = count + n; history[count] = total; return total; }
+            fn main() { count = add(input()); output(count); output(count * 2); }").unwrap();
+        let mut pool = Pool::new(&program, program.len() + 1000);
+        assert_eq!(pool.query(&[5]), Ok(vec![5, 10]));
+        assert_eq!(pool.query(&[5]), Ok(vec![5, 10]));
+        assert_eq!(pool.query(&[7]), Ok(vec![7, 14]));
+        assert_eq!(pool.query(&[]), Err(QueryError::WaitingInput));
+        assert_eq!(pool.query(&[1]), Ok(vec![1, 2]));
+        // Array accesses patch the code, which has to be undone as well
+        pool.worker.reset(&program);
+        assert_eq!(pool.worker.machine.mem[..program.len()], program[..]);
+        assert!(pool.worker.machine.mem[program.len()..].iter().all(|n| *n == 0));
+
+        let batch: Vec<Vec<i64>> = (0..50).map(|n| vec![n]).collect();
+        let expected: Vec<_> = batch.iter().map(|inputs| pool.query(inputs)).collect();
+        assert_eq!(pool.query_batch(&batch, 4), expected);
+        assert_eq!(pool.query_batch(&batch, 1), expected);
+        assert_eq!(pool.query_batch(&[], 4), []);
+    }
+
+    #[test]
+    fn errors() {
+        let mut pool = Pool::new(&[1105, 1, 0], 10);
+        pool.set_max_steps(Some(100));
+        assert_eq!(pool.query(&[]), Err(QueryError::StepLimit));
+
+        // Writes past the end of memory; the input it stored must still be undone
+        let mut pool = Pool::new(&[3, 9, 1, 9, 9, 1000, 4, 9, 99, 0], 0);
+        assert_eq!(pool.query(&[2]), Err(QueryError::Crash));
+        assert_eq!(pool.query(&[3]), Err(QueryError::Crash));
+        assert_eq!(pool.worker.machine.mem, [3, 9, 1, 9, 9, 1000, 4, 9, 99, 3]);
+    }
+}
@@ -0,0 +1,617 @@
+/*
+ * Symbolic execution of Intcode programs.
+ *
+ * Chosen memory cells and inputs are replaced by named symbols, and ADD/MUL build affine
+ * expressions over them (like `243000*noun + verb + 250702` for day 2). Comparisons and
+ * jumps that depend on symbols fork the machine, with each path remembering the constraints
+ * it took. Symbols can be given ranges, which are narrowed by the constraints and used to
+ * prune paths that can't happen.
+ *
+ * Reading through an address that depends on a symbol, or multiplying two symbols, gives an
+ * unknown value. Unknown values can be moved around and output, but a path ends as soon as
+ * one is needed to decide a branch, a jump target or a write address.
+ */
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Affine {
+    pub constant: i64,
+    // Coefficients of the symbols; never zero
+    pub terms: BTreeMap<String, i64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Affine(Affine),
+    Unknown,
+}
+
+/*
+ * Relation between an expression and zero.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Less,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Constraint {
+    pub expr: Affine,
+    pub relation: Relation,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum End {
+    Halt,
+    WaitingInput,
+    StepLimit,
+    // The path was still running when the limit on the number of paths was hit
+    Unexplored,
+    InvalidInstruction(usize),
+    SymbolicAddress(usize),
+    UnknownBranch(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Value>,
+    pub mem: Vec<Value>,
+    pub end: End,
+    pub steps: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct SymbolicMachine {
+    ip: usize,
+    relative_base: i64,
+    mem: Vec<Value>,
+    inputs: VecDeque<Value>,
+    outputs: Vec<Value>,
+    constraints: Vec<Constraint>,
+    ranges: BTreeMap<String, (i64, i64)>,
+    steps: usize,
+}
+
+impl Affine {
+    pub fn constant(n: i64) -> Affine {
+        Affine { constant: n, terms: BTreeMap::new() }
+    }
+
+    pub fn symbol(name: &str) -> Affine {
+        let mut terms = BTreeMap::new();
+        terms.insert(name.to_string(), 1);
+        Affine { constant: 0, terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        if self.terms.is_empty() { Some(self.constant) } else { None }
+    }
+
+    fn add(&self, other: &Affine) -> Option<Affine> {
+        let mut sum = self.clone();
+        sum.constant = sum.constant.checked_add(other.constant)?;
+        for (name, coefficient) in &other.terms {
+            let c = sum.terms.entry(name.clone()).or_insert(0);
+            *c = c.checked_add(*coefficient)?;
+            if *c == 0 {
+                sum.terms.remove(name);
+            }
+        }
+        Some(sum)
+    }
+
+    fn scale(&self, factor: i64) -> Option<Affine> {
+        if factor == 0 {
+            return Some(Affine::constant(0));
+        }
+        let mut scaled = Affine::constant(self.constant.checked_mul(factor)?);
+        for (name, coefficient) in &self.terms {
+            scaled.terms.insert(name.clone(), coefficient.checked_mul(factor)?);
+        }
+        Some(scaled)
+    }
+
+    fn sub(&self, other: &Affine) -> Option<Affine> {
+        self.add(&other.scale(-1)?)
+    }
+
+    /*
+     * Replaces a symbol by a value.
+     */
+    pub fn substitute(&self, name: &str, value: i64) -> Option<Affine> {
+        let mut result = self.clone();
+        if let Some(coefficient) = result.terms.remove(name) {
+            result.constant = result.constant.checked_add(coefficient.checked_mul(value)?)?;
+        }
+        Some(result)
+    }
+
+    /*
+     * Evaluates the expression with all of its symbols given a value.
+     */
+    pub fn evaluate(&self, values: &BTreeMap<String, i64>) -> Option<i64> {
+        let mut result = self.clone();
+        for (name, value) in values {
+            result = result.substitute(name, *value)?;
+        }
+        result.as_constant()
+    }
+
+    /*
+     * Finds the integer value of the only remaining symbol `name` that makes the expression equal `target`.
+     */
+    pub fn solve(&self, name: &str, target: i64) -> Option<i64> {
+        if self.terms.len() != 1 {
+            return None;
+        }
+        let coefficient = *self.terms.get(name)?;
+        let rest = target.checked_sub(self.constant)?;
+        if rest % coefficient == 0 { Some(rest / coefficient) } else { None }
+    }
+}
+
+impl fmt::Display for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "{}", self.constant);
+        }
+        for (i, (name, coefficient)) in self.terms.iter().enumerate() {
+            let sign = if *coefficient < 0 { "-" } else { "+" };
+            match (i, *coefficient) {
+                (0, 1) => write!(f, "{}", name)?,
+                (0, -1) => write!(f, "-{}", name)?,
+                (0, c) => write!(f, "{}*{}", c, name)?,
+                (_, 1) | (_, -1) => write!(f, " {} {}", sign, name)?,
+                (_, c) => write!(f, " {} {}*{}", sign, c.abs(), name)?,
+            }
+        }
+        match self.constant {
+            0 => Ok(()),
+            c if c < 0 => write!(f, " - {}", -(c as i128)),
+            c => write!(f, " + {}", c),
+        }
+    }
+}
+
+impl Value {
+    pub fn constant(n: i64) -> Value {
+        Value::Affine(Affine::constant(n))
+    }
+
+    pub fn symbol(name: &str) -> Value {
+        Value::Affine(Affine::symbol(name))
+    }
+
+    pub fn as_affine(&self) -> Option<&Affine> {
+        match self {
+            Value::Affine(affine) => Some(affine),
+            Value::Unknown => None,
+        }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        self.as_affine()?.as_constant()
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Affine(affine) => write!(f, "{}", affine),
+            Value::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+impl Relation {
+    fn negate(self) -> Relation {
+        match self {
+            Relation::Less => Relation::GreaterOrEqual,
+            Relation::GreaterOrEqual => Relation::Less,
+            Relation::Equal => Relation::NotEqual,
+            Relation::NotEqual => Relation::Equal,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relation = match self.relation {
+            Relation::Less => "<",
+            Relation::GreaterOrEqual => ">=",
+            Relation::Equal => "==",
+            Relation::NotEqual => "!=",
+        };
+        write!(f, "{} {} 0", self.expr, relation)
+    }
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) { q - 1 } else { q }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+impl SymbolicMachine {
+    pub fn new(program: &[i64]) -> SymbolicMachine {
+        SymbolicMachine {
+            ip: 0,
+            relative_base: 0,
+            mem: program.iter().map(|n| Value::constant(*n)).collect(),
+            inputs: VecDeque::new(),
+            outputs: Vec::new(),
+            constraints: Vec::new(),
+            ranges: BTreeMap::new(),
+            steps: 0,
+        }
+    }
+
+    /*
+     * Replaces the contents of a memory cell by a symbol.
+     */
+    pub fn symbolize(&mut self, addr: usize, name: &str) {
+        self.write(addr, Value::symbol(name));
+    }
+
+    /*
+     * Restricts the values a symbol can take to `min..=max`.
+     */
+    pub fn set_range(&mut self, name: &str, min: i64, max: i64) {
+        self.ranges.insert(name.to_string(), (min, max));
+    }
+
+    pub fn push_input(&mut self, value: Value) {
+        self.inputs.push_back(value);
+    }
+
+    fn range(&self, name: &str) -> (i64, i64) {
+        *self.ranges.get(name).unwrap_or(&(i64::MIN, i64::MAX))
+    }
+
+    /*
+     * Smallest and largest values an expression can take within the symbols' ranges.
+     */
+    fn bounds(&self, expr: &Affine) -> (i128, i128) {
+        let mut min = expr.constant as i128;
+        let mut max = expr.constant as i128;
+        for (name, coefficient) in &expr.terms {
+            let (lo, hi) = self.range(name);
+            let (a, b) = (*coefficient as i128 * lo as i128, *coefficient as i128 * hi as i128);
+            min = min.saturating_add(a.min(b));
+            max = max.saturating_add(a.max(b));
+        }
+        (min, max)
+    }
+
+    /*
+     * Adds a constraint to the path, narrowing the range of its symbol if there is only one.
+     * Returns false if the constraint can't hold.
+     */
+    fn assume(&mut self, expr: &Affine, relation: Relation) -> bool {
+        let (min, max) = self.bounds(expr);
+        let possible = match relation {
+            Relation::Less => min < 0,
+            Relation::GreaterOrEqual => max >= 0,
+            Relation::Equal => min <= 0 && max >= 0,
+            Relation::NotEqual => !(min == 0 && max == 0),
+        };
+        if !possible {
+            return false;
+        }
+
+        if expr.terms.len() == 1 {
+            let (name, a) = expr.terms.iter().next().unwrap();
+            let (a, c) = (*a as i128, expr.constant as i128);
+            let (lo, hi) = self.range(name);
+            let (mut lo, mut hi) = (lo as i128, hi as i128);
+            match relation {
+                // a*x + c < 0 is a*x <= -c - 1
+                Relation::Less if a > 0 => hi = hi.min(div_floor(-c - 1, a)),
+                Relation::Less => lo = lo.max(div_ceil(-c - 1, a)),
+                Relation::GreaterOrEqual if a > 0 => lo = lo.max(div_ceil(-c, a)),
+                Relation::GreaterOrEqual => hi = hi.min(div_floor(-c, a)),
+                Relation::Equal if (-c) % a != 0 => return false,
+                Relation::Equal => {
+                    lo = lo.max(-c / a);
+                    hi = hi.min(-c / a);
+                }
+                Relation::NotEqual if (-c) % a == 0 => {
+                    if lo == -c / a { lo += 1; }
+                    if hi == -c / a { hi -= 1; }
+                }
+                Relation::NotEqual => (),
+            }
+            if lo > hi {
+                return false;
+            }
+            self.ranges.insert(name.clone(), (lo as i64, hi as i64));
+        }
+        self.constraints.push(Constraint { expr: expr.clone(), relation });
+        true
+    }
+
+    /*
+     * Decides whether `expr <relation> 0` holds. If that depends on the symbols,
+     * the machine assumes it does and returns a copy that assumes it doesn't.
+     */
+    fn decide(&mut self, expr: &Affine, relation: Relation) -> Result<(bool, Option<SymbolicMachine>), End> {
+        let mut other = self.clone();
+        let holds = self.assume(expr, relation);
+        let fails = other.assume(expr, relation.negate());
+        match (holds, fails) {
+            (true, true) => Ok((true, Some(other))),
+            (true, false) => Ok((true, None)),
+            (false, true) => {
+                *self = other;
+                Ok((false, None))
+            }
+            // Every path has to go one way or the other; this only happens on overflow
+            (false, false) => Err(End::UnknownBranch(self.ip)),
+        }
+    }
+
+    fn read(&self, addr: usize) -> Value {
+        self.mem.get(addr).cloned().unwrap_or_else(|| Value::constant(0))
+    }
+
+    fn write(&mut self, addr: usize, value: Value) {
+        if addr >= self.mem.len() {
+            self.mem.resize(addr + 1, Value::constant(0));
+        }
+        self.mem[addr] = value;
+    }
+
+    /*
+     * Address of a position or relative mode parameter, if it doesn't depend on a symbol.
+     */
+    fn address(&self, mode: i64, raw: &Value) -> Result<usize, End> {
+        let raw = raw.as_constant().ok_or(End::SymbolicAddress(self.ip))?;
+        let addr = if mode == 2 { self.relative_base + raw } else { raw };
+        if addr < 0 { Err(End::InvalidInstruction(self.ip)) } else { Ok(addr as usize) }
+    }
+
+    fn operand(&self, mode: i64, raw: &Value) -> Value {
+        match mode {
+            1 => raw.clone(),
+            _ => match self.address(mode, raw) {
+                Ok(addr) => self.read(addr),
+                Err(_) => Value::Unknown,
+            },
+        }
+    }
+
+    /*
+     * Executes one instruction. Returns the other side of a fork, if there was one.
+     */
+    fn step(&mut self) -> Result<Option<SymbolicMachine>, End> {
+        let invalid = End::InvalidInstruction(self.ip);
+        let instruction = self.read(self.ip).as_constant().ok_or(End::InvalidInstruction(self.ip))?;
+        let opcode = instruction % 100;
+        let num_params = match opcode {
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => return Err(End::Halt),
+            _ => return Err(invalid),
+        };
+        let modes: Vec<i64> = (0..num_params).map(|i| (instruction / 10i64.pow(2 + i as u32)) % 10).collect();
+        if modes.iter().any(|m| *m > 2) {
+            return Err(invalid);
+        }
+        let raw: Vec<Value> = (0..num_params).map(|i| self.read(self.ip + 1 + i)).collect();
+        let arg = |i: usize| self.operand(modes[i], &raw[i]);
+        let dest = |i: usize| if modes[i] == 1 { Err(End::InvalidInstruction(self.ip)) } else { self.address(modes[i], &raw[i]) };
+
+        let mut next_ip = self.ip + 1 + num_params;
+        let mut fork = None;
+        match opcode {
+            1 | 2 => {
+                let (a, b) = (arg(0), arg(1));
+                let result = match (&a, &b) {
+                    (Value::Affine(a), Value::Affine(b)) if opcode == 1 => a.add(b).map(Value::Affine),
+                    (Value::Affine(a), Value::Affine(b)) => match (a.as_constant(), b.as_constant()) {
+                        (Some(n), _) => b.scale(n).map(Value::Affine),
+                        (_, Some(n)) => a.scale(n).map(Value::Affine),
+                        _ => None,
+                    },
+                    _ if opcode == 2 && (a.as_constant() == Some(0) || b.as_constant() == Some(0)) => Some(Value::constant(0)),
+                    _ => None,
+                };
+                let addr = dest(2)?;
+                self.write(addr, result.unwrap_or(Value::Unknown));
+            }
+            3 => {
+                let addr = dest(0)?;
+                let input = self.inputs.pop_front().ok_or(End::WaitingInput)?;
+                self.write(addr, input);
+            }
+            4 => {
+                let value = arg(0);
+                self.outputs.push(value);
+            }
+            5 | 6 => {
+                let condition = match arg(0) {
+                    Value::Affine(affine) => affine,
+                    Value::Unknown => return Err(End::UnknownBranch(self.ip)),
+                };
+                let target = arg(1).as_constant().ok_or(End::SymbolicAddress(self.ip))?;
+                if target < 0 {
+                    return Err(invalid);
+                }
+                let relation = if opcode == 5 { Relation::NotEqual } else { Relation::Equal };
+                let (jump, other) = self.decide(&condition, relation)?;
+                if let Some(mut other) = other {
+                    // The other path doesn't jump
+                    other.ip = next_ip;
+                    other.steps += 1;
+                    fork = Some(other);
+                }
+                if jump {
+                    next_ip = target as usize;
+                }
+            }
+            7 | 8 => {
+                let addr = dest(2)?;
+                let result = match (arg(0), arg(1)) {
+                    (Value::Affine(a), Value::Affine(b)) => {
+                        let difference = a.sub(&b).ok_or(End::UnknownBranch(self.ip))?;
+                        let relation = if opcode == 7 { Relation::Less } else { Relation::Equal };
+                        let (holds, other) = self.decide(&difference, relation)?;
+                        if let Some(mut other) = other {
+                            other.write(addr, Value::constant(0));
+                            other.ip = next_ip;
+                            other.steps += 1;
+                            fork = Some(other);
+                        }
+                        Value::constant(holds as i64)
+                    }
+                    _ => Value::Unknown,
+                };
+                self.write(addr, result);
+            }
+            9 => {
+                let offset = arg(0).as_constant().ok_or(End::SymbolicAddress(self.ip))?;
+                self.relative_base += offset;
+            }
+            _ => unreachable!(),
+        }
+        self.ip = next_ip;
+        self.steps += 1;
+        Ok(fork)
+    }
+
+    fn finish(self, end: End) -> Path {
+        Path {
+            constraints: self.constraints,
+            outputs: self.outputs,
+            mem: self.mem,
+            end,
+            steps: self.steps,
+        }
+    }
+
+    /*
+     * Explores every path through the program, up to `max_paths` paths of `max_steps` instructions each.
+     */
+    pub fn explore(self, max_steps: usize, max_paths: usize) -> Vec<Path> {
+        let mut paths = Vec::new();
+        let mut pending = vec![self];
+        while let Some(mut machine) = pending.pop() {
+            if paths.len() + pending.len() >= max_paths {
+                paths.push(machine.finish(End::Unexplored));
+                continue;
+            }
+            let end = loop {
+                if machine.steps >= max_steps {
+                    break End::StepLimit;
+                }
+                match machine.step() {
+                    Ok(None) => (),
+                    Ok(Some(other)) => pending.push(other),
+                    Err(end) => break end,
+                }
+            };
+            paths.push(machine.finish(end));
+        }
+        paths
+    }
+}
+
+impl Path {
+    pub fn read(&self, addr: usize) -> Value {
+        self.mem.get(addr).cloned().unwrap_or_else(|| Value::constant(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode;
+
+    fn parse(s: &str) -> Vec<i64> {
+        intcode::parse_program(s).unwrap()
+    }
+
+    #[test]
+    fn closed_form() {
+        // mem[0] = mem[noun] + mem[verb]; mem[0] = noun * mem[17] + verb + mem[18]
+        let program = parse("1,0,0,3,2,1,17,3,1,3,2,0,1,0,18,0,99,243000,250702");
+        let mut machine = SymbolicMachine::new(&program);
+        machine.symbolize(1, "noun");
+        machine.symbolize(2, "verb");
+        let paths = machine.explore(1000, 10);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].end, End::Halt);
+        let output = paths[0].read(0);
+        assert_eq!(output.to_string(), "243000*noun + verb + 250702");
+
+        let output = output.as_affine().unwrap();
+        assert_eq!(output.substitute("noun", 80).unwrap().solve("verb", 19_690_720), Some(18));
+        let values = [("noun".to_string(), 12), ("verb".to_string(), 2)].iter().cloned().collect();
+        assert_eq!(output.evaluate(&values), Some(3_166_704));
+    }
+
+    #[test]
+    fn inversion() {
+        // Outputs 7 - 3 * input
+        let program = parse("3,13,1002,13,-3,13,1001,13,7,13,4,13,99,0");
+        let mut machine = SymbolicMachine::new(&program);
+        machine.push_input(Value::symbol("x"));
+        let paths = machine.explore(100, 10);
+        let output = paths[0].outputs[0].as_affine().unwrap();
+        assert_eq!(output.to_string(), "-3*x + 7");
+        assert_eq!(output.solve("x", -26), Some(11));
+        assert_eq!(output.solve("x", -25), None);
+    }
+
+    #[test]
+    fn branches() {
+        // Outputs whether the input equals 8
+        let program = parse("3,9,8,9,10,9,4,9,99,-1,8");
+        let mut machine = SymbolicMachine::new(&program);
+        machine.push_input(Value::symbol("input"));
+        let mut paths = machine.explore(100, 10);
+        paths.sort_by_key(|p| p.outputs[0].as_constant());
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].constraints[0].to_string(), "input - 8 != 0");
+        assert_eq!(paths[0].outputs, [Value::constant(0)]);
+        assert_eq!(paths[1].constraints[0].to_string(), "input - 8 == 0");
+        assert_eq!(paths[1].outputs, [Value::constant(1)]);
+
+        // Counts up to the input, which can't be bigger than 3
+        let program = intcode::compiler::compile("fn main() { var n = input(); var i = 0; while (i < n) { i = i + 1; } output(i); }").unwrap();
+        let mut machine = SymbolicMachine::new(&program);
+        machine.push_input(Value::symbol("n"));
+        machine.set_range("n", 0, 3);
+        let mut outputs: Vec<i64> = machine.explore(10_000, 10)
+            .iter()
+            .map(|p| { assert_eq!(p.end, End::Halt); p.outputs[0].as_constant().unwrap() })
+            .collect();
+        outputs.sort();
+        assert_eq!(outputs, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn unknowns() {
+        // Writes through an address taken from the input
+        let mut machine = SymbolicMachine::new(&parse("3,5,1101,1,1,0,99"));
+        machine.push_input(Value::symbol("addr"));
+        assert_eq!(machine.explore(100, 10)[0].end, End::SymbolicAddress(2));
+
+        // The square of the input isn't affine, but it can still be output
+        let mut machine = SymbolicMachine::new(&parse("3,9,2,9,9,9,4,9,99,0"));
+        machine.push_input(Value::symbol("x"));
+        let path = &machine.explore(100, 10)[0];
+        assert_eq!((&path.end, &path.outputs[..]), (&End::Halt, &[Value::Unknown][..]));
+    }
+}
@@ -3,7 +3,13 @@ extern crate aoc_runner;
 #[macro_use]
 extern crate aoc_runner_derive;
 
+pub mod adventure;
+pub mod arcade;
+pub mod image;
 pub mod intcode;
+pub mod ocr;
+pub mod robot;
+pub mod sif;
 pub mod day01;
 pub mod day02;
 pub mod day03;
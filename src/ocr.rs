@@ -0,0 +1,168 @@
+/*
+ * Reads the block capitals some puzzles draw as their answer (days 8 and 11).
+ *
+ * The letters are 6 pixels tall and 4 wide with a blank column after each, except for Y,
+ * which takes up all 5 columns.
+ */
+
+use std::error::Error;
+use std::fmt;
+
+const HEIGHT: usize = 6;
+const PITCH: usize = 5;
+
+const GLYPHS: [(char, &str); 18] = [
+    ('A', ".##..#..#.#..#.####.#..#.#..#."),
+    ('B', "###..#..#.###..#..#.#..#.###.."),
+    ('C', ".##..#..#.#....#....#..#..##.."),
+    ('E', "####.#....###..#....#....####."),
+    ('F', "####.#....###..#....#....#...."),
+    ('G', ".##..#..#.#....#.##.#..#..###."),
+    ('H', "#..#.#..#.####.#..#.#..#.#..#."),
+    ('I', ".###...#....#....#....#...###."),
+    ('J', "..##....#....#....#.#..#..##.."),
+    ('K', "#..#.#.#..##...#.#..#.#..#..#."),
+    ('L', "#....#....#....#....#....####."),
+    ('O', ".##..#..#.#..#.#..#.#..#..##.."),
+    ('P', "###..#..#.#..#.###..#....#...."),
+    ('R', "###..#..#.#..#.###..#.#..#..#."),
+    ('S', ".###.#....#.....##.....#.###.."),
+    ('U', "#..#.#..#.#..#.#..#.#..#..##.."),
+    ('Y', "#...##...#.#.#...#....#....#.."),
+    ('Z', "####....#...#...#...#....####."),
+];
+
+/*
+ * What couldn't be read, with '?' in the text for every glyph that wasn't recognised.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct Unrecognized {
+    pub text: String,
+    // Drawn with '#' and '.', a line per row
+    pub glyphs: Vec<String>,
+}
+
+impl fmt::Display for Unrecognized {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not recognise {} of the letters in \"{}\":", self.glyphs.len(), self.text)?;
+        for glyph in &self.glyphs {
+            write!(f, "\n\n{}", glyph.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Unrecognized {}
+
+fn lit(grid: &[Vec<bool>], x: i64, y: usize) -> bool {
+    x >= 0 && grid.get(y).and_then(|row| row.get(x as usize)).cloned().unwrap_or(false)
+}
+
+fn draw(grid: &[Vec<bool>], left: i64, top: usize, width: usize, height: usize) -> String {
+    let mut picture = String::new();
+    for y in top..top + height {
+        for x in left..left + width as i64 {
+            picture.push(if lit(grid, x, y) { '#' } else { '.' });
+        }
+        picture.push('\n');
+    }
+    picture
+}
+
+/*
+ * Reads the letters in cells of PITCH columns, starting from column `left`.
+ */
+fn read_from(grid: &[Vec<bool>], left: i64, right: i64, top: usize) -> Result<String, Unrecognized> {
+    let mut text = String::new();
+    let mut glyphs = Vec::new();
+    let mut x = left;
+    while x <= right {
+        let pixels: String = draw(grid, x, top, PITCH, HEIGHT).lines().collect();
+        match GLYPHS.iter().find(|(_, glyph)| *glyph == pixels) {
+            Some((letter, _)) => text.push(*letter),
+            None => {
+                text.push('?');
+                glyphs.push(draw(grid, x, top, PITCH, HEIGHT));
+            }
+        }
+        x += PITCH as i64;
+    }
+    if glyphs.is_empty() { Ok(text) } else { Err(Unrecognized { text, glyphs }) }
+}
+
+/*
+ * Reads a line of letters from a grid of pixels, given as rows with true for lit pixels.
+ * There can be any amount of blank space around the letters.
+ */
+pub fn read(grid: &[Vec<bool>]) -> Result<String, Unrecognized> {
+    let rows: Vec<usize> = (0..grid.len()).filter(|&y| grid[y].contains(&true)).collect();
+    let (top, bottom) = match (rows.first(), rows.last()) {
+        (Some(&top), Some(&bottom)) => (top, bottom),
+        _ => return Ok(String::new()),
+    };
+    let columns = grid.iter().flat_map(|row| (0..row.len()).filter(move |&x| row[x]));
+    let left = columns.clone().min().unwrap() as i64;
+    let right = columns.max().unwrap() as i64;
+    if bottom - top >= HEIGHT {
+        let glyph = draw(grid, left, top, (right - left + 1) as usize, bottom - top + 1);
+        return Err(Unrecognized { text: "?".to_string(), glyphs: vec![glyph] });
+    }
+
+    // I and J start with blank columns, so the first letter may start a little left of the first lit pixel
+    let first = read_from(grid, left, right, top);
+    if first.is_ok() {
+        return first;
+    }
+    (1..=2).map(|shift| read_from(grid, left - shift, right, top)).find(|text| text.is_ok()).unwrap_or(first)
+}
+
+/*
+ * Reads letters drawn in text, with '#' for lit pixels.
+ */
+pub fn read_str(picture: &str) -> Result<String, Unrecognized> {
+    let grid: Vec<Vec<bool>> = picture.lines().map(|line| line.chars().map(|c| c == '#').collect()).collect();
+    read(&grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn letters() {
+        let alphabet: String = GLYPHS.iter().map(|(letter, _)| *letter).collect();
+        let grid: Vec<Vec<bool>> = (0..HEIGHT).map(|y| {
+            GLYPHS.iter().flat_map(|(_, glyph)| glyph[y * PITCH..(y + 1) * PITCH].chars().map(|c| c == '#')).collect()
+        }).collect();
+        assert_eq!(read(&grid), Ok(alphabet));
+
+        assert_eq!(read_str("\
+..........
+...###.#..
+....#..#..
+....#..#..
+....#..#..
+....#..#..
+...###.####
+"), Ok("IL".to_string()));
+        assert_eq!(read_str(""), Ok(String::new()));
+    }
+
+    #[test]
+    fn unrecognized() {
+        let error = read_str("\
+#..#..#..#
+#..#..#..#
+####..#..#
+#..#..#..#
+#..#..#..#
+#..#...##.
+").unwrap_err();
+        assert_eq!(error.text, "H?");
+        assert_eq!(error.glyphs, [".#..#\n.#..#\n.#..#\n.#..#\n.#..#\n..##.\n"]);
+        assert_eq!(error.to_string(), "could not recognise 1 of the letters in \"H?\":\n\n.#..#\n.#..#\n.#..#\n.#..#\n.#..#\n..##.");
+
+        let error = read_str("##\n#\n#\n#\n#\n#\n#\n").unwrap_err();
+        assert_eq!(error.glyphs, ["##\n#.\n#.\n#.\n#.\n#.\n#.\n"]);
+    }
+}
@@ -0,0 +1,322 @@
+/*
+ * Framework for the Intcode robots that move around a grid (days 11, 15 and 17).
+ *
+ * A `Robot` describes the protocol: how a command is sent to the machine as input, and what the
+ * response (everything output until the machine wants the next command) changes in the `World`
+ * it is mapping.
+ * A `Driver` owns the machine and does the running, so a new robot only needs its protocol.
+ *
+ * Positions are `Complex<i64>` in screen coordinates, with y growing downwards.
+ */
+
+extern crate num;
+
+use num::complex::Complex;
+use std::collections::HashMap;
+
+use crate::intcode;
+use crate::intcode::IntCode;
+
+pub type Position = Complex<i64>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    North,
+    South,
+    West,
+    East,
+}
+
+/*
+ * Sparse grid of cells, growing in every direction as the robot discovers it.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct World<T> {
+    cells: HashMap<Position, T>,
+}
+
+pub trait Robot {
+    // What the world is made of
+    type Cell;
+    // What can be asked of the robot
+    type Command;
+
+    fn encode(&self, command: &Self::Command) -> Vec<i64>;
+
+    /*
+     * Applies a response to the robot and the world. `command` is None for what the machine
+     * outputs before it first asks for input.
+     */
+    fn update(&mut self, command: Option<&Self::Command>, outputs: &[i64], world: &mut World<Self::Cell>);
+
+    /*
+     * What the robot does next when left to itself, or None to stop.
+     */
+    fn next(&mut self, _world: &World<Self::Cell>) -> Option<Self::Command> {
+        None
+    }
+}
+
+pub struct Driver<R: Robot> {
+    machine: IntCode,
+    robot: R,
+    world: World<R::Cell>,
+    halted: bool,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::North, Direction::South, Direction::West, Direction::East];
+
+    pub fn offset(self) -> Position {
+        match self {
+            Direction::North => Complex::new(0, -1),
+            Direction::South => Complex::new(0, 1),
+            Direction::West => Complex::new(-1, 0),
+            Direction::East => Complex::new(1, 0),
+        }
+    }
+
+    pub fn from_offset(offset: Position) -> Option<Direction> {
+        Direction::ALL.iter().cloned().find(|direction| direction.offset() == offset)
+    }
+
+    // With y pointing down, turning left is multiplying by -i
+    pub fn turn_left(self) -> Direction {
+        Direction::from_offset(self.offset() * Complex::new(0, -1)).unwrap()
+    }
+
+    pub fn turn_right(self) -> Direction {
+        Direction::from_offset(self.offset() * Complex::new(0, 1)).unwrap()
+    }
+}
+
+impl<T> World<T> {
+    pub fn new() -> World<T> {
+        World { cells: HashMap::new() }
+    }
+
+    pub fn get(&self, position: Position) -> Option<&T> {
+        self.cells.get(&position)
+    }
+
+    pub fn set(&mut self, position: Position, cell: T) {
+        self.cells.insert(position, cell);
+    }
+
+    pub fn contains(&self, position: Position) -> bool {
+        self.cells.contains_key(&position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.cells.iter().map(|(position, cell)| (*position, cell))
+    }
+
+    /*
+     * Top-left and bottom-right corners of the known cells, or None if there are none.
+     */
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let min_x = self.cells.keys().map(|p| p.re).min()?;
+        let max_x = self.cells.keys().map(|p| p.re).max()?;
+        let min_y = self.cells.keys().map(|p| p.im).min()?;
+        let max_y = self.cells.keys().map(|p| p.im).max()?;
+        Some((Complex::new(min_x, min_y), Complex::new(max_x, max_y)))
+    }
+
+    /*
+     * Rows of the known part of the world, with `f` applied to every cell.
+     */
+    pub fn grid<U, F: Fn(Option<&T>) -> U>(&self, f: F) -> Vec<Vec<U>> {
+        match self.bounds() {
+            Some((min, max)) => (min.im..=max.im)
+                .map(|y| (min.re..=max.re).map(|x| f(self.get(Complex::new(x, y)))).collect())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /*
+     * Draws the known part of the world, one character per cell.
+     */
+    pub fn render<F: Fn(Option<&T>) -> char>(&self, symbol: F) -> String {
+        self.grid(symbol).into_iter().map(|row| row.into_iter().chain(Some('\n')).collect::<String>()).collect()
+    }
+}
+
+impl<T> Default for World<T> {
+    fn default() -> World<T> {
+        World::new()
+    }
+}
+
+// Not derived, since the cells have to be Clone too
+impl<R: Robot + Clone> Clone for Driver<R> where R::Cell: Clone {
+    fn clone(&self) -> Driver<R> {
+        Driver { machine: self.machine.clone(), robot: self.robot.clone(), world: self.world.clone(), halted: self.halted }
+    }
+}
+
+impl<R: Robot> Driver<R> {
+    /*
+     * Boots the robot, running the machine until it first asks for a command.
+     */
+    pub fn new(machine: IntCode, robot: R) -> Driver<R> {
+        let mut driver = Driver { machine, robot, world: World::new(), halted: false };
+        driver.respond(None);
+        driver
+    }
+
+    pub fn robot(&self) -> &R {
+        &self.robot
+    }
+
+    pub fn world(&self) -> &World<R::Cell> {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World<R::Cell> {
+        &mut self.world
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    fn respond(&mut self, command: Option<&R::Command>) {
+        let mut outputs = Vec::new();
+        loop {
+            match self.machine.execute() {
+                intcode::Status::Output(n) => outputs.push(n),
+                intcode::Status::WaitingInput => break,
+                intcode::Status::Halt => {
+                    self.halted = true;
+                    break;
+                }
+            }
+        }
+        if command.is_some() || !outputs.is_empty() {
+            self.robot.update(command, &outputs, &mut self.world);
+        }
+    }
+
+    /*
+     * Sends a command and applies the response. Returns whether the robot is still running.
+     */
+    pub fn send(&mut self, command: R::Command) -> bool {
+        if self.halted {
+            panic!("Robot was sent a command after halting");
+        }
+        for value in self.robot.encode(&command) {
+            self.machine.push_input(value);
+        }
+        self.respond(Some(&command));
+        !self.halted
+    }
+
+    /*
+     * Lets the robot go about its business until it halts or has nothing left to do.
+     */
+    pub fn run(&mut self) {
+        while !self.halted {
+            match self.robot.next(&self.world) {
+                Some(command) => self.send(command),
+                None => break,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::compiler;
+
+    #[test]
+    fn directions() {
+        assert_eq!(Direction::North.turn_left(), Direction::West);
+        assert_eq!(Direction::West.turn_left(), Direction::South);
+        assert_eq!(Direction::North.turn_right(), Direction::East);
+        assert_eq!(Direction::South.turn_right(), Direction::West);
+        assert_eq!(Direction::from_offset(Complex::new(0, 1)), Some(Direction::South));
+        assert_eq!(Direction::from_offset(Complex::new(1, 1)), None);
+    }
+
+    #[test]
+    fn world() {
+        let mut world = World::new();
+        assert_eq!(world.bounds(), None);
+        assert_eq!(world.render(|_: Option<&bool>| '?'), "");
+        world.set(Complex::new(-1, 2), true);
+        world.set(Complex::new(1, 3), false);
+        assert_eq!(world.bounds(), Some((Complex::new(-1, 2), Complex::new(1, 3))));
+        let picture = world.render(|cell| match cell {
+            Some(true) => '#',
+            Some(false) => '.',
+            None => ' ',
+        });
+        assert_eq!(picture, "#  \n  .\n");
+        assert_eq!(world.grid(|cell| cell.is_some()), [[true, false, false], [false, false, true]]);
+    }
+
+    // Walks where it's told, and reports how many steps it has taken so far
+    #[derive(Clone)]
+    struct Walker {
+        position: Position,
+    }
+
+    impl Robot for Walker {
+        type Cell = i64;
+        type Command = Direction;
+
+        fn encode(&self, command: &Direction) -> Vec<i64> {
+            vec![Direction::ALL.iter().position(|d| d == command).unwrap() as i64]
+        }
+
+        fn update(&mut self, command: Option<&Direction>, outputs: &[i64], world: &mut World<i64>) {
+            if let Some(direction) = command {
+                self.position += direction.offset();
+            }
+            world.set(self.position, outputs[0]);
+        }
+
+        fn next(&mut self, world: &World<i64>) -> Option<Direction> {
+            if world.len() < 3 { Some(Direction::East) } else { None }
+        }
+    }
+
+    #[test]
+    fn driver() {
+        let program = compiler::compile("
+            fn main() {
+                var steps = 0;
+                output(steps);
+                while (steps < 5) { input(); steps = steps + 1; output(steps); }
+            }").unwrap();
+        let mut machine = IntCode::new(program);
+        machine.resize(machine.memory_size() + 100);
+        let mut driver = Driver::new(machine, Walker { position: Complex::new(0, 0) });
+        assert_eq!(driver.world().get(Complex::new(0, 0)), Some(&0));
+
+        // Cloning the driver clones the machine, so the copy can wander off on its own
+        let mut explorer = driver.clone();
+        assert!(explorer.send(Direction::South));
+        assert_eq!(explorer.world().get(Complex::new(0, 1)), Some(&1));
+        assert!(!driver.world().contains(Complex::new(0, 1)));
+
+        driver.run();
+        assert_eq!(driver.world().render(|steps| steps.map_or(' ', |n| (b'0' + *n as u8) as char)), "012\n");
+        assert_eq!(driver.robot().position, Complex::new(2, 0));
+        assert!(!driver.is_halted());
+        assert!(driver.send(Direction::South));
+        assert!(driver.send(Direction::South));
+        assert!(!driver.send(Direction::South));
+        assert!(driver.is_halted());
+    }
+}
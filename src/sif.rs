@@ -0,0 +1,372 @@
+/*
+ * Space Image Format (day 8): a string of digits, one per pixel, in layers of width * height
+ * pixels each, with the first layer in front.
+ *
+ * By default pixels are 0 for black, 1 for white and 2 for transparent, but a `Palette` can give
+ * any digit a colour and make any one of them transparent. Compositing the layers shows
+ * the frontmost pixel that isn't transparent.
+ * When converted to an image, pixels that are still transparent become mid-grey.
+ */
+
+use std::error::Error;
+use std::fmt;
+
+use crate::image::{ColorImage, Image, Rgb};
+
+// Digits of the default palette
+pub const BLACK: u8 = 0;
+pub const WHITE: u8 = 1;
+pub const TRANSPARENT: u8 = 2;
+
+const GREY: Rgb = Rgb(128, 128, 128);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SifError {
+    ZeroSize,
+    NoLayers,
+    // The data doesn't divide into whole layers
+    Length { length: usize, layer: usize },
+    Digit { position: usize, found: char },
+    Size { expected: (usize, usize), found: (usize, usize) },
+}
+
+impl fmt::Display for SifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SifError::ZeroSize => write!(f, "image has no pixels"),
+            SifError::NoLayers => write!(f, "image has no layers"),
+            SifError::Length { length, layer } => {
+                write!(f, "{} pixels don't make whole layers of {} pixels", length, layer)
+            }
+            SifError::Digit { position, found } => write!(f, "invalid pixel {:?} at position {}", found, position),
+            SifError::Size { expected, found } => write!(f, "layer is {}x{}, but the image is {}x{}",
+                found.0, found.1, expected.0, expected.1),
+        }
+    }
+}
+
+impl Error for SifError {}
+
+/*
+ * Colours of the digits an image can use, and which digit is transparent.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [Option<Rgb>; 10],
+    transparent: u8,
+}
+
+/*
+ * How many pixels of each value a layer has.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LayerStats {
+    counts: [usize; 10],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SifImage {
+    width: usize,
+    height: usize,
+    layers: Vec<Vec<u8>>,
+    palette: Palette,
+}
+
+impl Palette {
+    /*
+     * A palette with only the transparent digit, to add colours to with `with`.
+     */
+    pub fn new(transparent: u8) -> Palette {
+        assert!(transparent <= 9, "Transparent digit {} isn't a digit", transparent);
+        Palette { colors: [None; 10], transparent }
+    }
+
+    pub fn with(mut self, digit: u8, color: Rgb) -> Palette {
+        assert!(digit <= 9 && digit != self.transparent, "Can't give digit {} a colour", digit);
+        self.colors[digit as usize] = Some(color);
+        self
+    }
+
+    pub fn transparent(&self) -> u8 {
+        self.transparent
+    }
+
+    pub fn color(&self, digit: u8) -> Option<Rgb> {
+        self.colors.get(digit as usize).cloned().flatten()
+    }
+
+    pub fn allows(&self, digit: u8) -> bool {
+        digit == self.transparent || self.color(digit).is_some()
+    }
+
+    /*
+     * The digit that looks closest to a colour, going by how bright it is.
+     * Mid-grey is transparent; ties go to the lowest digit, and to transparency before that.
+     */
+    pub fn nearest(&self, shade: u8) -> u8 {
+        let transparent = (self.transparent, GREY.luma());
+        let colors = (0..10).filter_map(|digit| self.color(digit).map(|color| (digit, color.luma())));
+        Some(transparent).into_iter().chain(colors)
+            .min_by_key(|(_, luma)| (*luma as i16 - shade as i16).abs())
+            .unwrap().0
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::new(TRANSPARENT).with(BLACK, Rgb::BLACK).with(WHITE, Rgb::WHITE)
+    }
+}
+
+impl LayerStats {
+    pub fn count(&self, digit: u8) -> usize {
+        self.counts.get(digit as usize).cloned().unwrap_or(0)
+    }
+}
+
+impl SifImage {
+    /*
+     * Makes an image out of layers of pixel values, checking that they are all the right size.
+     */
+    pub fn new(width: usize, height: usize, layers: Vec<Vec<u8>>) -> Result<SifImage, SifError> {
+        SifImage::with_palette(width, height, layers, Palette::default())
+    }
+
+    /*
+     * Like `new`, but with pixels from the given palette.
+     */
+    pub fn with_palette(width: usize, height: usize, layers: Vec<Vec<u8>>, palette: Palette) -> Result<SifImage, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::ZeroSize);
+        }
+        if layers.is_empty() {
+            return Err(SifError::NoLayers);
+        }
+        for (i, layer) in layers.iter().enumerate() {
+            if layer.len() != width * height {
+                return Err(SifError::Length { length: layer.len(), layer: width * height });
+            }
+            if let Some(x) = layer.iter().position(|&pixel| !palette.allows(pixel)) {
+                let found = std::char::from_digit(layer[x] as u32, 10).unwrap_or('?');
+                return Err(SifError::Digit { position: i * width * height + x, found });
+            }
+        }
+        Ok(SifImage { width, height, layers, palette })
+    }
+
+    /*
+     * Reads an image from its digits, ignoring whitespace at the end like a trailing newline.
+     */
+    pub fn parse(data: &str, width: usize, height: usize) -> Result<SifImage, SifError> {
+        SifImage::parse_with_palette(data, width, height, Palette::default())
+    }
+
+    pub fn parse_with_palette(data: &str, width: usize, height: usize, palette: Palette) -> Result<SifImage, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::ZeroSize);
+        }
+        let data = data.trim_end();
+        let length = data.chars().count();
+        if !length.is_multiple_of(width * height) {
+            return Err(SifError::Length { length, layer: width * height });
+        }
+        let mut pixels = Vec::with_capacity(length);
+        for (position, c) in data.chars().enumerate() {
+            match c.to_digit(10) {
+                Some(digit) if palette.allows(digit as u8) => pixels.push(digit as u8),
+                _ => return Err(SifError::Digit { position, found: c }),
+            }
+        }
+        let layers = pixels.chunks(width * height).map(|layer| layer.to_vec()).collect();
+        SifImage::with_palette(width, height, layers, palette)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn layers(&self) -> &[Vec<u8>] {
+        &self.layers
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn stats(&self) -> Vec<LayerStats> {
+        self.layers.iter().map(|layer| {
+            let mut counts = [0; 10];
+            for &pixel in layer {
+                counts[pixel as usize] += 1;
+            }
+            LayerStats { counts }
+        }).collect()
+    }
+
+    /*
+     * The pixels seen through all the layers, which can still be transparent where every layer is.
+     */
+    pub fn composite(&self) -> Vec<u8> {
+        let transparent = self.palette.transparent;
+        (0..self.width * self.height).map(|i| {
+            self.layers.iter().map(|layer| layer[i]).find(|&pixel| pixel != transparent).unwrap_or(transparent)
+        }).collect()
+    }
+
+    /*
+     * The composite as a single-layer image.
+     */
+    pub fn flatten(&self) -> SifImage {
+        SifImage { width: self.width, height: self.height, layers: vec![self.composite()], palette: self.palette.clone() }
+    }
+
+    pub fn encode(&self) -> String {
+        self.layers.iter().flatten().map(|&pixel| (b'0' + pixel) as char).collect()
+    }
+
+    fn color(&self, pixel: u8) -> Rgb {
+        self.palette.color(pixel).unwrap_or(GREY)
+    }
+
+    fn paint(&self, pixels: &[u8]) -> ColorImage {
+        ColorImage::from_fn(self.width, self.height, |x, y| self.color(pixels[y * self.width + x]))
+    }
+
+    pub fn to_color_image(&self) -> ColorImage {
+        self.paint(&self.composite())
+    }
+
+    pub fn to_image(&self) -> Image {
+        self.to_color_image().to_grey()
+    }
+
+    pub fn layer_image(&self, layer: usize) -> Image {
+        self.paint(&self.layers[layer]).to_grey()
+    }
+
+    /*
+     * Draws the composite for a terminal with 24-bit colour.
+     */
+    pub fn to_ansi(&self) -> String {
+        self.to_color_image().to_ansi()
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8> {
+        self.to_color_image().to_ppm()
+    }
+
+    /*
+     * Makes a single-layer image from a greyscale one, reading mid-grey as transparent.
+     */
+    pub fn from_image(image: &Image) -> Result<SifImage, SifError> {
+        SifImage::from_images(std::slice::from_ref(image))
+    }
+
+    /*
+     * Makes an image with a layer from each greyscale image, frontmost first.
+     */
+    pub fn from_images(images: &[Image]) -> Result<SifImage, SifError> {
+        SifImage::from_images_with_palette(images, Palette::default())
+    }
+
+    /*
+     * Like `from_images`, with each pixel the digit of the palette closest to its shade of grey.
+     */
+    pub fn from_images_with_palette(images: &[Image], palette: Palette) -> Result<SifImage, SifError> {
+        let (width, height) = match images.first() {
+            Some(image) => (image.width(), image.height()),
+            None => return Err(SifError::NoLayers),
+        };
+        if let Some(image) = images.iter().find(|image| (image.width(), image.height()) != (width, height)) {
+            return Err(SifError::Size { expected: (width, height), found: (image.width(), image.height()) });
+        }
+        let layers = images.iter().map(|image| {
+            (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| palette.nearest(image.get(x, y))).collect()
+        }).collect();
+        SifImage::with_palette(width, height, layers, palette)
+    }
+}
+
+impl fmt::Display for SifImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.composite().chunks(self.width) {
+            let line: String = row.iter().map(|&pixel| match self.palette.color(pixel) {
+                Some(color) if color.luma() < 128 => ' ',
+                Some(_) => '#',
+                None => '.',
+            }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers() {
+        // The example from the puzzle
+        let image = SifImage::parse("0222112222120000\n", 2, 2).unwrap();
+        assert_eq!(image.layers().len(), 4);
+        assert_eq!(image.composite(), [0, 1, 1, 0]);
+        assert_eq!(image.to_string(), " #\n# \n");
+        assert_eq!(image.stats()[0].count(2), 3);
+        assert_eq!(image.stats()[3].count(0), 4);
+        assert_eq!(image.stats()[3].count(7), 0);
+
+        assert_eq!(SifImage::parse("0222112", 2, 2), Err(SifError::Length { length: 7, layer: 4 }));
+        assert_eq!(SifImage::parse("0223", 2, 2), Err(SifError::Digit { position: 3, found: '3' }));
+        assert_eq!(SifImage::parse("", 2, 2), Err(SifError::NoLayers));
+        assert_eq!(SifImage::parse("0", 0, 1), Err(SifError::ZeroSize));
+        assert_eq!(SifImage::parse("0223", 2, 2).unwrap_err().to_string(), "invalid pixel '3' at position 3");
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = "222201202221";
+        let image = SifImage::parse(data, 2, 2).unwrap();
+        assert_eq!(image.encode(), data);
+        assert_eq!(image.flatten().encode(), "0120");
+
+        let picture = image.to_image();
+        assert_eq!(picture, Image::from_fn(2, 2, |x, y| [[0, 255], [128, 0]][y][x]));
+        let png = Image::decode(&picture.to_png()).unwrap();
+        assert_eq!(SifImage::from_image(&png), Ok(image.flatten()));
+        let pgm = Image::decode(&picture.to_pgm()).unwrap();
+        assert_eq!(SifImage::from_image(&pgm), Ok(image.flatten()));
+
+        let layers: Vec<Image> = (0..3).map(|i| image.layer_image(i)).collect();
+        assert_eq!(SifImage::from_images(&layers), Ok(image));
+        assert_eq!(SifImage::from_images(&[Image::new(2, 2), Image::new(3, 2)]),
+            Err(SifError::Size { expected: (2, 2), found: (3, 2) }));
+    }
+
+    #[test]
+    fn palette() {
+        assert_eq!(Palette::default().nearest(64), TRANSPARENT);
+        assert_eq!(Palette::default().nearest(63), BLACK);
+        assert_eq!(Palette::default().nearest(192), WHITE);
+
+        // Red, green and blue over a transparent 0
+        let palette = Palette::new(0).with(3, Rgb(255, 0, 0)).with(5, Rgb(0, 255, 0)).with(9, Rgb(0, 0, 255));
+        let image = SifImage::parse_with_palette("30000509\n", 2, 2, palette.clone()).unwrap();
+        assert_eq!(image.composite(), [3, 5, 0, 9]);
+        assert_eq!(image.encode(), "30000509");
+        assert_eq!(image.to_string(), " #\n. \n");
+        assert_eq!(image.to_ansi(), "\x1b[48;2;255;0;0m  \x1b[48;2;0;255;0m  \x1b[0m\n\
+            \x1b[48;2;128;128;128m  \x1b[48;2;0;0;255m  \x1b[0m\n");
+        assert_eq!(String::from_utf8(image.to_ppm()).unwrap(), "P3\n2 2\n255\n255 0 0  0 255 0\n128 128 128  0 0 255\n");
+        assert_eq!(SifImage::parse_with_palette("3102", 2, 2, palette.clone()), Err(SifError::Digit { position: 1, found: '1' }));
+        assert_eq!(SifImage::parse("3", 1, 1), Err(SifError::Digit { position: 0, found: '3' }));
+
+        // Colours only survive a greyscale image if they're different shades
+        let layers: Vec<Image> = (0..2).map(|i| image.layer_image(i)).collect();
+        assert_eq!(SifImage::from_images_with_palette(&layers, palette), Ok(image));
+    }
+}